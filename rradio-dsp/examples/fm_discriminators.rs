@@ -0,0 +1,61 @@
+//! Speed and distortion comparison of the FM discriminators.
+//!
+//! cargo run --release -p rradio-dsp --example fm_discriminators
+
+use std::time::Instant;
+
+use num_complex::Complex32;
+use rradio_dsp::fm_demod::{
+    ConjugateProductDiscriminator, Discriminator, FastAtanDiscriminator, FmDemodulator,
+    QuadratureDiscriminator,
+};
+
+const FS: f64 = 1.2e6;
+const TONE: f64 = 1000.0;
+const DEVIATION: f64 = 75e3;
+const SECONDS: usize = 10;
+
+fn fm_tone(n: usize) -> Vec<Complex32> {
+    let index = DEVIATION / TONE;
+    (0..n).map(|i| {
+        let w = 2.0 * std::f64::consts::PI * TONE * i as f64 / FS;
+        Complex32::from_polar(0.5, (index * w.sin()) as f32)
+    }).collect()
+}
+
+/// THD over whole periods from harmonics 2–9.
+fn thd(samples: &[f32]) -> f64 {
+    let harmonic = |h: f64| {
+        let (re, im) = samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, &x)| {
+            let w = 2.0 * std::f64::consts::PI * h * TONE * i as f64 / FS;
+            (re + x as f64 * w.cos(), im + x as f64 * w.sin())
+        });
+        re * re + im * im
+    };
+    ((2..=9).map(|h| harmonic(h as f64)).sum::<f64>() / harmonic(1.0)).sqrt()
+}
+
+fn run(name: &str, mut d: impl Discriminator, input: &[Complex32]) {
+    let mut out = Vec::with_capacity(input.len());
+    let start = Instant::now();
+    out.extend(input.iter().map(|&s| d.process(s)));
+    let elapsed = start.elapsed();
+    std::hint::black_box(&out);
+
+    let period = (FS / TONE) as usize;
+    let distortion = thd(&out[period..11 * period]);
+    println!("{:<18} {:>7.2} ns/sample  THD {:>8.4}% ({:.1} dB)",
+        name,
+        elapsed.as_nanos() as f64 / input.len() as f64,
+        distortion * 100.0,
+        20.0 * distortion.log10());
+}
+
+fn main() {
+    let input = fm_tone(SECONDS * FS as usize);
+    println!("{} kHz deviation, {} Hz tone at {} MHz", DEVIATION / 1e3, TONE, FS / 1e6);
+    run("polar (atan2)", FmDemodulator::new(), &input);
+    run("conjugate product", ConjugateProductDiscriminator::new(), &input);
+    run("fast atan", FastAtanDiscriminator::new(), &input);
+    run("quadrature", QuadratureDiscriminator::new(), &input);
+}
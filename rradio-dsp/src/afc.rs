@@ -0,0 +1,202 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use num_complex::Complex32;
+
+use crate::osc::Osc;
+
+/// Carrier offset shared between the estimator and the NCO that removes it.
+///
+/// The offset is where the station sits relative to 0 Hz, so the NCO runs
+/// at its negative. Cloning shares the value, so it can also be read from
+/// other threads for reporting.
+#[derive(Debug, Clone, Default)]
+pub struct AfcHandle(Arc<AtomicU32>);
+
+impl AfcHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Estimated carrier offset in Hz.
+    pub fn offset(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set_offset(&self, offset: f32) {
+        self.0.store(offset.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Receiver frequency error in parts per million when tuned to `center`
+    /// Hz. Positive means the local oscillator runs fast, so stations appear
+    /// below where they should.
+    pub fn ppm(&self, center: f64) -> f64 {
+        if center == 0.0 {
+            return 0.0;
+        }
+        -(self.offset() as f64) / center * 1e6
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AfcConfig {
+    /// Averaging time per estimate in seconds.
+    pub block: f32,
+    /// Fraction of each measured residual applied to the NCO.
+    pub gain: f32,
+    /// Limit on the correction in Hz, so noise can't walk it off the station.
+    pub max_offset: f32,
+}
+
+impl Default for AfcConfig {
+    fn default() -> Self {
+        AfcConfig {
+            block: 0.1,
+            gain: 0.3,
+            max_offset: 25000.0,
+        }
+    }
+}
+
+/// Estimates the residual carrier offset from the DC level of FM
+/// discriminator output (phase step per sample in radians) and folds it
+/// into the shared handle. FM modulation averages to zero, so whatever mean
+/// is left is mistuning.
+pub struct AfcEstimator {
+    fs: f32,
+    config: AfcConfig,
+    block_len: usize,
+    sum: f64,
+    count: usize,
+    handle: AfcHandle,
+}
+
+impl AfcEstimator {
+    /// `fs` is the rate the discriminator runs at.
+    pub fn new(fs: f32, config: AfcConfig, handle: AfcHandle) -> Self {
+        let block_len = ((config.block * fs) as usize).max(1);
+        AfcEstimator { fs, config, block_len, sum: 0.0, count: 0, handle }
+    }
+
+    pub fn handle(&self) -> &AfcHandle {
+        &self.handle
+    }
+
+    /// Feed one discriminator output; returns the new offset when an estimate completes.
+    pub fn process(&mut self, demod: f32) -> Option<f32> {
+        self.sum += demod as f64;
+        self.count += 1;
+        if self.count < self.block_len {
+            return None;
+        }
+        let residual = (self.sum / self.count as f64) as f32 * self.fs / (2.0 * std::f32::consts::PI);
+        self.sum = 0.0;
+        self.count = 0;
+
+        let offset = (self.handle.offset() + self.config.gain * residual)
+            .clamp(-self.config.max_offset, self.config.max_offset);
+        self.handle.set_offset(offset);
+        Some(offset)
+    }
+}
+
+// Samples between re-reads of the handle
+const RETUNE_INTERVAL: usize = 1024;
+
+/// Mixes by the negative of the handle's offset, retuning as it changes.
+pub struct AfcMixIter<I> {
+    iter: I,
+    osc: Osc,
+    fs: f32,
+    handle: AfcHandle,
+    offset: f32,
+    until_retune: usize,
+}
+
+impl<I> Iterator for AfcMixIter<I> where I: Iterator<Item = Complex32> {
+    type Item = Complex32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.iter.next()?;
+        if self.until_retune == 0 {
+            let offset = self.handle.offset();
+            if offset != self.offset {
+                self.offset = offset;
+                self.osc.set_freq(-offset, self.fs);
+            }
+            self.until_retune = RETUNE_INTERVAL;
+        }
+        self.until_retune -= 1;
+        if self.offset == 0.0 {
+            return Some(sample);
+        }
+        Some(sample * self.osc.next())
+    }
+}
+
+pub trait AfcCorrectable: Iterator<Item = Complex32> + Sized {
+    fn afc_mix(self, handle: AfcHandle, sample_rate: f32) -> AfcMixIter<Self>;
+}
+
+impl<I> AfcCorrectable for I where I: Iterator<Item = Complex32> {
+    fn afc_mix(self, handle: AfcHandle, sample_rate: f32) -> AfcMixIter<Self> {
+        let offset = handle.offset();
+        AfcMixIter {
+            iter: self,
+            osc: Osc::new(-offset, sample_rate),
+            fs: sample_rate,
+            handle,
+            offset,
+            until_retune: RETUNE_INTERVAL,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fm_demod::FmDemodulatable;
+
+    const FS: f32 = 240000.0;
+
+    /// 1 kHz tone at 20 kHz deviation on a carrier `offset` Hz from centre.
+    fn fm_carrier(offset: f32, n: usize) -> impl Iterator<Item = Complex32> {
+        (0..n).map(move |i| {
+            let t = i as f64 / FS as f64;
+            let phase = 2.0 * std::f64::consts::PI * offset as f64 * t
+                + 20.0 * (2.0 * std::f64::consts::PI * 1000.0 * t).sin();
+            Complex32::from_polar(1.0, phase as f32)
+        })
+    }
+
+    #[test]
+    fn test_afc_converges() {
+        let handle = AfcHandle::new();
+        let mut estimator = AfcEstimator::new(FS, AfcConfig::default(), handle.clone());
+        let demoded: Vec<f32> = fm_carrier(3000.0, 3 * FS as usize)
+            .afc_mix(handle.clone(), FS)
+            .fm_demodulate()
+            .inspect(|&x| { estimator.process(x); })
+            .collect();
+
+        assert!((handle.offset() - 3000.0).abs() < 5.0, "offset {}", handle.offset());
+        // 3 kHz at 100 MHz: the oscillator is 30 ppm slow
+        assert!((handle.ppm(100e6) + 30.0).abs() < 0.1);
+        // Discriminator output is centred again
+        let tail = &demoded[demoded.len() - FS as usize / 10..];
+        let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+        assert!((mean * FS / (2.0 * std::f32::consts::PI)).abs() < 10.0, "residual {}", mean);
+    }
+
+    #[test]
+    fn test_afc_limit() {
+        let handle = AfcHandle::new();
+        let config = AfcConfig { max_offset: 1000.0, ..Default::default() };
+        let mut estimator = AfcEstimator::new(FS, config, handle.clone());
+        fm_carrier(3000.0, FS as usize)
+            .afc_mix(handle.clone(), FS)
+            .fm_demodulate()
+            .for_each(|x| { estimator.process(x); });
+        assert_eq!(handle.offset(), 1000.0);
+    }
+}
@@ -0,0 +1,192 @@
+use std::marker::PhantomData;
+
+use num_complex::Complex32;
+
+use crate::filterable::{Filter, Filterable};
+
+/// What the AGC regulates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AgcDetector {
+    /// Sample magnitude; keeps peaks at the target.
+    Peak,
+    /// Mean power; keeps RMS level at the target.
+    Rms,
+}
+
+#[derive(Debug, Clone)]
+pub struct AgcConfig {
+    /// Output level (peak or RMS, per `detector`).
+    pub target: f32,
+    /// Time constant in seconds for the level to follow a rising input.
+    pub attack: f32,
+    /// Time constant in seconds for the level to follow a falling input.
+    pub decay: f32,
+    /// Time in seconds to hold the level after a peak before decaying.
+    pub hang: f32,
+    pub detector: AgcDetector,
+    pub max_gain: f32,
+}
+
+impl Default for AgcConfig {
+    fn default() -> Self {
+        AgcConfig {
+            target: 1.0,
+            attack: 0.001,
+            decay: 0.1,
+            hang: 0.0,
+            detector: AgcDetector::Rms,
+            max_gain: 1e5,
+        }
+    }
+}
+
+/// Sample types the AGC can level.
+pub trait AgcSample: Filterable<Self> {
+    fn norm_sqr(&self) -> f32;
+}
+
+impl AgcSample for f32 {
+    fn norm_sqr(&self) -> f32 {
+        self * self
+    }
+}
+
+impl AgcSample for Complex32 {
+    fn norm_sqr(&self) -> f32 {
+        Complex32::norm_sqr(self)
+    }
+}
+
+/// Feed-forward automatic gain control with separate attack and decay and
+/// an optional hang.
+#[derive(Debug, Clone)]
+pub struct Agc<Num> {
+    target: f32,
+    detector: AgcDetector,
+    max_gain: f32,
+    attack_alpha: f32,
+    decay_alpha: f32,
+    hang_samples: usize,
+    hang_left: usize,
+    /// Detected magnitude (peak) or power (RMS).
+    level: f32,
+    gain: f32,
+    _num: PhantomData<Num>,
+}
+
+impl<Num> Agc<Num> where Num: AgcSample {
+    pub fn new(fs: f32, config: AgcConfig) -> Self {
+        let alpha = |tau: f32| if tau > 0.0 { 1.0 - (-1.0 / (tau * fs)).exp() } else { 1.0 };
+        // Start at the target so the initial gain is unity
+        let level = match config.detector {
+            AgcDetector::Peak => config.target,
+            AgcDetector::Rms => config.target * config.target,
+        };
+        Agc {
+            target: config.target,
+            detector: config.detector,
+            max_gain: config.max_gain,
+            attack_alpha: alpha(config.attack),
+            decay_alpha: alpha(config.decay),
+            hang_samples: (config.hang * fs) as usize,
+            hang_left: 0,
+            level,
+            gain: 1.0,
+            _num: PhantomData,
+        }
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Update the detector with `x` and return the gain to apply to it.
+    pub fn update(&mut self, x: Num) -> f32 {
+        let inst = match self.detector {
+            AgcDetector::Peak => x.norm_sqr().sqrt(),
+            AgcDetector::Rms => x.norm_sqr(),
+        };
+        if inst > self.level {
+            self.level += self.attack_alpha * (inst - self.level);
+            self.hang_left = self.hang_samples;
+        } else if self.hang_left > 0 {
+            self.hang_left -= 1;
+        } else {
+            self.level += self.decay_alpha * (inst - self.level);
+        }
+
+        let level = match self.detector {
+            AgcDetector::Peak => self.level,
+            AgcDetector::Rms => self.level.sqrt(),
+        };
+        if level > 1e-10 {
+            self.gain = (self.target / level).min(self.max_gain);
+        }
+        self.gain
+    }
+
+    pub fn process(&mut self, x: Num) -> Num {
+        x * self.update(x)
+    }
+}
+
+impl<Num> Filter<Num> for Agc<Num> where Num: AgcSample {
+    fn process(&mut self, x: Num) -> Num {
+        Agc::process(self, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f32 = 48000.0;
+
+    fn tone(amp: f32, n: usize) -> impl Iterator<Item = f32> {
+        (0..n).map(move |i| amp * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / FS).sin())
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |m, x| m.max(x.abs()))
+    }
+
+    #[test]
+    fn test_rms_levels_complex() {
+        let config = AgcConfig { target: 0.5, ..Default::default() };
+        let mut agc: Agc<Complex32> = Agc::new(FS, config);
+        let out: Vec<Complex32> = (0..2 * FS as usize)
+            .map(|i| agc.process(Complex32::from_polar(0.01, i as f32 * 0.1)))
+            .collect();
+        assert!((out.last().unwrap().norm() - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_peak_attack_and_hang() {
+        let config = AgcConfig {
+            target: 0.8, attack: 0.001, decay: 0.05, hang: 0.2,
+            detector: AgcDetector::Peak, max_gain: 100.0,
+        };
+        let mut agc: Agc<f32> = Agc::new(FS, config);
+        let n = FS as usize / 2;
+
+        // Loud burst: attack pulls peaks down to the target within a few ms
+        let loud: Vec<f32> = tone(4.0, n).map(|x| agc.process(x)).collect();
+        assert!(peak(&loud[n / 10..]) < 0.85);
+        let loud_gain = agc.gain();
+
+        // Quiet passage: gain holds through the hang, then recovers
+        let quiet: Vec<f32> = tone(0.1, n).map(|x| agc.process(x)).collect();
+        let hang_end = (0.15 * FS) as usize;
+        assert!(peak(&quiet[..hang_end]) < 0.11, "gain moved during hang");
+        assert!((peak(&quiet[n - 1000..]) - 0.8).abs() < 0.05);
+        assert!(agc.gain() > 30.0 * loud_gain);
+    }
+
+    #[test]
+    fn test_max_gain() {
+        let config = AgcConfig { max_gain: 1.0, detector: AgcDetector::Peak, ..Default::default() };
+        let mut agc: Agc<f32> = Agc::new(FS, config);
+        let out: Vec<f32> = tone(0.1, FS as usize / 4).map(|x| agc.process(x)).collect();
+        assert!((peak(&out) - 0.1).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,161 @@
+use num_complex::Complex32;
+
+/// Envelope detector.
+///
+/// Output is the envelope relative to its average (the carrier), so a
+/// carrier with modulation index m comes out as ±m independent of level.
+pub struct AmDemodulator {
+    carrier: f32,
+    alpha: f32,
+}
+
+// Carrier tracking time constant, long compared to the lowest audio
+const CARRIER_TAU_S: f32 = 0.1;
+
+impl AmDemodulator {
+    pub fn new(fs: f32) -> Self {
+        AmDemodulator {
+            carrier: 0.0,
+            alpha: 1.0 - (-1.0 / (CARRIER_TAU_S * fs)).exp(),
+        }
+    }
+
+    pub fn process(&mut self, sample: Complex32) -> f32 {
+        let envelope = sample.norm();
+        self.carrier += self.alpha * (envelope - self.carrier);
+        if self.carrier > 0.0 { envelope / self.carrier - 1.0 } else { 0.0 }
+    }
+}
+
+/// Synchronous AM detector.
+///
+/// A PLL locks an NCO to the carrier and the in-phase component is taken as
+/// the audio. Unlike the envelope detector this doesn't distort when
+/// selective fading takes the carrier below the sidebands.
+pub struct SyncAmDemodulator {
+    /// NCO phase in radians.
+    phase: f32,
+    integrator: f32,
+    max_integrator: f32,
+    kp: f32,
+    ki: f32,
+    carrier: f32,
+    alpha: f32,
+}
+
+const SYNC_LOOP_BW_HZ: f32 = 50.0;
+const SYNC_DAMPING: f32 = 0.707;
+const SYNC_MAX_OFFSET_HZ: f32 = 500.0;
+
+impl SyncAmDemodulator {
+    pub fn new(fs: f32) -> Self {
+        let t = 1.0 / fs;
+        let zeta = SYNC_DAMPING;
+        let wn = 2.0 * std::f32::consts::PI * 2.0 * SYNC_LOOP_BW_HZ / (zeta + 1.0 / (4.0 * zeta));
+        SyncAmDemodulator {
+            phase: 0.0,
+            integrator: 0.0,
+            max_integrator: 2.0 * std::f32::consts::PI * SYNC_MAX_OFFSET_HZ * t,
+            kp: 2.0 * zeta * wn * t,
+            ki: (wn * t).powi(2),
+            carrier: 0.0,
+            alpha: 1.0 - (-1.0 / (CARRIER_TAU_S * fs)).exp(),
+        }
+    }
+
+    /// Current carrier offset estimate in radians/sample.
+    pub fn frequency(&self) -> f32 {
+        self.integrator
+    }
+
+    pub fn process(&mut self, sample: Complex32) -> f32 {
+        let mixed = sample * Complex32::from_polar(1.0, -self.phase);
+        let error = mixed.im.atan2(mixed.re);
+
+        self.integrator = (self.integrator + self.ki * error).clamp(-self.max_integrator, self.max_integrator);
+        self.phase = (self.phase + self.integrator + self.kp * error).rem_euclid(2.0 * std::f32::consts::PI);
+
+        self.carrier += self.alpha * (mixed.re - self.carrier);
+        if self.carrier > 0.0 { mixed.re / self.carrier - 1.0 } else { 0.0 }
+    }
+}
+
+pub struct AmDemodIter<I> {
+    iter: I,
+    demodulator: AmDemodulator,
+}
+
+impl<I> Iterator for AmDemodIter<I> where I: Iterator<Item = Complex32> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.iter.next()?;
+        Some(self.demodulator.process(sample))
+    }
+}
+
+pub struct SyncAmDemodIter<I> {
+    iter: I,
+    demodulator: SyncAmDemodulator,
+}
+
+impl<I> Iterator for SyncAmDemodIter<I> where I: Iterator<Item = Complex32> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.iter.next()?;
+        Some(self.demodulator.process(sample))
+    }
+}
+
+pub trait AmDemodulatable {
+    fn am_demodulate(self, fs: f32) -> AmDemodIter<Self> where Self: Sized;
+    fn sync_am_demodulate(self, fs: f32) -> SyncAmDemodIter<Self> where Self: Sized;
+}
+
+impl<I> AmDemodulatable for I where I: Iterator<Item = Complex32> {
+    fn am_demodulate(self, fs: f32) -> AmDemodIter<Self> {
+        AmDemodIter { iter: self, demodulator: AmDemodulator::new(fs) }
+    }
+
+    fn sync_am_demodulate(self, fs: f32) -> SyncAmDemodIter<Self> {
+        SyncAmDemodIter { iter: self, demodulator: SyncAmDemodulator::new(fs) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f32 = 48000.0;
+
+    /// 1 kHz tone at modulation index `m` on a carrier offset by `offset` Hz.
+    fn am_signal(m: f32, offset: f32, n: usize) -> impl Iterator<Item = Complex32> {
+        (0..n).map(move |i| {
+            let t = i as f32 / FS;
+            let envelope = 0.3 * (1.0 + m * (2.0 * std::f32::consts::PI * 1000.0 * t).sin());
+            Complex32::from_polar(envelope, 2.0 * std::f32::consts::PI * offset * t + 0.7)
+        })
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |m, x| m.max(x.abs()))
+    }
+
+    #[test]
+    fn test_envelope() {
+        let out: Vec<f32> = am_signal(0.5, 120.0, FS as usize).am_demodulate(FS).collect();
+        let p = peak(&out[out.len() / 2..]);
+        assert!((p - 0.5).abs() < 0.05, "peak {}", p);
+    }
+
+    #[test]
+    fn test_sync_locks_to_offset_carrier() {
+        let mut demod = SyncAmDemodulator::new(FS);
+        let out: Vec<f32> = am_signal(0.5, 120.0, FS as usize).map(|s| demod.process(s)).collect();
+        let p = peak(&out[out.len() / 2..]);
+        assert!((p - 0.5).abs() < 0.05, "peak {}", p);
+        let offset_hz = demod.frequency() * FS / (2.0 * std::f32::consts::PI);
+        assert!((offset_hz - 120.0).abs() < 1.0, "offset {}", offset_hz);
+    }
+}
@@ -1,4 +1,7 @@
 use crate::filterable::{Filterable, Filter};
+use crate::response::{self, FrequencyResponse};
+
+use num_complex::Complex32;
 
 // Referencing biquad design & notes from https://arachnoid.com/BiQuadDesigner/index.html
 
@@ -64,6 +67,104 @@ impl<Num> Biquad<Num> where Num: Filterable<Num> {
 
         Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
     }
+
+    /// Bandpass with constant skirt gain; peak gain equals `q`.
+    pub fn bandpass_skirt(fs: f32, center: f32, q: f32) -> Self {
+        let (w0, alpha) = Self::w0_alpha(fs, center, q);
+        let b0 = w0.sin() / 2.0;
+        let b1 = 0.0;
+        let b2 = -w0.sin() / 2.0;
+        let a0 = 1.0 + alpha;
+        let a1 = -2.0 * w0.cos();
+        let a2 = 1.0 - alpha;
+
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+
+    /// Bandpass with constant 0 dB peak gain.
+    pub fn bandpass_peak(fs: f32, center: f32, q: f32) -> Self {
+        let (w0, alpha) = Self::w0_alpha(fs, center, q);
+        let b0 = alpha;
+        let b1 = 0.0;
+        let b2 = -alpha;
+        let a0 = 1.0 + alpha;
+        let a1 = -2.0 * w0.cos();
+        let a2 = 1.0 - alpha;
+
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+
+    pub fn notch(fs: f32, center: f32, q: f32) -> Self {
+        let (w0, alpha) = Self::w0_alpha(fs, center, q);
+        let b0 = 1.0;
+        let b1 = -2.0 * w0.cos();
+        let b2 = 1.0;
+        let a0 = 1.0 + alpha;
+        let a1 = -2.0 * w0.cos();
+        let a2 = 1.0 - alpha;
+
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+
+    pub fn allpass(fs: f32, center: f32, q: f32) -> Self {
+        let (w0, alpha) = Self::w0_alpha(fs, center, q);
+        let b0 = 1.0 - alpha;
+        let b1 = -2.0 * w0.cos();
+        let b2 = 1.0 + alpha;
+        let a0 = 1.0 + alpha;
+        let a1 = -2.0 * w0.cos();
+        let a2 = 1.0 - alpha;
+
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+
+    pub fn peaking_eq(fs: f32, center: f32, q: f32, gain_db: f32) -> Self {
+        let (w0, alpha) = Self::w0_alpha(fs, center, q);
+        let a = 10f32.powf(gain_db / 40.0);
+        let b0 = 1.0 + alpha * a;
+        let b1 = -2.0 * w0.cos();
+        let b2 = 1.0 - alpha * a;
+        let a0 = 1.0 + alpha / a;
+        let a1 = -2.0 * w0.cos();
+        let a2 = 1.0 - alpha / a;
+
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+
+    pub fn low_shelf(fs: f32, corner: f32, q: f32, gain_db: f32) -> Self {
+        let (w0, alpha) = Self::w0_alpha(fs, corner, q);
+        let a = 10f32.powf(gain_db / 40.0);
+        let cos = w0.cos();
+        let sq = 2.0 * a.sqrt() * alpha;
+        let b0 = a * ((a + 1.0) - (a - 1.0) * cos + sq);
+        let b1 = 2.0 * a * ((a - 1.0) - (a + 1.0) * cos);
+        let b2 = a * ((a + 1.0) - (a - 1.0) * cos - sq);
+        let a0 = (a + 1.0) + (a - 1.0) * cos + sq;
+        let a1 = -2.0 * ((a - 1.0) + (a + 1.0) * cos);
+        let a2 = (a + 1.0) + (a - 1.0) * cos - sq;
+
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+
+    pub fn high_shelf(fs: f32, corner: f32, q: f32, gain_db: f32) -> Self {
+        let (w0, alpha) = Self::w0_alpha(fs, corner, q);
+        let a = 10f32.powf(gain_db / 40.0);
+        let cos = w0.cos();
+        let sq = 2.0 * a.sqrt() * alpha;
+        let b0 = a * ((a + 1.0) + (a - 1.0) * cos + sq);
+        let b1 = -2.0 * a * ((a - 1.0) + (a + 1.0) * cos);
+        let b2 = a * ((a + 1.0) + (a - 1.0) * cos - sq);
+        let a0 = (a + 1.0) - (a - 1.0) * cos + sq;
+        let a1 = 2.0 * ((a - 1.0) - (a + 1.0) * cos);
+        let a2 = (a + 1.0) - (a - 1.0) * cos - sq;
+
+        Biquad::new(b0 / a0, b1 / a0, b2 / a0, a1 / a0, a2 / a0)
+    }
+
+    fn w0_alpha(fs: f32, f0: f32, q: f32) -> (f32, f32) {
+        let w0 = 2.0 * std::f32::consts::PI * f0 / fs;
+        (w0, w0.sin() / (2.0 * q))
+    }
 }
 
 impl<Num> Filter<Num> for Biquad<Num> where Num: Filterable<Num> {
@@ -72,10 +173,19 @@ impl<Num> Filter<Num> for Biquad<Num> where Num: Filterable<Num> {
     }
 }
 
+impl<Num> FrequencyResponse for Biquad<Num> {
+    fn frequency_response(&self, freqs: &[f32]) -> Vec<Complex32> {
+        response::rational_response(&[self.b0, self.b1, self.b2], &[1.0, self.a1, self.a2], freqs)
+    }
+
+    fn group_delay(&self, freqs: &[f32]) -> Vec<f32> {
+        response::rational_group_delay(&[self.b0, self.b1, self.b2], &[1.0, self.a1, self.a2], freqs)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use num_complex::Complex32;
 
     #[test]
     fn test_biquad_complex() {
@@ -120,4 +230,90 @@ mod tests {
         assert!((bq.a1 - -1.17684383).abs() < 1e-4, "a1 mismatch: {}", bq.a1);
         assert!((bq.a2 - 0.42798985).abs() < 1e-4, "a2 mismatch: {}", bq.a2);
     }
+
+    #[test]
+    fn test_bandpass_peak_coefficients() {
+        // 57kHz RDS subcarrier, 240kHz sample rate
+        let bq: Biquad<f32> = Biquad::bandpass_peak(240000.0, 57000.0, 10.0);
+        assert!((bq.b0 - 0.04747922).abs() < 1e-4, "b0 mismatch: {}", bq.b0);
+        assert!((bq.b1 - 0.0).abs() < 1e-4, "b1 mismatch: {}", bq.b1);
+        assert!((bq.b2 - -0.04747922).abs() < 1e-4, "b2 mismatch: {}", bq.b2);
+        assert!((bq.a1 - -0.1494678).abs() < 1e-4, "a1 mismatch: {}", bq.a1);
+        assert!((bq.a2 - 0.9050415).abs() < 1e-4, "a2 mismatch: {}", bq.a2);
+    }
+
+    #[test]
+    fn test_notch_coefficients() {
+        // 19kHz pilot notch, 240kHz sample rate
+        let bq: Biquad<f32> = Biquad::notch(240000.0, 19000.0, 5.0);
+        assert!((bq.b0 - 0.9544572).abs() < 1e-4, "b0 mismatch: {}", bq.b0);
+        assert!((bq.b1 - -1.677587).abs() < 1e-4, "b1 mismatch: {}", bq.b1);
+        assert!((bq.b2 - 0.9544572).abs() < 1e-4, "b2 mismatch: {}", bq.b2);
+        assert!((bq.a1 - -1.677587).abs() < 1e-4, "a1 mismatch: {}", bq.a1);
+        assert!((bq.a2 - 0.9089145).abs() < 1e-4, "a2 mismatch: {}", bq.a2);
+    }
+
+    #[test]
+    fn test_peaking_eq_coefficients() {
+        // +6dB at 1kHz, 48kHz sample rate
+        let bq: Biquad<f32> = Biquad::peaking_eq(48000.0, 1000.0, 1.0, 6.0);
+        assert!((bq.b0 - 1.043953).abs() < 1e-4, "b0 mismatch: {}", bq.b0);
+        assert!((bq.b1 - -1.895321).abs() < 1e-4, "b1 mismatch: {}", bq.b1);
+        assert!((bq.b2 - 0.8677223).abs() < 1e-4, "b2 mismatch: {}", bq.b2);
+        assert!((bq.a1 - -1.895321).abs() < 1e-4, "a1 mismatch: {}", bq.a1);
+        assert!((bq.a2 - 0.9116754).abs() < 1e-4, "a2 mismatch: {}", bq.a2);
+    }
+
+    #[test]
+    fn test_high_shelf_coefficients() {
+        // -10dB above 3kHz, 48kHz sample rate
+        let bq: Biquad<f32> = Biquad::high_shelf(48000.0, 3000.0, 0.707, -10.0);
+        assert!((bq.b0 - 0.3706692).abs() < 1e-4, "b0 mismatch: {}", bq.b0);
+        assert!((bq.b1 - -0.4767611).abs() < 1e-4, "b1 mismatch: {}", bq.b1);
+        assert!((bq.b2 - 0.178259).abs() < 1e-4, "b2 mismatch: {}", bq.b2);
+        assert!((bq.a1 - -1.585674).abs() < 1e-4, "a1 mismatch: {}", bq.a1);
+        assert!((bq.a2 - 0.6578408).abs() < 1e-4, "a2 mismatch: {}", bq.a2);
+    }
+
+    #[test]
+    fn test_allpass_unit_magnitude() {
+        let mut bq: Biquad<f32> = Biquad::allpass(48000.0, 2000.0, 0.707);
+        let n = 48000;
+        let out: Vec<f32> = (0..n)
+            .map(|i| (2.0 * std::f32::consts::PI * 5000.0 * i as f32 / 48000.0).sin())
+            .map(|x| bq.process(x))
+            .collect();
+        let peak = out[n / 2..].iter().fold(0.0f32, |m, &y| m.max(y.abs()));
+        assert!((peak - 1.0).abs() < 1e-2, "allpass peak: {}", peak);
+    }
+
+    #[test]
+    fn test_lowpass_response() {
+        let bq: Biquad<f32> = Biquad::lowpass(48000.0, 4000.0, 0.707);
+        let db = bq.magnitude_db(&[0.0, 4000.0 / 48000.0, 16000.0 / 48000.0]);
+        assert!(db[0].abs() < 1e-3, "DC gain: {} dB", db[0]);
+        assert!((db[1] + 3.01).abs() < 0.05, "gain at cutoff: {} dB", db[1]);
+        assert!(db[2] < -24.0, "stopband gain: {} dB", db[2]);
+    }
+
+    #[test]
+    fn test_notch_response() {
+        let bq: Biquad<f32> = Biquad::notch(240000.0, 19000.0, 5.0);
+        let db = bq.magnitude_db(&[19000.0 / 240000.0, 1000.0 / 240000.0, 60000.0 / 240000.0]);
+        assert!(db[0] < -60.0, "gain at notch: {} dB", db[0]);
+        assert!(db[1].abs() < 0.1);
+        assert!(db[2].abs() < 0.1);
+    }
+
+    #[test]
+    fn test_allpass_group_delay() {
+        // Second-order allpass: flat magnitude, group delay peaks near the center frequency
+        let bq: Biquad<f32> = Biquad::allpass(48000.0, 2000.0, 5.0);
+        let freqs = [500.0 / 48000.0, 2000.0 / 48000.0, 12000.0 / 48000.0];
+        for db in bq.magnitude_db(&freqs) {
+            assert!(db.abs() < 1e-3);
+        }
+        let gd = bq.group_delay(&freqs);
+        assert!(gd[1] > gd[0] && gd[1] > gd[2], "group delay: {:?}", gd);
+    }
 }
\ No newline at end of file
@@ -1,4 +1,7 @@
 use crate::filterable::{Filterable,Filter};
+use crate::response::{self, FrequencyResponse};
+
+use num_complex::Complex32;
 
 #[derive(Debug, Clone)]
 pub struct Deemphasis<Num> {
@@ -30,3 +33,29 @@ impl<Num> Filter<Num> for Deemphasis<Num> where Num: Filterable<Num> {
         y
     }
 }
+
+impl<Num> FrequencyResponse for Deemphasis<Num> {
+    fn frequency_response(&self, freqs: &[f32]) -> Vec<Complex32> {
+        response::rational_response(&[self.b0, self.b1], &[1.0, self.a1], freqs)
+    }
+
+    fn group_delay(&self, freqs: &[f32]) -> Vec<f32> {
+        response::rational_group_delay(&[self.b0, self.b1], &[1.0, self.a1], freqs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_75us_corner() {
+        // 75 µs → corner at 1/(2π·75µs) ≈ 2122 Hz
+        let de: Deemphasis<f32> = Deemphasis::new(240000.0, 75e-6);
+        let db = de.magnitude_db(&[0.0, 2122.0 / 240000.0, 15000.0 / 240000.0]);
+        assert!(db[0].abs() < 1e-3, "DC gain: {} dB", db[0]);
+        assert!((db[1] + 3.01).abs() < 0.05, "gain at corner: {} dB", db[1]);
+        // One pole: ~-17 dB at 15 kHz
+        assert!((db[2] + 17.1).abs() < 0.5, "gain at 15 kHz: {} dB", db[2]);
+    }
+}
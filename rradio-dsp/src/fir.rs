@@ -1,6 +1,9 @@
 /// Developed using Claude Opus 4.6
 
 use crate::filterable::{Filterable, Filter};
+use crate::response::{self, FrequencyResponse};
+
+use num_complex::Complex32;
 use std::f64::consts::PI;
 
 #[derive(Debug, Clone)]
@@ -52,6 +55,16 @@ impl<Num> Filter<Num> for Fir<Num> where Num: Filterable<Num> {
     }
 }
 
+impl<Num> FrequencyResponse for Fir<Num> {
+    fn frequency_response(&self, freqs: &[f32]) -> Vec<Complex32> {
+        response::rational_response(&self.coeffs, &[1.0], freqs)
+    }
+
+    fn group_delay(&self, freqs: &[f32]) -> Vec<f32> {
+        response::rational_group_delay(&self.coeffs, &[1.0], freqs)
+    }
+}
+
 // ── FIR filter design ──────────────────────────────────────────────
 
 /// Window function types for FIR design.
@@ -208,4 +221,22 @@ mod tests {
             assert!((y - expected).abs() < 1e-6);
         }
     }
-}
+
+    #[test]
+    fn test_lowpass_taps_response() {
+        // 15 kHz audio lowpass at 240 kHz
+        let fir: Fir<f32> = Fir::new(generate_lowpass_taps(240000.0, 15000.0, 101, &WindowType::Blackman));
+        let passband: Vec<f32> = (0..=8).map(|i| i as f32 * 1000.0 / 240000.0).collect();
+        let ripple = fir.magnitude_db(&passband).iter().fold(0.0f32, |m, db| m.max(db.abs()));
+        assert!(ripple < 0.05, "passband ripple: {} dB", ripple);
+
+        let stopband: Vec<f32> = (0..=50).map(|i| (25000.0 + i as f32 * 1000.0) / 240000.0).collect();
+        let worst = fir.magnitude_db(&stopband).iter().fold(f32::MIN, |m, &db| m.max(db));
+        assert!(worst < -70.0, "stopband attenuation: {} dB", worst);
+
+        // Symmetric taps: linear phase with (N-1)/2 samples of delay
+        for gd in fir.group_delay(&passband) {
+            assert!((gd - 50.0).abs() < 1e-2, "group delay: {}", gd);
+        }
+    }
+}
\ No newline at end of file
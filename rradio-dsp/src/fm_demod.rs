@@ -1,5 +1,14 @@
 use num_complex::Complex32;
 
+/// Turns complex baseband into instantaneous frequency.
+///
+/// All discriminators output the phase step per sample in radians, so they
+/// can be swapped without rescaling what follows.
+pub trait Discriminator {
+    fn process(&mut self, sample: Complex32) -> f32;
+}
+
+/// Polar discriminator: atan2 of each sample and an unwrapped difference.
 pub struct FmDemodulator {
     last_phase: f32,
 }
@@ -24,9 +33,144 @@ impl FmDemodulator {
     }
 }
 
-pub struct FmDemodIter<I> {
+impl Discriminator for FmDemodulator {
+    fn process(&mut self, sample: Complex32) -> f32 {
+        FmDemodulator::process(self, sample)
+    }
+}
+
+/// `arg(x[n]·conj(x[n-1]))`: one atan2 per sample and no unwrapping.
+pub struct ConjugateProductDiscriminator {
+    last: Complex32,
+}
+
+impl ConjugateProductDiscriminator {
+    pub fn new() -> Self {
+        ConjugateProductDiscriminator { last: Complex32::new(0.0, 0.0) }
+    }
+}
+
+impl Default for ConjugateProductDiscriminator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discriminator for ConjugateProductDiscriminator {
+    fn process(&mut self, sample: Complex32) -> f32 {
+        let product = sample * self.last.conj();
+        self.last = sample;
+        product.arg()
+    }
+}
+
+/// Conjugate product with a polynomial atan2 (max error ~0.0015 rad).
+pub struct FastAtanDiscriminator {
+    last: Complex32,
+}
+
+impl FastAtanDiscriminator {
+    pub fn new() -> Self {
+        FastAtanDiscriminator { last: Complex32::new(0.0, 0.0) }
+    }
+}
+
+impl Default for FastAtanDiscriminator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discriminator for FastAtanDiscriminator {
+    fn process(&mut self, sample: Complex32) -> f32 {
+        let product = sample * self.last.conj();
+        self.last = sample;
+        fast_atan2(product.im, product.re)
+    }
+}
+
+/// atan2 from a first-octant polynomial, Rajan et al. 2006.
+pub fn fast_atan2(y: f32, x: f32) -> f32 {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    let (ax, ay) = (x.abs(), y.abs());
+    if ax == 0.0 && ay == 0.0 {
+        return 0.0;
+    }
+    let z = ax.min(ay) / ax.max(ay);
+    let mut angle = FRAC_PI_4 * z - z * (z - 1.0) * (0.2447 + 0.0663 * z);
+    if ay > ax { angle = FRAC_PI_2 - angle; }
+    if x < 0.0 { angle = PI - angle; }
+    if y < 0.0 { -angle } else { angle }
+}
+
+/// Division-free quadrature discriminator.
+///
+/// A tracking limiter normalizes the amplitude with a Newton step on
+/// 1/|x| (no divide or sqrt), then `Im(x[n]·conj(x[n-1])) = sin(Δφ)`
+/// approximates the phase step. The sine compression adds distortion as
+/// deviation approaches a large fraction of the sample rate.
+pub struct QuadratureDiscriminator {
+    last: Complex32,
+    gain: f32,
+}
+
+impl QuadratureDiscriminator {
+    pub fn new() -> Self {
+        QuadratureDiscriminator { last: Complex32::new(0.0, 0.0), gain: 1.0 }
+    }
+}
+
+impl Default for QuadratureDiscriminator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Discriminator for QuadratureDiscriminator {
+    fn process(&mut self, sample: Complex32) -> f32 {
+        let power = sample.norm_sqr();
+        if power == 0.0 {
+            return 0.0;
+        }
+        // Two Newton iterations for g = 1/sqrt(power), seeded by the last gain.
+        // Restart from a safe seed if a sudden level jump would diverge.
+        if power * self.gain * self.gain > 2.5 {
+            self.gain = 1.0 / power.max(1.0);
+        }
+        for _ in 0..2 {
+            self.gain *= 1.5 - 0.5 * power * self.gain * self.gain;
+        }
+        let limited = sample * self.gain;
+        let step = limited.im * self.last.re - limited.re * self.last.im;
+        self.last = limited;
+        step
+    }
+}
+
+/// One of the discriminators above, chosen at run time. A match rather
+/// than a trait object keeps the per-sample call direct.
+pub enum AnyDiscriminator {
+    Polar(FmDemodulator),
+    ConjugateProduct(ConjugateProductDiscriminator),
+    FastAtan(FastAtanDiscriminator),
+    Quadrature(QuadratureDiscriminator),
+}
+
+impl Discriminator for AnyDiscriminator {
+    #[inline]
+    fn process(&mut self, sample: Complex32) -> f32 {
+        match self {
+            AnyDiscriminator::Polar(d) => d.process(sample),
+            AnyDiscriminator::ConjugateProduct(d) => d.process(sample),
+            AnyDiscriminator::FastAtan(d) => d.process(sample),
+            AnyDiscriminator::Quadrature(d) => d.process(sample),
+        }
+    }
+}
+
+pub struct FmDemodIter<I, D = FmDemodulator> {
     iter: I,
-    demodulator: FmDemodulator,
+    demodulator: D,
 }
 
 impl<I> FmDemodIter<I> where I: Iterator<Item = Complex32> {
@@ -38,7 +182,7 @@ impl<I> FmDemodIter<I> where I: Iterator<Item = Complex32> {
     }
 }
 
-impl<I> Iterator for FmDemodIter<I> where I: Iterator<Item = Complex32> {
+impl<I, D> Iterator for FmDemodIter<I, D> where I: Iterator<Item = Complex32>, D: Discriminator {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -49,10 +193,89 @@ impl<I> Iterator for FmDemodIter<I> where I: Iterator<Item = Complex32> {
 
 pub trait FmDemodulatable {
     fn fm_demodulate(self) -> FmDemodIter<Self> where Self: Sized;
+    fn fm_demodulate_with<D: Discriminator>(self, discriminator: D) -> FmDemodIter<Self, D> where Self: Sized;
 }
 
 impl<I> FmDemodulatable for I where I: Iterator<Item = Complex32> {
     fn fm_demodulate(self) -> FmDemodIter<Self> {
         FmDemodIter::new(self)
     }
+
+    fn fm_demodulate_with<D: Discriminator>(self, discriminator: D) -> FmDemodIter<Self, D> {
+        FmDemodIter { iter: self, demodulator: discriminator }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f64 = 1.2e6;
+    const TONE: f64 = 1000.0;
+
+    /// FM tone at `deviation` Hz, with an amplitude ripple to exercise limiting.
+    fn fm_tone(deviation: f64, n: usize) -> Vec<Complex32> {
+        let index = deviation / TONE;
+        (0..n).map(|i| {
+            let t = i as f64 / FS;
+            let w = 2.0 * std::f64::consts::PI * TONE * t;
+            let amp = 0.5 * (1.0 + 0.2 * (0.37 * w).sin());
+            Complex32::from_polar(amp as f32, (index * w.sin()) as f32)
+        }).collect()
+    }
+
+    /// THD of a demodulated 1 kHz tone over whole periods, from harmonics 2–5.
+    fn thd(samples: &[f32]) -> f64 {
+        let harmonic = |h: f64| {
+            let (re, im) = samples.iter().enumerate().fold((0.0, 0.0), |(re, im), (i, &x)| {
+                let w = 2.0 * std::f64::consts::PI * h * TONE * i as f64 / FS;
+                (re + x as f64 * w.cos(), im + x as f64 * w.sin())
+            });
+            re * re + im * im
+        };
+        let fundamental = harmonic(1.0);
+        ((2..=5).map(|h| harmonic(h as f64)).sum::<f64>() / fundamental).sqrt()
+    }
+
+    fn measure<D: Discriminator>(d: D, deviation: f64) -> (f32, f64) {
+        // Skip the first sample (no previous phase) and keep 10 whole periods
+        let period = (FS / TONE) as usize;
+        let out: Vec<f32> = fm_tone(deviation, 11 * period).into_iter().fm_demodulate_with(d).skip(period).collect();
+        let peak = out.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        (peak, thd(&out))
+    }
+
+    #[test]
+    fn test_discriminators_agree() {
+        // 75 kHz deviation at 1.2 MHz: peak step 2π·75k/1.2M ≈ 0.393 rad
+        let expected = (2.0 * std::f64::consts::PI * 75e3 / FS) as f32;
+        let polar = measure(FmDemodulator::new(), 75e3);
+        let conj = measure(ConjugateProductDiscriminator::new(), 75e3);
+        let fast = measure(FastAtanDiscriminator::new(), 75e3);
+        let quad = measure(QuadratureDiscriminator::new(), 75e3);
+        assert_eq!(measure(AnyDiscriminator::FastAtan(FastAtanDiscriminator::new()), 75e3), fast);
+        for (name, (peak, thd)) in [("polar", polar), ("conj", conj), ("fast", fast)] {
+            assert!((peak - expected).abs() < 0.005, "{} peak {}", name, peak);
+            assert!(thd < 0.005, "{} THD {}", name, thd);
+        }
+        // sin(Δφ) compresses the peaks
+        assert!((quad.0 - expected.sin()).abs() < 0.005, "quadrature peak {}", quad.0);
+        assert!(quad.1 < 0.01, "quadrature THD {}", quad.1);
+    }
+
+    #[test]
+    fn test_quadrature_low_deviation() {
+        // Small phase steps are where sin(Δφ) ≈ Δφ holds
+        let (_, thd) = measure(QuadratureDiscriminator::new(), 5e3);
+        assert!(thd < 1e-3, "THD {}", thd);
+    }
+
+    #[test]
+    fn test_fast_atan2() {
+        for i in 0..360 {
+            let a = (i as f32 - 179.5).to_radians();
+            let err = (fast_atan2(a.sin(), a.cos()) - a).abs();
+            assert!(err < 0.002, "angle {} err {}", a, err);
+        }
+    }
 }
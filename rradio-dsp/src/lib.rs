@@ -1,11 +1,18 @@
 pub mod filterable;
+pub mod response;
 pub mod biquad;
+pub mod sos;
 pub mod fir;
 pub mod osc;
+pub mod afc;
 pub mod resample;
 pub mod deemphasis;
+pub mod agc;
+pub mod iq_correction;
 pub mod pll;
 pub mod fm_demod;
+pub mod am_demod;
+pub mod ssb_demod;
 pub mod interleaver;
 pub mod spy;
 pub mod buffer;
@@ -16,6 +16,11 @@ impl Osc {
         }
     }
 
+    /// Change frequency without a phase discontinuity.
+    pub fn set_freq(&mut self, freq: f32, sample_rate: f32) {
+        self.increment = freq / sample_rate;
+    }
+
     pub fn next(&mut self) -> Complex32 {
         let phase = self.phase * 2.0 * std::f32::consts::PI;
         self.phase = (self.phase + self.increment) % 1.0;
@@ -1,3 +1,6 @@
+use num_complex::Complex32;
+
+use crate::biquad::Biquad;
 use crate::filterable::Filter;
 
 pub struct PllOutput {
@@ -46,3 +49,202 @@ impl <F> RealPll<F> where F: Filter<f32> {
         PllOutput { out, lock: self.lock_level }
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct PilotPllConfig {
+    /// Nominal pilot frequency in Hz.
+    pub freq: f32,
+    /// Q of each of the two cascaded pilot bandpass sections.
+    pub bpf_q: f32,
+    /// Loop noise bandwidth in Hz.
+    pub loop_bandwidth: f32,
+    pub damping: f32,
+    /// Bandwidth in Hz of the level/quality detector smoothing.
+    pub lock_bandwidth: f32,
+    /// Pilot amplitude hysteresis for the stereo indicator: lock is declared
+    /// above `lock_on` and dropped below `lock_off`.
+    pub lock_on: f32,
+    pub lock_off: f32,
+    /// Minimum lock quality (cosine of the phase error) to declare lock.
+    pub min_quality: f32,
+    /// Largest pilot offset in Hz the loop may pull to.
+    pub max_offset: f32,
+}
+
+impl Default for PilotPllConfig {
+    fn default() -> Self {
+        PilotPllConfig {
+            freq: 19e3,
+            bpf_q: 20.0,
+            loop_bandwidth: 20.0,
+            damping: 0.707,
+            lock_bandwidth: 10.0,
+            // Nominal pilot (9% of 75 kHz deviation) is ~0.035 at the fm_demod output scale
+            lock_on: 0.016,
+            lock_off: 0.010,
+            min_quality: 0.9,
+            max_offset: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PilotEvent {
+    Locked,
+    Unlocked,
+}
+
+pub struct PilotPllOutput {
+    /// e^j2φ for a pilot of sin(φ): `im` is the L−R subcarrier, `re` its quadrature.
+    pub subcarrier_38k: Complex32,
+    /// e^j3φ, the third pilot harmonic the RDS subcarrier is locked to.
+    pub subcarrier_57k: Complex32,
+    /// Smoothed pilot amplitude, in input units.
+    pub level: f32,
+    /// Cosine of the smoothed phase error, 1 when fully locked.
+    pub quality: f32,
+    /// Stereo indicator.
+    pub locked: bool,
+    /// Set on the sample where the stereo indicator changes.
+    pub event: Option<PilotEvent>,
+}
+
+/// Phase-locked loop for the FM stereo pilot.
+///
+/// The MPX is narrowed to the pilot by two resonant bandpass sections, mixed
+/// down against the NCO and the phase error taken from the complex product,
+/// so the loop dynamics don't depend on pilot level. A type-2 loop filter
+/// tracks any offset up to `max_offset`.
+pub struct PilotPll {
+    config: PilotPllConfig,
+    bpf: [Biquad<f32>; 2],
+    // Rejects the 2× pilot image from the mixer product
+    detector_filt: Biquad<Complex32>,
+    /// NCO phase in cycles; the pilot is cos(2π·phase) when locked.
+    phase: f32,
+    increment: f32,
+    integrator: f32,
+    max_integrator: f32,
+    kp: f32,
+    ki: f32,
+    lock_alpha: f32,
+    smoothed: Complex32,
+    locked: bool,
+}
+
+impl PilotPll {
+    pub fn new(sample_rate: f32, config: PilotPllConfig) -> Self {
+        let t = 1.0 / sample_rate;
+        // Natural frequency from noise bandwidth: Bn = ωn/2·(ζ + 1/4ζ)
+        let zeta = config.damping;
+        let wn = 2.0 * std::f32::consts::PI * 2.0 * config.loop_bandwidth / (zeta + 1.0 / (4.0 * zeta));
+        let to_cycles = 1.0 / (2.0 * std::f32::consts::PI);
+        PilotPll {
+            bpf: [
+                Biquad::bandpass_peak(sample_rate, config.freq, config.bpf_q),
+                Biquad::bandpass_peak(sample_rate, config.freq, config.bpf_q),
+            ],
+            detector_filt: Biquad::lowpass(sample_rate, 1000.0, 0.707),
+            phase: 0.0,
+            increment: config.freq * t,
+            integrator: 0.0,
+            max_integrator: config.max_offset * t,
+            kp: 2.0 * zeta * wn * t * to_cycles,
+            ki: (wn * t).powi(2) * to_cycles,
+            lock_alpha: 1.0 - (-2.0 * std::f32::consts::PI * config.lock_bandwidth * t).exp(),
+            smoothed: Complex32::new(0.0, 0.0),
+            locked: false,
+            config,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> PilotPllOutput {
+        let pilot = self.bpf.iter_mut().fold(input, |x, f| f.process(x));
+        let nco = Complex32::from_polar(1.0, -2.0 * std::f32::consts::PI * self.phase);
+        // Half the pilot amplitude at the phase error, once the 2× image is removed
+        let z = self.detector_filt.process(nco * pilot);
+        let error = z.im.atan2(z.re);
+
+        self.integrator = (self.integrator + self.ki * error).clamp(-self.max_integrator, self.max_integrator);
+
+        self.smoothed += (z - self.smoothed) * self.lock_alpha;
+        let level = 2.0 * self.smoothed.norm();
+        let quality = if level > 0.0 { 2.0 * self.smoothed.re / level } else { 0.0 };
+
+        let event = if self.locked && (level < self.config.lock_off || quality < self.config.min_quality) {
+            self.locked = false;
+            Some(PilotEvent::Unlocked)
+        } else if !self.locked && level > self.config.lock_on && quality > self.config.min_quality {
+            self.locked = true;
+            Some(PilotEvent::Locked)
+        } else {
+            None
+        };
+
+        // Pilot is sin(φ) with φ = 2π·phase + π/2
+        let phi = 2.0 * std::f32::consts::PI * self.phase + std::f32::consts::FRAC_PI_2;
+        let out = PilotPllOutput {
+            subcarrier_38k: Complex32::from_polar(1.0, 2.0 * phi),
+            subcarrier_57k: Complex32::from_polar(1.0, 3.0 * phi),
+            level,
+            quality,
+            locked: self.locked,
+            event,
+        };
+
+        self.phase = (self.phase + self.increment + self.integrator + self.kp * error).rem_euclid(1.0);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f32 = 240000.0;
+
+    fn run_tone(freq: f64, amp: f64, n: usize) -> (PilotPll, Vec<(f64, PilotPllOutput)>) {
+        let mut pll = PilotPll::new(FS, PilotPllConfig::default());
+        let out = (0..n).map(|i| {
+            let t = i as f64 / FS as f64;
+            let x = amp * (2.0 * std::f64::consts::PI * freq * t).sin();
+            (t, pll.process(x as f32))
+        }).collect();
+        (pll, out)
+    }
+
+    #[test]
+    fn test_subcarriers_coherent_with_offset_pilot() {
+        let freq = 19002.0;
+        let (_, out) = run_tone(freq, 0.035, FS as usize);
+        // Correlate the last 0.1 s against the ideal harmonics
+        let tail = &out[out.len() - FS as usize / 10..];
+        let tau = 2.0 * std::f64::consts::PI;
+        let corr = |h: f64, sel: &dyn Fn(&PilotPllOutput) -> f32| {
+            tail.iter().map(|(t, o)| sel(o) as f64 * (tau * h * freq * t).sin()).sum::<f64>() * 2.0 / tail.len() as f64
+        };
+        let c38 = corr(2.0, &|o| o.subcarrier_38k.im);
+        let c57 = corr(3.0, &|o| o.subcarrier_57k.im);
+        assert!(c38 > 0.99, "38 kHz correlation {}", c38);
+        assert!(c57 > 0.99, "57 kHz correlation {}", c57);
+
+        let last = &tail.last().unwrap().1;
+        assert!(last.locked);
+        assert!((last.level - 0.035).abs() < 0.002, "level {}", last.level);
+        assert!(last.quality > 0.99);
+    }
+
+    #[test]
+    fn test_lock_event_fires_once() {
+        let (_, out) = run_tone(19000.0, 0.035, FS as usize / 2);
+        let events: Vec<PilotEvent> = out.iter().filter_map(|(_, o)| o.event).collect();
+        assert_eq!(events, vec![PilotEvent::Locked]);
+    }
+
+    #[test]
+    fn test_no_lock_without_pilot() {
+        // A strong tone away from the pilot must not trip the indicator
+        let (_, out) = run_tone(15000.0, 0.5, FS as usize / 2);
+        assert!(out.iter().all(|(_, o)| !o.locked));
+    }
+}
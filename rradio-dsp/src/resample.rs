@@ -1,4 +1,7 @@
 use crate::filterable::Filterable;
+use crate::response::{self, FrequencyResponse};
+
+use num_complex::Complex32;
 
 use num_traits::Zero;
 
@@ -166,6 +169,30 @@ impl<Num: Filterable<Num>> RationalResampler<Num> {
 
         Some(output)
     }
+
+    /// Reconstruct the prototype taps from the polyphase arms, zero-padded
+    /// to a whole number of arms.
+    pub fn prototype(&self) -> Vec<f32> {
+        let mut taps = vec![0.0; self.arm_len * self.l];
+        for (k, arm) in self.phases.iter().enumerate() {
+            for (i, &c) in arm.iter().rev().enumerate() {
+                taps[k + i * self.l] = c;
+            }
+        }
+        taps
+    }
+}
+
+/// Response of the prototype filter. Frequencies are normalized to the
+/// interpolated rate (fs_in × L), which is the rate the prototype runs at.
+impl<Num: Filterable<Num>> FrequencyResponse for RationalResampler<Num> {
+    fn frequency_response(&self, freqs: &[f32]) -> Vec<Complex32> {
+        response::rational_response(&self.prototype(), &[1.0], freqs)
+    }
+
+    fn group_delay(&self, freqs: &[f32]) -> Vec<f32> {
+        response::rational_group_delay(&self.prototype(), &[1.0], freqs)
+    }
 }
 
 // --- Iterator adapter ---
@@ -179,6 +206,16 @@ where
     resampler: RationalResampler<Num>,
 }
 
+impl<I, Num> RationalResampleIter<I, Num>
+where
+    I: Iterator<Item = Num>,
+    Num: Filterable<Num>,
+{
+    pub fn resampler(&self) -> &RationalResampler<Num> {
+        &self.resampler
+    }
+}
+
 impl<I, Num> Iterator for RationalResampleIter<I, Num>
 where
     I: Iterator<Item = Num>,
@@ -223,7 +260,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use num_complex::Complex32;
+    use crate::fir;
 
     #[test]
     fn test_upsample() {
@@ -282,4 +319,27 @@ mod tests {
             .collect();
         assert_eq!(output, vec![1.0, 3.0, 5.0]);
     }
+
+    #[test]
+    fn test_prototype_round_trip() {
+        let taps = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0];
+        let resampler: RationalResampler<f32> = RationalResampler::new(taps.clone(), 3, 4);
+        let proto = resampler.prototype();
+        assert_eq!(proto.len(), 9);
+        assert_eq!(&proto[..7], &taps[..]);
+        assert!(proto[7..].iter().all(|&t| t == 0.0));
+    }
+
+    #[test]
+    fn test_rds_stage1_prototype_response() {
+        // 240k -> 171k (L=57, M=80), prototype designed at 240k × 57
+        let up_fs = 240000.0 * 57.0;
+        let taps = fir::generate_lowpass_taps(up_fs as f64, 80e3, 255, &fir::WindowType::Blackman);
+        let iter = std::iter::empty::<f32>().resample(taps, 57, 80);
+        let db = iter.resampler().magnitude_db(&[1000.0 / up_fs, 57000.0 / up_fs, 240000.0 / up_fs]);
+        assert!(db[0].abs() < 0.01, "DC gain: {} dB", db[0]);
+        // 80 kHz is the -6 dB point; the RDS subcarrier sits on the slope
+        assert!(db[1] > -3.0, "gain at 57 kHz: {} dB", db[1]);
+        assert!(db[2] < -70.0, "gain at 240 kHz: {} dB", db[2]);
+    }
 }
\ No newline at end of file
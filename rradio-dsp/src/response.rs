@@ -0,0 +1,83 @@
+use num_complex::{Complex32, Complex64};
+use std::f64::consts::PI;
+
+/// Frequency-domain analysis of a linear filter.
+///
+/// Frequencies are normalized to the filter's sample rate (cycles/sample,
+/// i.e. `f / fs`), so `0.5` is Nyquist.
+pub trait FrequencyResponse {
+    /// Complex response H(e^jω) at each frequency.
+    fn frequency_response(&self, freqs: &[f32]) -> Vec<Complex32>;
+
+    /// Group delay in samples at each frequency.
+    fn group_delay(&self, freqs: &[f32]) -> Vec<f32>;
+
+    /// Magnitude response in dB at each frequency.
+    fn magnitude_db(&self, freqs: &[f32]) -> Vec<f32> {
+        self.frequency_response(freqs)
+            .iter()
+            .map(|h| 20.0 * h.norm().log10())
+            .collect()
+    }
+}
+
+/// Evaluate a polynomial in z^-1 on the unit circle.
+///
+/// Returns `C(e^jω) = Σ c[n]·e^-jωn` together with its group delay
+/// contribution `Re{Σ n·c[n]·e^-jωn / C(e^jω)}`. A rational response
+/// B/A has group delay `gd(B) - gd(A)`.
+fn polynomial_at(coeffs: &[f32], freq: f32) -> (Complex64, f64) {
+    let w = 2.0 * PI * freq as f64;
+    let (value, ramp) = coeffs.iter().enumerate().fold(
+        (Complex64::new(0.0, 0.0), Complex64::new(0.0, 0.0)),
+        |(value, ramp), (n, &c)| {
+            let term = Complex64::from_polar(c as f64, -w * n as f64);
+            (value + term, ramp + term * n as f64)
+        },
+    );
+    let delay = if value.norm_sqr() > 0.0 { (ramp / value).re } else { 0.0 };
+    (value, delay)
+}
+
+/// Response of `num / den` (both polynomials in z^-1) at each frequency.
+pub(crate) fn rational_response(num: &[f32], den: &[f32], freqs: &[f32]) -> Vec<Complex32> {
+    freqs.iter()
+        .map(|&f| {
+            let h = polynomial_at(num, f).0 / polynomial_at(den, f).0;
+            Complex32::new(h.re as f32, h.im as f32)
+        })
+        .collect()
+}
+
+/// Group delay of `num / den` in samples at each frequency.
+pub(crate) fn rational_group_delay(num: &[f32], den: &[f32], freqs: &[f32]) -> Vec<f32> {
+    freqs.iter()
+        .map(|&f| (polynomial_at(num, f).1 - polynomial_at(den, f).1) as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pure_delay() {
+        // z^-3: unit magnitude, 3 samples of delay everywhere
+        let num = [0.0, 0.0, 0.0, 1.0];
+        let freqs = [0.0, 0.1, 0.25, 0.4];
+        for h in rational_response(&num, &[1.0], &freqs) {
+            assert!((h.norm() - 1.0).abs() < 1e-6);
+        }
+        for gd in rational_group_delay(&num, &[1.0], &freqs) {
+            assert!((gd - 3.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_one_pole_dc_gain() {
+        // y[n] = x[n] + 0.5 y[n-1] has DC gain 2 and Nyquist gain 2/3
+        let h = rational_response(&[1.0], &[1.0, -0.5], &[0.0, 0.5]);
+        assert!((h[0].norm() - 2.0).abs() < 1e-6);
+        assert!((h[1].norm() - 2.0 / 3.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::biquad::Biquad;
+use crate::filterable::{Filterable, Filter};
+use crate::response::FrequencyResponse;
+
+use num_complex::{Complex32, Complex64};
+use std::f64::consts::PI;
+
+// Cascaded second-order sections designed from classic analog prototypes.
+// Poles/zeros are placed in the s-plane for a prototype normalized to 1 rad/s,
+// scaled (or inverted for highpass) to the pre-warped cutoff, mapped to z with
+// the bilinear transform, and finally paired into biquads.
+//
+// Elliptic design follows Orfanidis, "Lecture Notes on Elliptic Filter Design".
+
+/// Analog prototype used by the `SosCascade` designers.
+#[derive(Debug, Clone)]
+pub enum IirDesign {
+    Butterworth,
+    /// Equiripple passband; `cutoff` is the edge of the ripple band.
+    ChebyshevI { ripple_db: f64 },
+    /// Equiripple stopband; `cutoff` is the stopband edge, not the -3 dB point.
+    ChebyshevII { atten_db: f64 },
+    /// Equiripple pass- and stopband; `cutoff` is the passband edge.
+    Elliptic { ripple_db: f64, atten_db: f64 },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Band {
+    Lowpass,
+    Highpass,
+}
+
+/// A chain of biquads run back to back.
+#[derive(Debug, Clone)]
+pub struct SosCascade<Num> {
+    sections: Vec<Biquad<Num>>,
+}
+
+impl<Num> SosCascade<Num> where Num: Filterable<Num> {
+    pub fn new(sections: Vec<Biquad<Num>>) -> Self {
+        SosCascade { sections }
+    }
+
+    pub fn sections(&self) -> &[Biquad<Num>] {
+        &self.sections
+    }
+
+    pub fn process(&mut self, x: Num) -> Num {
+        self.sections.iter_mut().fold(x, |acc, s| s.process(acc))
+    }
+
+    pub fn lowpass(fs: f32, cutoff: f32, order: usize, design: &IirDesign) -> Self {
+        Self::design(fs, cutoff, order, design, Band::Lowpass)
+    }
+
+    pub fn highpass(fs: f32, cutoff: f32, order: usize, design: &IirDesign) -> Self {
+        Self::design(fs, cutoff, order, design, Band::Highpass)
+    }
+
+    fn design(fs: f32, cutoff: f32, order: usize, design: &IirDesign, band: Band) -> Self {
+        assert!(order > 0, "filter order must be at least 1");
+        let proto = design.prototype(order);
+        let warped = (PI * cutoff as f64 / fs as f64).tan();
+
+        // Frequency transform, then push the zeros at infinity to the band edge
+        // they land on after the bilinear transform (Nyquist for LP, DC for HP).
+        let (zeros, poles, infinite_zero): (Vec<Complex64>, Vec<Complex64>, f64) = match band {
+            Band::Lowpass => (
+                proto.zeros.iter().map(|&z| z * warped).collect(),
+                proto.poles.iter().map(|&p| p * warped).collect(),
+                -1.0,
+            ),
+            Band::Highpass => (
+                proto.zeros.iter().map(|&z| warped / z).collect(),
+                proto.poles.iter().map(|&p| warped / p).collect(),
+                1.0,
+            ),
+        };
+
+        let mut zeros: Vec<Complex64> = zeros.into_iter().map(bilinear).collect();
+        zeros.resize(poles.len(), Complex64::new(infinite_zero, 0.0));
+        let poles: Vec<Complex64> = poles.into_iter().map(bilinear).collect();
+
+        // Passband reference point on the unit circle: DC for LP, Nyquist for HP.
+        // Each section gets unity gain there; the first also carries the
+        // prototype's passband gain (below 1 for even-order equiripple designs).
+        let z_ref = -infinite_zero;
+        let sections = pair_sections(&zeros, &poles)
+            .into_iter()
+            .enumerate()
+            .map(|(i, (num, den))| {
+                let gain = if i == 0 { proto.passband_gain } else { 1.0 };
+                let scale = gain * (den[0] + den[1] * z_ref + den[2]) / (num[0] + num[1] * z_ref + num[2]);
+                let num = num.map(|b| b * scale);
+                Biquad::new(num[0] as f32, num[1] as f32, num[2] as f32, den[1] as f32, den[2] as f32)
+            })
+            .collect();
+
+        SosCascade { sections }
+    }
+}
+
+impl<Num> Filter<Num> for SosCascade<Num> where Num: Filterable<Num> {
+    fn process(&mut self, x: Num) -> Num {
+        self.process(x)
+    }
+}
+
+impl<Num> FrequencyResponse for SosCascade<Num> {
+    fn frequency_response(&self, freqs: &[f32]) -> Vec<Complex32> {
+        self.sections.iter().fold(vec![Complex32::new(1.0, 0.0); freqs.len()], |acc, s| {
+            acc.iter().zip(s.frequency_response(freqs)).map(|(a, h)| a * h).collect()
+        })
+    }
+
+    fn group_delay(&self, freqs: &[f32]) -> Vec<f32> {
+        self.sections.iter().fold(vec![0.0; freqs.len()], |acc, s| {
+            acc.iter().zip(s.group_delay(freqs)).map(|(a, gd)| a + gd).collect()
+        })
+    }
+}
+
+// ── Analog prototypes (normalized to 1 rad/s) ──────────────────────────
+
+struct AnalogPrototype {
+    zeros: Vec<Complex64>,
+    poles: Vec<Complex64>,
+    /// Gain at DC of the normalized lowpass.
+    passband_gain: f64,
+}
+
+impl IirDesign {
+    fn prototype(&self, order: usize) -> AnalogPrototype {
+        let n = order as f64;
+        // Angles of the Butterworth/Chebyshev pole pattern
+        let thetas: Vec<f64> = (1..=order).map(|i| PI * (2 * i - 1) as f64 / (2.0 * n)).collect();
+        let even_gain = |ripple_db: f64| if order.is_multiple_of(2) { 10f64.powf(-ripple_db / 20.0) } else { 1.0 };
+
+        match *self {
+            IirDesign::Butterworth => AnalogPrototype {
+                zeros: vec![],
+                poles: thetas.iter().map(|&t| Complex64::new(-t.sin(), t.cos())).collect(),
+                passband_gain: 1.0,
+            },
+            IirDesign::ChebyshevI { ripple_db } => {
+                let ep = (10f64.powf(ripple_db / 10.0) - 1.0).sqrt();
+                AnalogPrototype {
+                    zeros: vec![],
+                    poles: chebyshev_poles(&thetas, ep),
+                    passband_gain: even_gain(ripple_db),
+                }
+            }
+            IirDesign::ChebyshevII { atten_db } => {
+                let ep = (10f64.powf(atten_db / 10.0) - 1.0).sqrt().recip();
+                AnalogPrototype {
+                    zeros: thetas.iter()
+                        .filter(|t| t.cos().abs() > 1e-12)
+                        .map(|&t| Complex64::new(0.0, t.cos().recip()))
+                        .collect(),
+                    poles: chebyshev_poles(&thetas, ep).into_iter().map(|p| p.inv()).collect(),
+                    passband_gain: 1.0,
+                }
+            }
+            IirDesign::Elliptic { ripple_db, atten_db } => elliptic_prototype(order, ripple_db, atten_db),
+        }
+    }
+}
+
+fn chebyshev_poles(thetas: &[f64], ep: f64) -> Vec<Complex64> {
+    let v0 = ep.recip().asinh() / thetas.len() as f64;
+    thetas.iter()
+        .map(|&t| Complex64::new(-v0.sinh() * t.sin(), v0.cosh() * t.cos()))
+        .collect()
+}
+
+fn elliptic_prototype(order: usize, ripple_db: f64, atten_db: f64) -> AnalogPrototype {
+    let n = order as f64;
+    let ep = (10f64.powf(ripple_db / 10.0) - 1.0).sqrt();
+    let es = (10f64.powf(atten_db / 10.0) - 1.0).sqrt();
+    let k1 = ep / es;
+    let k = ellipdeg(order, k1);
+
+    let j = Complex64::i();
+    let v0 = (-j * asne(j / ep, k1) / n).re;
+
+    let mut zeros = Vec::with_capacity(order);
+    let mut poles = Vec::with_capacity(order);
+    for i in 1..=order / 2 {
+        let u = (2 * i - 1) as f64 / n;
+        let zeta = cde(Complex64::new(u, 0.0), k).re;
+        let z = j / (k * zeta);
+        zeros.push(z);
+        zeros.push(z.conj());
+        let p = j * cde(Complex64::new(u, -v0), k);
+        poles.push(p);
+        poles.push(p.conj());
+    }
+    if !order.is_multiple_of(2) {
+        poles.push(Complex64::new((j * sne(j * v0, k)).re, 0.0));
+    }
+
+    AnalogPrototype {
+        zeros,
+        poles,
+        passband_gain: if order.is_multiple_of(2) { 10f64.powf(-ripple_db / 20.0) } else { 1.0 },
+    }
+}
+
+// ── Jacobi elliptic helpers (Landen transformation) ────────────────────
+
+const LANDEN_STEPS: usize = 7;
+
+/// Descending Landen sequence of moduli starting from `k`.
+fn landen(k: f64) -> Vec<f64> {
+    let mut v = Vec::with_capacity(LANDEN_STEPS);
+    let mut k = k;
+    for _ in 0..LANDEN_STEPS {
+        k = (k / (1.0 + (1.0 - k * k).sqrt())).powi(2);
+        v.push(k);
+    }
+    v
+}
+
+/// Complete elliptic integrals K(k) and K'(k).
+fn ellipk(k: f64) -> (f64, f64) {
+    let kk = |k: f64| landen(k).iter().map(|v| 1.0 + v).product::<f64>() * PI / 2.0;
+    (kk(k), kk((1.0 - k * k).sqrt()))
+}
+
+/// Jacobi cd(uK, k) with u normalized to the quarter period.
+fn cde(u: Complex64, k: f64) -> Complex64 {
+    landen(k).iter().rev().fold((u * PI / 2.0).cos(), |w, &v| (1.0 + v) * w / (1.0 + v * w * w))
+}
+
+/// Jacobi sn(uK, k) with u normalized to the quarter period.
+fn sne(u: Complex64, k: f64) -> Complex64 {
+    landen(k).iter().rev().fold((u * PI / 2.0).sin(), |w, &v| (1.0 + v) * w / (1.0 + v * w * w))
+}
+
+/// Inverse of `cde`.
+fn acde(w: Complex64, k: f64) -> Complex64 {
+    let mut w = w;
+    let mut prev = k;
+    for v in landen(k) {
+        w = w / (1.0 + (1.0 - w * w * prev * prev).sqrt()) * 2.0 / (1.0 + v);
+        prev = v;
+    }
+    let u = w.acos() * 2.0 / PI;
+    let (kk, kp) = ellipk(k);
+    Complex64::new(srem(u.re, 4.0), srem(u.im, 2.0 * kp / kk))
+}
+
+/// Inverse of `sne`.
+fn asne(w: Complex64, k: f64) -> Complex64 {
+    1.0 - acde(w, k)
+}
+
+/// Solve the elliptic degree equation for the selectivity modulus.
+fn ellipdeg(order: usize, k1: f64) -> f64 {
+    let n = order as f64;
+    let k1p = (1.0 - k1 * k1).sqrt();
+    let prod: f64 = (1..=order / 2)
+        .map(|i| sne(Complex64::new((2 * i - 1) as f64 / n, 0.0), k1p).re)
+        .product();
+    let kp = k1p.powi(order as i32) * prod.powi(4);
+    (1.0 - kp * kp).sqrt()
+}
+
+/// Symmetric remainder, result in [-y/2, y/2].
+fn srem(x: f64, y: f64) -> f64 {
+    x - y * (x / y).round()
+}
+
+// ── s → z and section pairing ──────────────────────────────────────────
+
+/// Bilinear transform for a prototype already pre-warped with tan(πf/fs).
+fn bilinear(s: Complex64) -> Complex64 {
+    (1.0 + s) / (1.0 - s)
+}
+
+const IM_EPS: f64 = 1e-9;
+
+/// Group roots into conjugate pairs and pairs of reals, as polynomial
+/// coefficients [1, c1, c2]. A lone real root becomes a first-order term.
+fn root_groups(roots: &[Complex64]) -> Vec<(Complex64, [f64; 3])> {
+    let mut groups: Vec<(Complex64, [f64; 3])> = roots.iter()
+        .filter(|r| r.im > IM_EPS)
+        .map(|&r| (r, [1.0, -2.0 * r.re, r.norm_sqr()]))
+        .collect();
+
+    let mut reals: Vec<f64> = roots.iter().filter(|r| r.im.abs() <= IM_EPS).map(|r| r.re).collect();
+    reals.sort_by(|a, b| a.total_cmp(b));
+    for chunk in reals.chunks(2) {
+        match *chunk {
+            [a, b] => groups.push((Complex64::new(a.max(b), 0.0), [1.0, -(a + b), a * b])),
+            [a] => groups.push((Complex64::new(a, 0.0), [1.0, -a, 0.0])),
+            _ => unreachable!(),
+        }
+    }
+    groups
+}
+
+/// Pair each pole group with its nearest zero group of the same order,
+/// starting from the poles closest to the unit circle. Sections are
+/// returned with the highest-Q section last.
+fn pair_sections(zeros: &[Complex64], poles: &[Complex64]) -> Vec<([f64; 3], [f64; 3])> {
+    let mut zero_groups = root_groups(zeros);
+    let mut pole_groups = root_groups(poles);
+    pole_groups.sort_by(|a, b| b.0.norm().total_cmp(&a.0.norm()));
+
+    let order = |c: &[f64; 3]| if c[2] == 0.0 { 1 } else { 2 };
+    let mut sections: Vec<([f64; 3], [f64; 3])> = pole_groups.into_iter()
+        .map(|(p, den)| {
+            let best = zero_groups.iter()
+                .enumerate()
+                .filter(|(_, (_, num))| order(num) == order(&den))
+                .min_by(|(_, (a, _)), (_, (b, _))| (a - p).norm().total_cmp(&(b - p).norm()))
+                .map(|(i, _)| i)
+                .expect("unpaired pole group");
+            let (_, num) = zero_groups.swap_remove(best);
+            (num, den)
+        })
+        .collect();
+    sections.reverse();
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Steady-state gain of a filter at `freq`, measured with a sine.
+    fn measured_gain(mut filt: SosCascade<f32>, fs: f32, freq: f32) -> f32 {
+        let n = fs as usize;
+        let out: Vec<f32> = (0..n)
+            .map(|i| (2.0 * PI * freq as f64 * i as f64 / fs as f64).sin() as f32)
+            .map(|x| filt.process(x))
+            .collect();
+        let rms = (out[n / 2..].iter().map(|y| y * y).sum::<f32>() / (n / 2) as f32).sqrt();
+        rms * std::f32::consts::SQRT_2
+    }
+
+    fn db(x: f32) -> f32 {
+        20.0 * x.log10()
+    }
+
+    #[test]
+    fn test_butterworth_cutoff() {
+        let filt: SosCascade<f32> = SosCascade::lowpass(48000.0, 4000.0, 4, &IirDesign::Butterworth);
+        assert_eq!(filt.sections().len(), 2);
+        assert!((db(measured_gain(filt.clone(), 48000.0, 100.0))).abs() < 0.05);
+        let g = db(measured_gain(filt.clone(), 48000.0, 4000.0));
+        assert!((g + 3.01).abs() < 0.1, "gain at cutoff: {} dB", g);
+        // 4th order rolls off ~24 dB/octave (steeper near Nyquist due to warping)
+        assert!(db(measured_gain(filt, 48000.0, 16000.0)) < -48.0);
+    }
+
+    #[test]
+    fn test_butterworth_odd_highpass() {
+        let filt: SosCascade<f32> = SosCascade::highpass(48000.0, 1000.0, 3, &IirDesign::Butterworth);
+        assert_eq!(filt.sections().len(), 2);
+        let g = db(measured_gain(filt.clone(), 48000.0, 1000.0));
+        assert!((g + 3.01).abs() < 0.1, "gain at cutoff: {} dB", g);
+        assert!(db(measured_gain(filt.clone(), 48000.0, 10000.0)).abs() < 0.05);
+        assert!(db(measured_gain(filt, 48000.0, 250.0)) < -35.0);
+    }
+
+    #[test]
+    fn test_chebyshev1_ripple() {
+        let design = IirDesign::ChebyshevI { ripple_db: 1.0 };
+        let filt: SosCascade<f32> = SosCascade::lowpass(48000.0, 4000.0, 4, &design);
+        for f in [200.0, 1000.0, 2000.0, 3000.0, 3900.0] {
+            let g = db(measured_gain(filt.clone(), 48000.0, f));
+            assert!((-1.05..=0.05).contains(&g), "passband gain at {} Hz: {} dB", f, g);
+        }
+        let g = db(measured_gain(filt, 48000.0, 4000.0));
+        assert!((g + 1.0).abs() < 0.1, "gain at ripple edge: {} dB", g);
+    }
+
+    #[test]
+    fn test_chebyshev2_stopband() {
+        let design = IirDesign::ChebyshevII { atten_db: 50.0 };
+        let filt: SosCascade<f32> = SosCascade::lowpass(48000.0, 8000.0, 5, &design);
+        assert!(db(measured_gain(filt.clone(), 48000.0, 100.0)).abs() < 0.05);
+        for f in [8000.0, 9000.0, 12000.0, 20000.0] {
+            let g = db(measured_gain(filt.clone(), 48000.0, f));
+            assert!(g < -49.5, "stopband gain at {} Hz: {} dB", f, g);
+        }
+    }
+
+    #[test]
+    fn test_elliptic_pass_and_stop() {
+        let design = IirDesign::Elliptic { ripple_db: 0.5, atten_db: 60.0 };
+        let filt: SosCascade<f32> = SosCascade::lowpass(48000.0, 4000.0, 6, &design);
+        assert_eq!(filt.sections().len(), 3);
+        for f in [200.0, 1000.0, 2000.0, 3000.0, 4000.0] {
+            let g = db(measured_gain(filt.clone(), 48000.0, f));
+            assert!((-0.55..=0.05).contains(&g), "passband gain at {} Hz: {} dB", f, g);
+        }
+        // Stopband edge for this order/ripple/attenuation is ~5490 Hz
+        for f in [5600.0, 8000.0, 16000.0] {
+            let g = db(measured_gain(filt.clone(), 48000.0, f));
+            assert!(g < -59.0, "stopband gain at {} Hz: {} dB", f, g);
+        }
+    }
+
+    #[test]
+    fn test_elliptic_odd_order() {
+        let design = IirDesign::Elliptic { ripple_db: 1.0, atten_db: 40.0 };
+        let filt: SosCascade<f32> = SosCascade::highpass(48000.0, 2000.0, 3, &design);
+        assert_eq!(filt.sections().len(), 2);
+        let g = db(measured_gain(filt.clone(), 48000.0, 8000.0));
+        assert!((-1.05..=0.05).contains(&g), "passband gain: {} dB", g);
+        assert!(db(measured_gain(filt, 48000.0, 500.0)) < -39.0);
+    }
+
+    #[test]
+    fn test_elliptic_response_analysis() {
+        let design = IirDesign::Elliptic { ripple_db: 0.1, atten_db: 80.0 };
+        let filt: SosCascade<f32> = SosCascade::lowpass(240000.0, 15000.0, 8, &design);
+
+        let passband: Vec<f32> = (0..=150).map(|i| i as f32 * 100.0 / 240000.0).collect();
+        let db = filt.magnitude_db(&passband);
+        let (lo, hi) = db.iter().fold((f32::MAX, f32::MIN), |(lo, hi), &d| (lo.min(d), hi.max(d)));
+        assert!(hi < 0.01 && lo > -0.11, "passband ripple: {} .. {} dB", lo, hi);
+
+        // Stopband edge for this order/ripple/attenuation is ~20.8 kHz
+        let stopband: Vec<f32> = (0..=99).map(|i| (21000.0 + i as f32 * 1000.0) / 240000.0).collect();
+        let worst = filt.magnitude_db(&stopband).iter().fold(f32::MIN, |m, &d| m.max(d));
+        assert!(worst < -79.0, "stopband attenuation: {} dB", worst);
+    }
+
+    #[test]
+    fn test_cascade_group_delay_matches_phase_slope() {
+        let filt: SosCascade<f32> = SosCascade::lowpass(48000.0, 4000.0, 4, &IirDesign::Butterworth);
+        let freqs = [0.01, 0.05, 0.1];
+        // -dφ/dω by central difference of the cascade's phase response
+        let step = 1e-3;
+        let below: Vec<f32> = freqs.iter().map(|f| f - step).collect();
+        let above: Vec<f32> = freqs.iter().map(|f| f + step).collect();
+        let slope: Vec<f32> = filt.frequency_response(&above).iter().zip(filt.frequency_response(&below))
+            .map(|(hi, lo)| -(hi / lo).arg() / (2.0 * std::f32::consts::PI * 2.0 * step))
+            .collect();
+        for (f, (gd, expected)) in freqs.iter().zip(filt.group_delay(&freqs).iter().zip(slope)) {
+            assert!((gd - expected).abs() < 0.01 * expected, "group delay at {}: {} vs {}", f, gd, expected);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,149 @@
+use num_complex::Complex32;
+
+use crate::osc::Osc;
+use crate::sos::{IirDesign, SosCascade};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sideband {
+    Upper,
+    Lower,
+}
+
+/// Weaver SSB demodulator.
+///
+/// The complex baseband is shifted so the wanted sideband is centred on
+/// 0 Hz, lowpassed to half the audio bandwidth (removing the other
+/// sideband), shifted back and the real part taken as audio.
+pub struct SsbDemodulator {
+    shift_in: Osc,
+    filter: SosCascade<Complex32>,
+    shift_out: Osc,
+}
+
+impl SsbDemodulator {
+    /// `bandwidth` is the audio bandwidth in Hz, measured from the carrier.
+    pub fn new(fs: f32, sideband: Sideband, bandwidth: f32) -> Self {
+        let centre = match sideband {
+            Sideband::Upper => bandwidth / 2.0,
+            Sideband::Lower => -bandwidth / 2.0,
+        };
+        SsbDemodulator {
+            shift_in: Osc::new(-centre, fs),
+            filter: SosCascade::lowpass(fs, bandwidth / 2.0, 8, &IirDesign::Elliptic { ripple_db: 0.5, atten_db: 60.0 }),
+            shift_out: Osc::new(centre, fs),
+        }
+    }
+
+    pub fn process(&mut self, sample: Complex32) -> f32 {
+        let centred = self.filter.process(sample * self.shift_in.next());
+        (centred * self.shift_out.next()).re
+    }
+}
+
+/// CW demodulator: a narrow filter around the carrier and a beat
+/// frequency oscillator to make the keyed carrier audible.
+pub struct CwDemodulator {
+    filter: SosCascade<Complex32>,
+    bfo: Osc,
+}
+
+impl CwDemodulator {
+    /// `bfo` is the audio pitch of a zero-beat signal, `bandwidth` the
+    /// total filter width, both in Hz.
+    pub fn new(fs: f32, bfo: f32, bandwidth: f32) -> Self {
+        CwDemodulator {
+            filter: SosCascade::lowpass(fs, bandwidth / 2.0, 6, &IirDesign::Elliptic { ripple_db: 0.5, atten_db: 60.0 }),
+            bfo: Osc::new(bfo, fs),
+        }
+    }
+
+    pub fn process(&mut self, sample: Complex32) -> f32 {
+        (self.filter.process(sample) * self.bfo.next()).re
+    }
+}
+
+pub struct SsbDemodIter<I> {
+    iter: I,
+    demodulator: SsbDemodulator,
+}
+
+impl<I> Iterator for SsbDemodIter<I> where I: Iterator<Item = Complex32> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.iter.next()?;
+        Some(self.demodulator.process(sample))
+    }
+}
+
+pub struct CwDemodIter<I> {
+    iter: I,
+    demodulator: CwDemodulator,
+}
+
+impl<I> Iterator for CwDemodIter<I> where I: Iterator<Item = Complex32> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.iter.next()?;
+        Some(self.demodulator.process(sample))
+    }
+}
+
+pub trait SsbDemodulatable {
+    fn ssb_demodulate(self, fs: f32, sideband: Sideband, bandwidth: f32) -> SsbDemodIter<Self> where Self: Sized;
+    fn cw_demodulate(self, fs: f32, bfo: f32, bandwidth: f32) -> CwDemodIter<Self> where Self: Sized;
+}
+
+impl<I> SsbDemodulatable for I where I: Iterator<Item = Complex32> {
+    fn ssb_demodulate(self, fs: f32, sideband: Sideband, bandwidth: f32) -> SsbDemodIter<Self> {
+        SsbDemodIter { iter: self, demodulator: SsbDemodulator::new(fs, sideband, bandwidth) }
+    }
+
+    fn cw_demodulate(self, fs: f32, bfo: f32, bandwidth: f32) -> CwDemodIter<Self> {
+        CwDemodIter { iter: self, demodulator: CwDemodulator::new(fs, bfo, bandwidth) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f32 = 48000.0;
+
+    fn tone(freq: f32, n: usize) -> impl Iterator<Item = Complex32> {
+        (0..n).map(move |i| Complex32::from_polar(1.0, 2.0 * std::f32::consts::PI * freq * i as f32 / FS))
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    fn ssb_rms(freq: f32, sideband: Sideband) -> f32 {
+        let out: Vec<f32> = tone(freq, FS as usize / 2).ssb_demodulate(FS, sideband, 3000.0).collect();
+        rms(&out[out.len() / 2..])
+    }
+
+    #[test]
+    fn test_sideband_selection() {
+        // Full-scale real tone has RMS 1/√2
+        let passed = std::f32::consts::FRAC_1_SQRT_2;
+        assert!((ssb_rms(1000.0, Sideband::Upper) - passed).abs() < 0.05);
+        assert!((ssb_rms(-1000.0, Sideband::Lower) - passed).abs() < 0.05);
+        assert!(ssb_rms(-1000.0, Sideband::Upper) < 0.01);
+        assert!(ssb_rms(1000.0, Sideband::Lower) < 0.01);
+    }
+
+    #[test]
+    fn test_cw_beat_note() {
+        // Carrier 100 Hz above the dial beats at 700 + 100 Hz
+        let out: Vec<f32> = tone(100.0, FS as usize / 2).cw_demodulate(FS, 700.0, 500.0).collect();
+        let tail = &out[out.len() / 2..];
+        let crossings = tail.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        let pitch = crossings as f32 / (tail.len() as f32 / FS);
+        assert!((pitch - 800.0).abs() < 10.0, "pitch {}", pitch);
+
+        let far: Vec<f32> = tone(1500.0, FS as usize / 2).cw_demodulate(FS, 700.0, 500.0).collect();
+        assert!(rms(&far[far.len() / 2..]) < 0.01);
+    }
+}
@@ -0,0 +1,192 @@
+//! Reference oscillator calibration against a broadcast FM station.
+//!
+//! Two independent measurements come out of the same discriminator output:
+//! the carrier offset (its DC level), which scales with the tuned frequency,
+//! and the 19 kHz stereo pilot, which transmitters hold to within a hertz
+//! and which shifts with the sample clock. Both clocks come from the same
+//! reference on the supported SDRs, so either gives the oscillator error.
+
+use std::f64::consts::PI;
+
+use num_complex::Complex64;
+
+/// What the ppm estimate is taken from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CalibrationReference {
+    /// The station's 19 kHz stereo pilot.
+    Pilot,
+    /// The station's carrier, or any unmodulated carrier at a known frequency.
+    Carrier,
+}
+
+impl std::str::FromStr for CalibrationReference {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pilot" => Ok(CalibrationReference::Pilot),
+            "carrier" => Ok(CalibrationReference::Carrier),
+            _ => Err(format!("unknown calibration reference '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    /// Carrier offset from the tuned frequency in Hz.
+    pub carrier_offset: f64,
+    pub carrier_ppm: f64,
+    /// Absent when no pilot was found.
+    pub pilot_ppm: Option<f64>,
+    /// Measured pilot deviation in Hz.
+    pub pilot_deviation: f64,
+}
+
+impl Calibration {
+    pub fn ppm(&self, reference: CalibrationReference) -> Option<f64> {
+        match reference {
+            CalibrationReference::Pilot => self.pilot_ppm,
+            CalibrationReference::Carrier => Some(self.carrier_ppm),
+        }
+    }
+}
+
+const PILOT_FREQ: f64 = 19000.0;
+// Pilot phasor averaging time; keeps the audio and L−R at least 4 kHz
+// away well down, and allows ±5 Hz (±260 ppm) of pilot error unambiguously
+const PILOT_BLOCK_S: f64 = 0.1;
+// Broadcast pilots run at 8–10% of 75 kHz deviation
+const MIN_PILOT_DEVIATION: f64 = 2000.0;
+
+/// Accumulates discriminator output (phase step per sample in radians).
+pub struct Calibrator {
+    fs: f64,
+    samples: u64,
+    demod_sum: f64,
+    pilot_phase: f64,
+    pilot_step: f64,
+    block_len: usize,
+    block: Complex64,
+    block_count: usize,
+    last_block: Option<Complex64>,
+    block_products: Complex64,
+    block_magnitudes: f64,
+    blocks: usize,
+}
+
+impl Calibrator {
+    pub fn new(fs: f32) -> Self {
+        let fs = fs as f64;
+        Calibrator {
+            fs,
+            samples: 0,
+            demod_sum: 0.0,
+            pilot_phase: 0.0,
+            pilot_step: 2.0 * PI * PILOT_FREQ / fs,
+            block_len: (PILOT_BLOCK_S * fs) as usize,
+            block: Complex64::new(0.0, 0.0),
+            block_count: 0,
+            last_block: None,
+            block_products: Complex64::new(0.0, 0.0),
+            block_magnitudes: 0.0,
+            blocks: 0,
+        }
+    }
+
+    pub fn process(&mut self, demod: f32) {
+        let demod = demod as f64;
+        self.samples += 1;
+        self.demod_sum += demod;
+
+        self.block += Complex64::from_polar(demod, -self.pilot_phase);
+        self.pilot_phase = (self.pilot_phase + self.pilot_step) % (2.0 * PI);
+        self.block_count += 1;
+        if self.block_count == self.block_len {
+            let block = self.block / self.block_len as f64;
+            if let Some(last) = self.last_block {
+                self.block_products += block * last.conj();
+            }
+            self.last_block = Some(block);
+            self.block_magnitudes += block.norm();
+            self.blocks += 1;
+            self.block = Complex64::new(0.0, 0.0);
+            self.block_count = 0;
+        }
+    }
+
+    /// Seconds of signal measured so far.
+    pub fn duration(&self) -> f64 {
+        self.samples as f64 / self.fs
+    }
+
+    /// Results for a station tuned at `station` Hz.
+    pub fn finish(&self, station: f64) -> Calibration {
+        let to_hz = self.fs / (2.0 * PI);
+        let carrier_offset = if self.samples > 0 { self.demod_sum / self.samples as f64 * to_hz } else { 0.0 };
+        // A fast oscillator tunes high, leaving the station below centre
+        let carrier_ppm = -carrier_offset / station * 1e6;
+
+        // The block phasor is half the pilot's peak phase step
+        let pilot_deviation = if self.blocks > 0 { 2.0 * self.block_magnitudes / self.blocks as f64 * to_hz } else { 0.0 };
+        let pilot_ppm = (self.blocks > 1 && pilot_deviation > MIN_PILOT_DEVIATION).then(|| {
+            let pilot_offset = self.block_products.arg() / (2.0 * PI * PILOT_BLOCK_S);
+            // A fast sample clock makes the pilot read low
+            -pilot_offset / PILOT_FREQ * 1e6
+        });
+
+        Calibration { carrier_offset, carrier_ppm, pilot_ppm, pilot_deviation }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_complex::Complex32;
+    use rradio_dsp::fm_demod::{ConjugateProductDiscriminator, FmDemodulatable};
+
+    const FS: f64 = 240000.0;
+    const STATION: f64 = 100e6;
+
+    /// FM station as received through an oscillator `ppm` fast: the carrier
+    /// lands below centre and the pilot reads low against the sample clock.
+    fn received(ppm: f64, pilot: bool, seconds: f64) -> impl Iterator<Item = Complex32> {
+        let offset = -STATION * ppm * 1e-6;
+        let pilot_freq = PILOT_FREQ / (1.0 + ppm * 1e-6);
+        let pilot_dev = if pilot { 6750.0 } else { 0.0 };
+        let mut phase = 0.0f64;
+        (0..(seconds * FS) as usize).map(move |i| {
+            let t = i as f64 / FS;
+            let freq = offset
+                + pilot_dev * (2.0 * PI * pilot_freq * t).sin()
+                + 40000.0 * (2.0 * PI * 1000.0 * t).sin();
+            phase = (phase + 2.0 * PI * freq / FS) % (2.0 * PI);
+            Complex32::from_polar(1.0, phase as f32)
+        })
+    }
+
+    fn calibrate(ppm: f64, pilot: bool) -> Calibration {
+        let mut cal = Calibrator::new(FS as f32);
+        received(ppm, pilot, 5.0)
+            .fm_demodulate_with(ConjugateProductDiscriminator::new())
+            .for_each(|x| cal.process(x));
+        cal.finish(STATION)
+    }
+
+    #[test]
+    fn test_carrier_and_pilot_agree() {
+        let result = calibrate(20.0, true);
+        assert!((result.carrier_offset + 2000.0).abs() < 1.0, "offset {}", result.carrier_offset);
+        assert!((result.carrier_ppm - 20.0).abs() < 0.01);
+        let pilot_ppm = result.pilot_ppm.expect("pilot not found");
+        assert!((pilot_ppm - 20.0).abs() < 1.0, "pilot ppm {}", pilot_ppm);
+        assert!((result.pilot_deviation - 6750.0).abs() < 100.0, "pilot deviation {}", result.pilot_deviation);
+    }
+
+    #[test]
+    fn test_mono_station_has_no_pilot() {
+        let result = calibrate(-8.0, false);
+        assert!(result.pilot_ppm.is_none());
+        assert_eq!(result.ppm(CalibrationReference::Pilot), None);
+        assert!((result.ppm(CalibrationReference::Carrier).unwrap() + 8.0).abs() < 0.01);
+    }
+}
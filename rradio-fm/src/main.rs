@@ -1,8 +1,11 @@
 mod playback;
 mod wideband_fm_audio;
+mod narrowband_fm_audio;
 mod rds_decoder;
 mod chip_sync;
 mod rds_demod;
+mod region;
+mod calibrate;
 
 use std::sync::atomic;
 use std::sync::Arc;
@@ -17,14 +20,38 @@ use rustfft::{num_traits::Zero, FftPlanner};
 
 use rradio_dsp::filterable::Filter;
 use rradio_dsp::filterable::FilterableIter;
-use rradio_dsp::fm_demod::FmDemodulatable;
+use rradio_dsp::afc::{AfcConfig, AfcCorrectable, AfcEstimator, AfcHandle};
+use rradio_dsp::agc::{Agc, AgcConfig, AgcDetector};
+use rradio_dsp::am_demod::AmDemodulatable;
+use rradio_dsp::iq_correction::IqCorrectionConfig;
+use rradio_dsp::fm_demod::{
+    AnyDiscriminator, ConjugateProductDiscriminator, FastAtanDiscriminator, FmDemodulatable, FmDemodulator,
+    QuadratureDiscriminator,
+};
+use rradio_dsp::ssb_demod::{Sideband, SsbDemodulatable};
 use rradio_dsp::interleaver::InterleaveableIter;
 use rradio_dsp::resample::{Downsampleable, RationalResampleable};
 use rradio_dsp::spy::SpyableIter;
-use rradio_dsp::osc::Mixable;
-
+use rradio_dsp::pll::PilotEvent;
+
+use rradio_sdr::gain::{GainConfig, GainControl};
+use rradio_sdr::pluto::PlutoSource;
+use rradio_sdr::rtl_tcp::RtlTcpSource;
+use rradio_sdr::rtl_tcp_server;
+use rradio_sdr::soapy::SoapySource;
+use rradio_sdr::source::{stream_with_reconnect, IqSource, SourceError, SourceEvent};
+use rradio_sdr::profile::Profiles;
+use rradio_sdr::raw::{RawIqFormat, RawIqSource};
+use rradio_sdr::recorder::{RecordMode, Recorder, RecorderConfig, RotationLimits};
+use rradio_sdr::sigmf::{RecordingLog, SigmfDatatype, SigmfError, SigmfStreamer};
+use rradio_sdr::sigmf_archive::{SigmfArchive, SigmfCollection, ARCHIVE_EXTENSION};
+use rradio_sdr::wav::WavIqSource;
+
+use crate::calibrate::{CalibrationReference, Calibrator};
 use crate::rds_demod::RdsDemodulatable;
-use crate::wideband_fm_audio::WidebandFmAudioIterable;
+use crate::narrowband_fm_audio::{NarrowbandFmAudioIterable, NarrowbandFmConfig};
+use crate::region::{Region, RegionConfig};
+use crate::wideband_fm_audio::{StereoBlendConfig, StereoMode, WidebandFmAudioIterable};
 
 #[allow(dead_code)]
 fn spectrogram<T>(window_size: usize, overlap: usize, fs: f32, samples: &[T]) where Complex32: From<T>, T: Copy {
@@ -144,138 +171,38 @@ fn plot_re(samples: &[f32], title: &str) {
     plot.show();
 }
 
-fn buffer_pluto(done: &atomic::AtomicBool, config: &rradio_sdr::pluto::SdrConfig, mut out: rradio_dsp::buffer::SendBuf<Vec<Complex32>>) {
-    const MAX_RETRIES: u32 = 3;
-
-    while !done.load(atomic::Ordering::SeqCst) {
-        // (Re)connect and configure SDR
-        let mut sdr = match rradio_sdr::pluto::PlutoSdr::connect(config) {
-            Ok(sdr) => {
-                eprintln!("SDR connected");
-                sdr
-            }
-            Err(e) => {
-                eprintln!("SDR connect failed: {:?}, retrying in 1s...", e);
-                std::thread::sleep(std::time::Duration::from_secs(1));
-                continue;
-            }
-        };
-
-        let mut streamer = match sdr.start_iq() {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("SDR start_iq failed: {:?}, retrying in 1s...", e);
-                std::thread::sleep(std::time::Duration::from_secs(1));
-                continue;
-            }
-        };
-
-        // Stream until error or shutdown
-        let mut consecutive_errors: u32 = 0;
-        while !done.load(atomic::Ordering::SeqCst) {
-            if let Some(mut buf) = out.get() {
-                match streamer.collect_iq(&mut buf) {
-                    Ok(()) => {
-                        consecutive_errors = 0;
-                        out.commit(buf);
-                    }
-                    Err(e) => {
-                        consecutive_errors += 1;
-                        if consecutive_errors >= MAX_RETRIES {
-                            eprintln!("SDR error: {:?} ({} consecutive failures), reconnecting...", e, consecutive_errors);
-                            break;
-                        }
-                        eprintln!("SDR error: {:?} (attempt {}/{})", e, consecutive_errors, MAX_RETRIES);
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
-                }
-            } else {
-                // SendBuf returned None — receiver is gone
-                return;
-            }
-        }
-
-        // Tear down before reconnecting
-        sdr.stop_iq(streamer);
-
-        if !done.load(atomic::Ordering::SeqCst) {
-            eprintln!("SDR connection lost, reconnecting in 1s...");
-            std::thread::sleep(std::time::Duration::from_secs(1));
+/// Stream from the configured source until done, reconnecting hardware on
+/// failure. Reconnects, lost blocks and retunes are noted in `recording_log`.
+fn buffer_source(done: &atomic::AtomicBool, source: SourceConfig, out: rradio_dsp::buffer::SendBuf<Vec<Complex32>>, recording_log: Option<RecordingLog>) {
+    let on_event = move |sample: u64, event: SourceEvent| {
+        let Some(log) = recording_log.as_ref() else { return };
+        match event {
+            SourceEvent::Reconnected => log.mark(sample, "SDR reconnected", None),
+            SourceEvent::DroppedBuffer(e) => log.mark(sample, "dropped buffer", Some(e)),
+            SourceEvent::Retuned(frequency) => log.retune(sample, frequency),
         }
+    };
+    match source {
+        SourceConfig::Pluto { config } => stream_with_reconnect(done, "SDR", || PlutoSource::connect(&config), out, on_event),
+        SourceConfig::Soapy { config } => stream_with_reconnect(done, "SoapySDR", || SoapySource::connect(&config), out, on_event),
+        SourceConfig::RtlTcp { config } => stream_with_reconnect(done, "rtl_tcp", || {
+            let source = RtlTcpSource::connect(&config)?;
+            eprintln!("rtl_tcp: {} tuner, {} gain steps", source.tuner(), source.gain_steps());
+            Ok(source)
+        }, out, on_event),
+        SourceConfig::File { source, name, .. } => stream_recording(done, name, source, out, on_event),
     }
 }
 
-fn buffer_soapy(done: &atomic::AtomicBool, config: &rradio_sdr::soapy::SoapyConfig, mut out: rradio_dsp::buffer::SendBuf<Vec<Complex32>>) {
-    const MAX_RETRIES: u32 = 3;
-
-    while !done.load(atomic::Ordering::SeqCst) {
-        let sdr = match rradio_sdr::soapy::SoapySdr::connect(config) {
-            Ok(sdr) => {
-                eprintln!("SoapySDR connected");
-                sdr
-            }
-            Err(e) => {
-                eprintln!("SoapySDR connect failed: {}, retrying in 1s...", e);
-                std::thread::sleep(std::time::Duration::from_secs(1));
-                continue;
-            }
-        };
-
-        let mut streamer = match sdr.start_iq() {
-            Ok(s) => s,
-            Err(e) => {
-                eprintln!("SoapySDR start_iq failed: {}, retrying in 1s...", e);
-                std::thread::sleep(std::time::Duration::from_secs(1));
-                continue;
-            }
-        };
-
-        let mut consecutive_errors: u32 = 0;
-        while !done.load(atomic::Ordering::SeqCst) {
-            if let Some(mut buf) = out.get() {
-                match streamer.collect_iq(&mut buf) {
-                    Ok(()) => {
-                        consecutive_errors = 0;
-                        out.commit(buf);
-                    }
-                    Err(e) => {
-                        consecutive_errors += 1;
-                        if consecutive_errors >= MAX_RETRIES {
-                            eprintln!("SoapySDR error: {} ({} consecutive failures), reconnecting...", e, consecutive_errors);
-                            break;
-                        }
-                        eprintln!("SoapySDR error: {} (attempt {}/{})", e, consecutive_errors, MAX_RETRIES);
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
-                }
-            } else {
-                return;
-            }
-        }
-
-        if let Err(e) = streamer.deactivate() {
-            eprintln!("SoapySDR deactivate error: {}", e);
-        }
-
-        if !done.load(atomic::Ordering::SeqCst) {
-            eprintln!("SoapySDR connection lost, reconnecting in 1s...");
-            std::thread::sleep(std::time::Duration::from_secs(1));
-        }
-    }
+/// A recording or pipe can only be opened once; it ends rather than failing.
+fn stream_recording<S: IqSource>(done: &atomic::AtomicBool, name: &str, source: S, out: rradio_dsp::buffer::SendBuf<Vec<Complex32>>, on_event: impl FnMut(u64, SourceEvent)) {
+    stream_with_reconnect(done, name, connect_once(source), out, on_event)
 }
 
-fn buffer_sigmf(done: &atomic::AtomicBool, mut streamer: rradio_sdr::sigmf::SigmfStreamer, mut out: rradio_dsp::buffer::SendBuf<Vec<Complex32>>, tune_offset: f32, fs: f32) {
-    const CHUNK: usize = 4*1024*1024;
-    while !done.load(atomic::Ordering::SeqCst) {
-        if let Some(mut buf) = out.get() {
-            buf.clear();
-            buf.extend((&mut streamer).take(CHUNK).mix(tune_offset, fs));
-            if buf.is_empty() {
-                break;
-            }
-            out.commit(buf);
-        }
-    }
+/// Hand out `source` on the first connect only.
+fn connect_once<S>(source: S) -> impl FnMut() -> Result<S, SourceError> {
+    let mut source = Some(source);
+    move || source.take().ok_or_else(|| SourceError::Connect("recording already consumed".to_string()))
 }
 
 struct AudioPipelineObservationSettings {
@@ -284,9 +211,47 @@ struct AudioPipelineObservationSettings {
     spy_audio: bool,
 }
 
+/// `--record` and the options that shape it.
+struct RecordSettings {
+    path: String,
+    /// Sample format on disk; the source's own when `None`.
+    format: Option<SigmfDatatype>,
+    mode: RecordMode,
+}
+
+/// Annotation label prefix that a `--record-trigger` name stands for.
+fn trigger_label(name: &str) -> String {
+    match name {
+        "sync" => "RDS sync lost",
+        "pi" => "RDS PI",
+        "squelch" => "squelch open",
+        label => label,
+    }.to_string()
+}
+
 struct SignalPipelineSettings {
     iq_downsample: usize,
     fm_demod_downsample: usize,
+    discriminator: FmDiscriminator,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum FmDiscriminator {
+    Polar,
+    ConjugateProduct,
+    FastAtan,
+    Quadrature,
+}
+
+impl FmDiscriminator {
+    fn build(self) -> AnyDiscriminator {
+        match self {
+            FmDiscriminator::Polar => AnyDiscriminator::Polar(FmDemodulator::new()),
+            FmDiscriminator::ConjugateProduct => AnyDiscriminator::ConjugateProduct(ConjugateProductDiscriminator::new()),
+            FmDiscriminator::FastAtan => AnyDiscriminator::FastAtan(FastAtanDiscriminator::new()),
+            FmDiscriminator::Quadrature => AnyDiscriminator::Quadrature(QuadratureDiscriminator::new()),
+        }
+    }
 }
 
 fn signal_pipeline(
@@ -294,29 +259,35 @@ fn signal_pipeline(
     fs: f32,
     inbuf: rradio_dsp::buffer::RecvBuf<Vec<Complex32>>,
     mut audio_out: rradio_dsp::buffer::SendBuf<Vec<(f32, f32)>>,
-    mut rds_out: rradio_dsp::buffer::SendBuf<Vec<f32>>,
+    mut rds_out: rradio_dsp::buffer::SendBuf<Vec<Complex32>>,
+    rds_pilot_ref: bool,
     settings: SignalPipelineSettings,
     obs_settings: AudioPipelineObservationSettings,
     mpx_path: Option<String>,
+    region: RegionConfig,
+    stereo_blend: StereoBlendConfig,
+    afc: Option<AfcConfig>,
+    station_freq: f64,
+    recording_log: Option<RecordingLog>,
 ) {
-    let samples = rradio_dsp::buffer::RecvBufIter::new(inbuf);
+    // AFC: the NCO at the front follows the offset measured after the discriminator
+    let afc_handle = AfcHandle::new();
+    let samples = rradio_dsp::buffer::RecvBufIter::new(inbuf)
+        .afc_mix(afc_handle.clone(), fs);
 
     let fs_spy = fs;
     let samples = samples.maybe_spy(6000000, move |iq_samples| {
         spectrogram(8192, 512, fs_spy, &iq_samples);
     }, obs_settings.spy_iq);
 
-    // IQ filtering + downsample
-    let iq_filter_stages = 2;
-    let iq_filters: Vec<rradio_dsp::biquad::Biquad<Complex32>> = (0..iq_filter_stages)
-        .map(|_| rradio_dsp::biquad::Biquad::lowpass(fs, 300000.0, 0.707))
-        .collect();
-    let mut filtered: Box<dyn Iterator<Item = Complex32>> = Box::new(samples);
-    for filt in iq_filters {
-        filtered = Box::new(filtered.dsp_filter(filt));
-    }
+    // IQ filtering + downsample (4th-order Butterworth)
+    let iq_filter: rradio_dsp::sos::SosCascade<Complex32> = rradio_dsp::sos::SosCascade::lowpass(
+        fs, 300000.0, 4, &rradio_dsp::sos::IirDesign::Butterworth,
+    );
+    let filtered = samples.dsp_filter(iq_filter);
     let fs: f32 = fs / (settings.iq_downsample as f32);
-    let resampled = filtered.downsample(settings.iq_downsample);
+    let resampled = filtered.downsample(settings.iq_downsample)
+        .dsp_filter(Agc::new(fs, iq_agc_config()));
 
     let fs_spy = fs;
     let resampled = resampled.maybe_spy(6000000, move |audio_samples| {
@@ -328,10 +299,22 @@ fn signal_pipeline(
     let fm_decim_taps: Vec<f32> = rradio_dsp::fir::generate_lowpass_taps(
         fs as f64, 80_000.0, 31, &rradio_dsp::fir::WindowType::Blackman,
     );
-    let demoded = resampled.dsp_filter(fm_filt).fm_demodulate().resample(fm_decim_taps, 1, settings.fm_demod_downsample);
-    let _fs = fs / (settings.fm_demod_downsample as f32);
+    let mut afc_estimator = afc.clone().map(|config| AfcEstimator::new(fs, config, afc_handle.clone()));
+    let mut afc_estimates: usize = 0;
+    let demoded = resampled.dsp_filter(fm_filt).fm_demodulate_with(settings.discriminator.build())
+        .inspect(move |&s| {
+            let Some(estimator) = afc_estimator.as_mut() else { return };
+            if estimator.process(s).is_some() {
+                afc_estimates += 1;
+                if afc_estimates.is_multiple_of(AFC_REPORT_INTERVAL) {
+                    report_afc(estimator.handle(), station_freq);
+                }
+            }
+        })
+        .resample(fm_decim_taps, 1, settings.fm_demod_downsample);
+    let wfm_fs = fs / (settings.fm_demod_downsample as f32);
 
-    let fs_spy = _fs;
+    let fs_spy = wfm_fs;
     let demoded = demoded.maybe_spy(48000, move |audio_samples| {
         spectrogram(1024, 512, fs_spy, &audio_samples);
     }, obs_settings.spy_demoded);
@@ -340,7 +323,7 @@ fn signal_pipeline(
     let mut mpx_writer = mpx_path.map(|path| {
         let spec = hound::WavSpec {
             channels: 1,
-            sample_rate: _fs as u32,
+            sample_rate: wfm_fs as u32,
             bits_per_sample: 16,
             sample_format: hound::SampleFormat::Int,
         };
@@ -358,10 +341,13 @@ fn signal_pipeline(
     });
 
     // Wideband FM audio (stereo + RDS extraction) — tee to two consumers
-    let mut wfm = demoded.wfm_audio(_fs);
+    let mut wfm = demoded.wfm_audio(wfm_fs, &region, stereo_blend);
+
+    let mut rds_downconvert = rds_demod::RdsDownconverter::new(wfm_fs, rds_pilot_ref);
 
     let mut audio_batch: Option<rradio_dsp::buffer::BufToken<Vec<(f32, f32)>>> = None;
-    let mut rds_batch: Option<rradio_dsp::buffer::BufToken<Vec<f32>>> = None;
+    let mut rds_batch: Option<rradio_dsp::buffer::BufToken<Vec<Complex32>>> = None;
+    let mut wfm_samples: u64 = 0;
 
     while !done.load(atomic::Ordering::SeqCst) {
         // Ensure we have output buffers
@@ -379,8 +365,20 @@ fn signal_pipeline(
             Some(s) => s,
             None => break,
         };
+        wfm_samples += 1;
+        let stereo_change = match sample.stereo_event {
+            Some(PilotEvent::Locked) => Some(("stereo", "Stereo pilot locked")),
+            Some(PilotEvent::Unlocked) => Some(("mono", "Stereo pilot lost")),
+            None => None,
+        };
+        if let Some((label, message)) = stereo_change {
+            eprintln!("{}", message);
+            if let Some(log) = recording_log.as_ref() {
+                log.mark_at(wfm_samples as f64 / wfm_fs as f64, label, Some(message.to_string()));
+            }
+        }
 
-        // Tee: audio gets (left, right), RDS gets raw MPX
+        // Tee: audio gets (left, right), RDS gets MPX downconverted from 57 kHz
         if let Some(ref mut buf) = audio_batch {
             buf.push((sample.left, sample.right));
             if buf.len() >= 4096 {
@@ -388,7 +386,7 @@ fn signal_pipeline(
             }
         }
         if let Some(ref mut buf) = rds_batch {
-            buf.push(sample.mpx);
+            buf.push(rds_downconvert.process(sample.mpx, sample.rds_carrier));
             if buf.len() >= 4096 {
                 rds_out.commit(rds_batch.take().unwrap());
             }
@@ -402,9 +400,110 @@ fn signal_pipeline(
     if let Some(buf) = rds_batch {
         if !buf.is_empty() { rds_out.commit(buf); }
     }
+
+    if afc.is_some() {
+        report_afc(&afc_handle, station_freq);
+    }
+}
+
+// AFC estimates (0.1 s each) between progress reports
+const AFC_REPORT_INTERVAL: usize = 100;
+
+fn report_afc(handle: &AfcHandle, station_freq: f64) {
+    if station_freq > 0.0 {
+        eprintln!("AFC: carrier offset {:+.0} Hz ({:+.2} ppm)", handle.offset(), handle.ppm(station_freq));
+    } else {
+        eprintln!("AFC: carrier offset {:+.0} Hz", handle.offset());
+    }
 }
 
-fn compute_pipeline_settings(fs: f32) -> SignalPipelineSettings {
+/// Narrowband modes: IQ → 240 kHz → channel filter to 48 kHz → demod → mono audio.
+fn narrowband_pipeline(
+    done: &atomic::AtomicBool,
+    fs: f32,
+    inbuf: rradio_dsp::buffer::RecvBuf<Vec<Complex32>>,
+    mut audio_out: rradio_dsp::buffer::SendBuf<Vec<(f32, f32)>>,
+    settings: SignalPipelineSettings,
+    mode: ReceiveMode,
+    recording_log: Option<RecordingLog>,
+) {
+    let samples = rradio_dsp::buffer::RecvBufIter::new(inbuf);
+
+    // Decimate to the same 240 kHz intermediate rate the WFM path uses
+    let signal_downsample = settings.iq_downsample * settings.fm_demod_downsample;
+    let if_fs = fs / signal_downsample as f32;
+    let if_taps = rradio_dsp::fir::generate_lowpass_taps(
+        fs as f64, 40_000.0, 16 * signal_downsample + 1, &rradio_dsp::fir::WindowType::Blackman,
+    );
+
+    // Channel filter while dropping to the audio rate
+    let audio_fs = if_fs / AUDIO_DOWNSAMPLE as f32;
+    let channel_cutoff = match &mode {
+        ReceiveMode::Nbfm(config) => config.channel_cutoff(),
+        ReceiveMode::Am | ReceiveMode::SyncAm => 5000.0,
+        ReceiveMode::Ssb(_) => 4000.0,
+        ReceiveMode::Cw { .. } => 1000.0,
+        ReceiveMode::Wfm => unreachable!("WFM runs in signal_pipeline"),
+    };
+    let channel_taps = rradio_dsp::fir::generate_lowpass_taps(
+        if_fs as f64, channel_cutoff as f64, 255, &rradio_dsp::fir::WindowType::Blackman,
+    );
+    let channel = samples
+        .resample(if_taps, 1, signal_downsample)
+        .resample(channel_taps, 1, AUDIO_DOWNSAMPLE)
+        .dsp_filter(Agc::new(audio_fs, iq_agc_config()));
+
+    let mut audio: Box<dyn Iterator<Item = f32>> = match mode {
+        ReceiveMode::Nbfm(config) => {
+            // Scale so peak deviation reads ±1
+            let demod_gain = audio_fs / (2.0 * std::f32::consts::PI * config.deviation());
+            let mut tone: Option<f32> = None;
+            let mut open = false;
+            let mut audio_samples: u64 = 0;
+            let squelched = config.ctcss_squelch.is_some();
+            Box::new(channel
+                .fm_demodulate_with(settings.discriminator.build())
+                .map(move |s| s * demod_gain)
+                .nbfm_audio(audio_fs, &config)
+                .map(move |sample| {
+                    audio_samples += 1;
+                    if squelched && sample.open != open {
+                        open = sample.open;
+                        if let Some(log) = recording_log.as_ref() {
+                            let label = if open { "squelch open" } else { "squelch closed" };
+                            log.mark_at(audio_samples as f64 / audio_fs as f64, label, None);
+                        }
+                    }
+                    if sample.ctcss_tone != tone {
+                        tone = sample.ctcss_tone;
+                        match tone {
+                            Some(t) => eprintln!("CTCSS {:.1} Hz", t),
+                            None => eprintln!("CTCSS lost"),
+                        }
+                    }
+                    sample.audio
+                }))
+        }
+        ReceiveMode::Am => Box::new(channel.am_demodulate(audio_fs)),
+        ReceiveMode::SyncAm => Box::new(channel.sync_am_demodulate(audio_fs)),
+        ReceiveMode::Ssb(sideband) => Box::new(channel.ssb_demodulate(audio_fs, sideband, SSB_BANDWIDTH)),
+        ReceiveMode::Cw { bfo } => Box::new(channel.cw_demodulate(audio_fs, bfo, CW_BANDWIDTH)),
+        ReceiveMode::Wfm => unreachable!("WFM runs in signal_pipeline"),
+    };
+
+    while !done.load(atomic::Ordering::SeqCst) {
+        let mut batch = match audio_out.get() {
+            Some(mut tok) => { tok.clear(); tok }
+            None => break,
+        };
+        batch.extend((&mut audio).take(4096).map(|s| (s, s)));
+        let finished = batch.is_empty();
+        audio_out.commit(batch);
+        if finished { break; }
+    }
+}
+
+fn compute_pipeline_settings(fs: f32, discriminator: FmDiscriminator) -> SignalPipelineSettings {
     // Total downsample from IQ to wfm_audio stage: must reach 240 kHz
     // wfm_audio runs at fs / iq_downsample / fm_demod_downsample
     // Audio consumer does ÷5 → 48 kHz, so wfm rate must be 240 kHz
@@ -429,15 +528,133 @@ fn compute_pipeline_settings(fs: f32) -> SignalPipelineSettings {
     SignalPipelineSettings {
         iq_downsample,
         fm_demod_downsample: 5,
+        discriminator,
     }
 }
 
-enum IqSource {
+enum SourceConfig {
     Pluto { config: rradio_sdr::pluto::SdrConfig },
     Soapy { config: rradio_sdr::soapy::SoapyConfig },
-    Sigmf { streamer: rradio_sdr::sigmf::SigmfStreamer, tune_offset: f32 },
+    RtlTcp { config: rradio_sdr::rtl_tcp::RtlTcpConfig },
+    /// A recording or pipe, opened already. `name` labels its messages,
+    /// `hw` describes it in SigMF metadata and `encoding` is how its samples
+    /// are stored.
+    File { source: Box<dyn IqSource + Send>, name: &'static str, hw: &'static str, encoding: SigmfDatatype },
+}
+
+impl SourceConfig {
+    fn sigmf(streamer: SigmfStreamer) -> Self {
+        // Real recordings come out of the pipeline as complex samples
+        let encoding = SigmfDatatype { complex: true, ..streamer.datatype() };
+        SourceConfig::File { source: Box::new(streamer), name: "SigMF", hw: "SigMF playback", encoding }
+    }
+
+    fn raw(source: RawIqSource) -> Self {
+        let encoding = SigmfDatatype { complex: true, ..source.datatype() };
+        SourceConfig::File { source: Box::new(source), name: "Raw IQ", hw: "Raw IQ playback", encoding }
+    }
+
+    fn wav(source: WavIqSource) -> Self {
+        let encoding = source.datatype();
+        SourceConfig::File { source: Box::new(source), name: "WAV-IQ", hw: "WAV-IQ playback", encoding }
+    }
+
+    fn sample_rate(&self) -> f32 {
+        match self {
+            SourceConfig::Pluto { config } => config.fs,
+            SourceConfig::Soapy { config } => config.fs,
+            SourceConfig::RtlTcp { config } => config.fs,
+            SourceConfig::File { source, .. } => source.sample_rate(),
+        }
+    }
+
+    /// Frequency in Hz the source starts out tuned to.
+    fn station(&self) -> f64 {
+        match self {
+            SourceConfig::Pluto { config } => config.station as f64,
+            SourceConfig::Soapy { config } => config.station as f64,
+            SourceConfig::RtlTcp { config } => config.station as f64,
+            SourceConfig::File { source, .. } => source.center_frequency(),
+        }
+    }
+
+    /// Hardware description, and the sample encoding the source delivers,
+    /// which recordings use by default.
+    fn recording_format(&self) -> (String, SigmfDatatype) {
+        match self {
+            SourceConfig::Pluto { .. } => ("PlutoSDR".to_string(), SigmfDatatype::CI16_LE),
+            SourceConfig::Soapy { config } => (format!("{} via SoapySDR", soapy_driver(&config.filter)), SigmfDatatype::CF32_LE),
+            SourceConfig::RtlTcp { .. } => ("RTL-SDR via rtl_tcp".to_string(), SigmfDatatype::CU8),
+            SourceConfig::File { hw, encoding, .. } => (hw.to_string(), *encoding),
+        }
+    }
 }
 
+/// The `driver=` value of a SoapySDR device filter, or the whole filter
+/// when it doesn't name one.
+fn soapy_driver(filter: &str) -> &str {
+    filter.split(',')
+        .find_map(|arg| arg.trim().strip_prefix("driver="))
+        .unwrap_or(filter)
+}
+
+enum ReceiveMode {
+    Wfm,
+    Nbfm(NarrowbandFmConfig),
+    Am,
+    SyncAm,
+    Ssb(Sideband),
+    Cw { bfo: f32 },
+}
+
+impl ReceiveMode {
+    fn name(&self) -> &'static str {
+        match self {
+            ReceiveMode::Wfm => "wfm",
+            ReceiveMode::Nbfm(_) => "nbfm",
+            ReceiveMode::Am => "am",
+            ReceiveMode::SyncAm => "sam",
+            ReceiveMode::Ssb(Sideband::Upper) => "usb",
+            ReceiveMode::Ssb(Sideband::Lower) => "lsb",
+            ReceiveMode::Cw { .. } => "cw",
+        }
+    }
+
+    /// `correction` for this mode. The DC notch would take out an AM
+    /// carrier, or a zero-beat CW signal, tuned to 0 Hz.
+    fn correction(&self, correction: IqCorrectionConfig) -> IqCorrectionConfig {
+        match self {
+            ReceiveMode::Am | ReceiveMode::SyncAm | ReceiveMode::Cw { .. } =>
+                IqCorrectionConfig { dc_block: false, ..correction },
+            _ => correction,
+        }
+    }
+}
+
+/// IQ normalization: slow enough to leave AM modulation alone, it just
+/// removes the difference in scale between sources and gain settings.
+fn iq_agc_config() -> AgcConfig {
+    AgcConfig { target: 0.5, attack: 0.05, decay: 0.5, ..Default::default() }
+}
+
+fn audio_agc_config(mode: &ReceiveMode) -> AgcConfig {
+    match mode {
+        // Broadcast audio is already levelled: only limit peaks
+        ReceiveMode::Wfm => AgcConfig {
+            target: 0.9, attack: 0.001, decay: 0.5, hang: 0.2,
+            detector: AgcDetector::Peak, max_gain: 1.0,
+        },
+        // Hang AGC so gain doesn't pump between words or CW elements
+        _ => AgcConfig {
+            target: 0.5, attack: 0.002, decay: 0.2, hang: 0.3,
+            detector: AgcDetector::Peak, max_gain: 100.0,
+        },
+    }
+}
+
+const SSB_BANDWIDTH: f32 = 2800.0;
+const CW_BANDWIDTH: f32 = 500.0;
+
 enum AudioOutput {
     Playback,
     Wav(String),
@@ -469,8 +686,11 @@ fn get_ratio(old: f32, new: f32) -> (usize, usize) {
     (up as usize, down as usize)
 }
 
-fn rds_pipeline(done: &atomic::AtomicBool, rds_rx: rradio_dsp::buffer::RecvBuf<Vec<f32>>, wfm_fs: f32, debug: bool, metrics: bool) {
-    let iterable = rradio_dsp::buffer::RecvBufIter::new(rds_rx);
+fn rds_pipeline(done: &atomic::AtomicBool, rds_rx: rradio_dsp::buffer::RecvBuf<Vec<Complex32>>, wfm_fs: f32, debug: bool, metrics: bool, rbds: bool, recording_log: Option<RecordingLog>) {
+    // Input position, to place annotations on the recording's timeline
+    let consumed = std::cell::Cell::new(0u64);
+    let iterable = rradio_dsp::buffer::RecvBufIter::new(rds_rx)
+        .inspect(|_| consumed.set(consumed.get() + 1));
 
     // Stage 1: resample 240k → 171k (same as v4)
     let stage1_target_fs: f32 = 57e3 * 3.0;
@@ -486,9 +706,12 @@ fn rds_pipeline(done: &atomic::AtomicBool, rds_rx: rradio_dsp::buffer::RecvBuf<V
 
     // Combined biphase + block sync (dual-phase searching, frozen polarity when locked)
     let mut chip_sync = chip_sync::ChipSync::new(2, 12, debug);
-    let mut decoder = rds_decoder::RdsDecoder::new();
+    let mut decoder = rds_decoder::RdsDecoder::new(rbds);
     let mut display = rds_decoder::RdsDisplay::new();
     let start_time = std::time::Instant::now();
+    let mut annotated_pi: u16 = 0;
+    let mut annotated_ps = String::new();
+    let mut locked = false;
 
     for chip in &mut chips {
         if done.load(atomic::Ordering::SeqCst) {
@@ -500,6 +723,17 @@ fn rds_pipeline(done: &atomic::AtomicBool, rds_rx: rradio_dsp::buffer::RecvBuf<V
                 chip_sync::SyncEvent::Group(group) => {
                     let state = decoder.process(&group);
                     let elapsed = start_time.elapsed().as_secs_f64();
+                    if let Some(log) = recording_log.as_ref() {
+                        let seconds = consumed.get() as f64 / wfm_fs as f64;
+                        if state.pi_code != 0 && state.pi_code != annotated_pi {
+                            annotated_pi = state.pi_code;
+                            log.mark_at(seconds, &format!("RDS PI {:04X}", state.pi_code), state.call_sign.clone());
+                        }
+                        if decoder.ps_complete() && state.ps != annotated_ps {
+                            annotated_ps = state.ps.clone();
+                            log.mark_at(seconds, &format!("RDS PS {}", state.ps), None);
+                        }
+                    }
                     if metrics {
                         let pi_str = if group.pi_code != 0 { format!("{:04X}", group.pi_code) } else { "0000".to_string() };
                         eprintln!("RDSMETRIC {{\"t\":{:.3},\"bler\":{:.4},\"groups\":{},\"pi\":\"{}\"}}", elapsed, group.rolling_bler, state.groups_decoded, pi_str);
@@ -513,9 +747,14 @@ fn rds_pipeline(done: &atomic::AtomicBool, rds_rx: rradio_dsp::buffer::RecvBuf<V
                     }
                 }
                 chip_sync::SyncEvent::Locked => {
+                    locked = true;
                     if !metrics && !debug { display.set_synced(true); display.render(&decoder.display_state()); }
                 }
                 chip_sync::SyncEvent::LostSync | chip_sync::SyncEvent::Searching => {
+                    if let (true, Some(log)) = (locked, recording_log.as_ref()) {
+                        log.mark_at(consumed.get() as f64 / wfm_fs as f64, "RDS sync lost", None);
+                    }
+                    locked = false;
                     if !metrics && !debug { display.set_synced(false); display.render(&decoder.display_state()); }
                 }
             }
@@ -532,43 +771,63 @@ fn rds_pipeline(done: &atomic::AtomicBool, rds_rx: rradio_dsp::buffer::RecvBuf<V
 
 const AUDIO_DOWNSAMPLE: usize = 5;
 
-fn run(iq_source: IqSource, audio_output: AudioOutput, done_sig: Arc<atomic::AtomicBool>, obs_settings: AudioPipelineObservationSettings, rds_debug: bool, rds_metrics: bool, record_path: Option<String>, mpx_path: Option<String>) {
-    let fs = match &iq_source {
-        IqSource::Pluto { config } => config.fs,
-        IqSource::Soapy { config } => config.fs,
-        IqSource::Sigmf { streamer, .. } => streamer.sample_rate(),
-    };
-    let station_freq = match &iq_source {
-        IqSource::Pluto { config } => config.station as f64,
-        IqSource::Soapy { config } => config.station as f64,
-        IqSource::Sigmf { .. } => 0.0,
-    };
+/// Tune to the nearest channel of the region's raster, warning if that moves the station.
+fn snap_to_channel(region: &RegionConfig, station: f32) -> f32 {
+    let channel = region.nearest_channel(station);
+    if (channel - station).abs() > 1.0 {
+        eprintln!("{:.2} MHz is not a channel in this region, tuning {:.2} MHz", station / 1e6, channel / 1e6);
+    }
+    channel
+}
+
+fn run(iq_source: SourceConfig, audio_output: AudioOutput, done_sig: Arc<atomic::AtomicBool>, obs_settings: AudioPipelineObservationSettings, rds_debug: bool, rds_metrics: bool, rds_pilot_ref: bool, record: Option<RecordSettings>, mpx_path: Option<String>, mode: ReceiveMode, discriminator: FmDiscriminator, region: RegionConfig, stereo_blend: StereoBlendConfig, afc: Option<AfcConfig>) {
+    let fs = iq_source.sample_rate();
+    let station_freq = iq_source.station();
 
-    let settings = compute_pipeline_settings(fs);
+    let settings = compute_pipeline_settings(fs, discriminator);
     let wfm_fs = fs / (settings.iq_downsample as f32) / (settings.fm_demod_downsample as f32);
     eprintln!("Pipeline: fs={} Hz, iq_ds={}, fm_ds={}, wfm_fs={} Hz",
         fs, settings.iq_downsample, settings.fm_demod_downsample, wfm_fs);
-    eprintln!("  Audio: wfm @ {} Hz → ÷{} → {} Hz stereo",
-        wfm_fs, AUDIO_DOWNSAMPLE, wfm_fs / AUDIO_DOWNSAMPLE as f32);
-    eprintln!("  RDS:   v5 pipeline (internal resample to 14250 Hz)");
+    match &mode {
+        ReceiveMode::Wfm => {
+            eprintln!("  Audio: wfm @ {} Hz → ÷{} → {} Hz stereo",
+                wfm_fs, AUDIO_DOWNSAMPLE, wfm_fs / AUDIO_DOWNSAMPLE as f32);
+            eprintln!("  RDS:   v5 pipeline (internal resample to 14250 Hz)");
+        }
+        narrowband => {
+            eprintln!("  Audio: {} demod @ {} Hz mono", narrowband.name(), wfm_fs / AUDIO_DOWNSAMPLE as f32);
+        }
+    }
 
     // Buffer pairs
     let (iq_tx, iq_rx) = rradio_dsp::buffer::buf_pair::<Vec<Complex32>>(8);
     let (audio_tx, audio_rx) = rradio_dsp::buffer::buf_pair::<Vec<(f32, f32)>>(8);
-    let (rds_tx, rds_rx) = rradio_dsp::buffer::buf_pair::<Vec<f32>>(8);
+    let (rds_tx, rds_rx) = rradio_dsp::buffer::buf_pair::<Vec<Complex32>>(8);
 
     // Optional IQ recording: splitter tees raw IQ to both signal pipeline and recorder
-    let (pipeline_rx, record_thread) = if let Some(ref path) = record_path {
+    let (pipeline_rx, record_thread, recording_log) = if let Some(record) = record {
         let (pipeline_tx, pipeline_rx) = rradio_dsp::buffer::buf_pair::<Vec<Complex32>>(8);
         let (record_tx, record_rx) = rradio_dsp::buffer::buf_pair::<Vec<Complex32>>(4);
 
-        let hw = match &iq_source {
-            IqSource::Pluto { .. } => "PlutoSDR",
-            IqSource::Soapy { .. } => "RTL-SDR via SoapySDR",
-            IqSource::Sigmf { .. } => "SigMF playback",
-        };
-        let mut writer = rradio_sdr::sigmf::SigmfWriter::new(path, fs as f64, station_freq, hw)
-            .expect("Failed to create SigMF recording");
+        // Record in the source's sample encoding unless told otherwise. The
+        // samples come after DC blocking and IQ balancing, so they are the
+        // corrected stream re-quantized, not what the ADC delivered; turn
+        // the corrections off for an untouched capture.
+        let (hw, encoding) = iq_source.recording_format();
+        let datatype = record.format.unwrap_or(encoding);
+        match &record.mode {
+            RecordMode::PreTrigger { seconds, triggers } =>
+                eprintln!("Holding the last {}s of {} for {} on {:?}", seconds, datatype.name(), record.path, triggers),
+            RecordMode::Continuous(_) => eprintln!("Recording {} to {}", datatype.name(), record.path),
+        }
+        let mut recorder = Recorder::new(RecorderConfig {
+            base_path: record.path,
+            sample_rate: fs as f64,
+            frequency: station_freq,
+            hw,
+            datatype,
+        }, record.mode);
+        let recording_log = recorder.log();
 
         // Splitter thread: reads IQ, writes to pipeline + recorder
         let done_ref = done_sig.clone();
@@ -604,7 +863,6 @@ fn run(iq_source: IqSource, audio_output: AudioOutput, done_sig: Arc<atomic::Ato
 
         // Recorder thread: writes IQ to disk
         let done_ref = done_sig.clone();
-        let rec_path = path.clone();
         let recorder = std::thread::spawn(move || {
             let iter = rradio_dsp::buffer::RecvBufIter::new(record_rx);
             let mut batch = Vec::with_capacity(8192);
@@ -612,7 +870,7 @@ fn run(iq_source: IqSource, audio_output: AudioOutput, done_sig: Arc<atomic::Ato
                 if done_ref.load(atomic::Ordering::SeqCst) { break; }
                 batch.push(sample);
                 if batch.len() >= 8192 {
-                    if let Err(e) = writer.write_samples(&batch) {
+                    if let Err(e) = recorder.write_samples(&batch) {
                         eprintln!("SigMF write error: {}", e);
                         break;
                     }
@@ -620,9 +878,9 @@ fn run(iq_source: IqSource, audio_output: AudioOutput, done_sig: Arc<atomic::Ato
                 }
             }
             if !batch.is_empty() {
-                let _ = writer.write_samples(&batch);
+                let _ = recorder.write_samples(&batch);
             }
-            if let Err(e) = writer.finalize() {
+            if let Err(e) = recorder.finalize() {
                 eprintln!("SigMF finalize error: {}", e);
             }
         });
@@ -634,36 +892,64 @@ fn run(iq_source: IqSource, audio_output: AudioOutput, done_sig: Arc<atomic::Ato
             recorder.join().unwrap();
         });
 
-        (pipeline_rx, Some(combined))
+        (pipeline_rx, Some(combined), Some(recording_log))
     } else {
-        (iq_rx, None)
+        (iq_rx, None, None)
     };
 
     // Thread 1: IQ source
     let done_ref = done_sig.clone();
+    let source_log = recording_log.clone();
     let iq_thread = std::thread::spawn(move || {
-        match iq_source {
-            IqSource::Pluto { config } => buffer_pluto(&done_ref, &config, iq_tx),
-            IqSource::Soapy { config } => buffer_soapy(&done_ref, &config, iq_tx),
-            IqSource::Sigmf { streamer, tune_offset } => buffer_sigmf(&done_ref, streamer, iq_tx, tune_offset, fs),
-        }
+        buffer_source(&done_ref, iq_source, iq_tx, source_log);
     });
 
-    // Thread 2: Signal pipeline (FM demod + stereo/RDS extraction → tee)
-    let done_ref = done_sig.clone();
-    let signal_thread = std::thread::spawn(move || {
-        signal_pipeline(&done_ref, fs, pipeline_rx, audio_tx, rds_tx, settings, obs_settings, mpx_path);
-    });
+    let rbds = region.rbds;
 
-    // Thread 3: RDS consumer
-    let done_ref = done_sig.clone();
-    let rds_thread = std::thread::spawn(move || {
-        rds_pipeline(&done_ref, rds_rx, wfm_fs, rds_debug, rds_metrics);
-    });
+    // Narrowband modes already produce audio at 48 kHz
+    let audio_downsample = match mode {
+        ReceiveMode::Wfm => AUDIO_DOWNSAMPLE,
+        _ => 1,
+    };
+    let audio_agc = audio_agc_config(&mode);
+    // Both paths deliver audio at this rate
+    let audio_out_fs = wfm_fs / AUDIO_DOWNSAMPLE as f32;
+
+    let (signal_thread, rds_thread) = match mode {
+        ReceiveMode::Wfm => {
+            // Thread 2: Signal pipeline (FM demod + stereo/RDS extraction → tee)
+            let done_ref = done_sig.clone();
+            let signal_log = recording_log.clone();
+            let signal_thread = std::thread::spawn(move || {
+                signal_pipeline(&done_ref, fs, pipeline_rx, audio_tx, rds_tx, rds_pilot_ref, settings, obs_settings, mpx_path, region, stereo_blend, afc, station_freq, signal_log);
+            });
+
+            // Thread 3: RDS consumer
+            let done_ref = done_sig.clone();
+            let rds_thread = std::thread::spawn(move || {
+                rds_pipeline(&done_ref, rds_rx, wfm_fs, rds_debug, rds_metrics, rbds, recording_log);
+            });
+            (signal_thread, Some(rds_thread))
+        }
+        narrowband => {
+            // Thread 2: narrowband pipeline, no RDS
+            let done_ref = done_sig.clone();
+            let signal_thread = std::thread::spawn(move || {
+                narrowband_pipeline(&done_ref, fs, pipeline_rx, audio_tx, settings, narrowband, recording_log);
+            });
+            (signal_thread, None)
+        }
+    };
 
     // Main thread: Audio consumer (downsample + interleave + output)
+    // One gain for both channels: level the pair as a single complex sample
+    let mut audio_agc: Agc<Complex32> = Agc::new(audio_out_fs, audio_agc);
     let audio_iter = rradio_dsp::buffer::RecvBufIter::new(audio_rx)
-        .downsample(AUDIO_DOWNSAMPLE)
+        .downsample(audio_downsample)
+        .map(move |(l, r)| {
+            let gain = audio_agc.update(Complex32::new(l, r));
+            (l * gain, r * gain)
+        })
         .interleave();
 
     match audio_output {
@@ -678,12 +964,200 @@ fn run(iq_source: IqSource, audio_output: AudioOutput, done_sig: Arc<atomic::Ato
 
     iq_thread.join().unwrap();
     signal_thread.join().unwrap();
-    rds_thread.join().unwrap();
+    if let Some(rt) = rds_thread {
+        rt.join().unwrap();
+    }
     if let Some(rt) = record_thread {
         rt.join().unwrap();
     }
 }
 
+/// Read `gain <setting>` lines from stdin and hand them to the streaming thread.
+fn spawn_gain_console(control: GainControl) {
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("gain"), Some(setting)) => {
+                    if let Err(e) = control.update(|gain| gain.apply_setting(setting)) {
+                        eprintln!("{}", e);
+                    }
+                }
+                (None, _) => {}
+                _ => eprintln!("Commands: gain <db|manual|slow_attack|fast_attack|ELEMENT=db>"),
+            }
+        }
+    });
+}
+
+const PLUTO_URI: &str = "ip:pluto.local";
+
+/// Oscillator correction for `device`: an explicit `--ppm`, else its saved profile.
+fn device_ppm(device: &str, ppm: Option<f64>) -> f64 {
+    if let Some(ppm) = ppm {
+        return ppm;
+    }
+    let Some(path) = Profiles::default_path() else { return 0.0 };
+    match Profiles::load(&path) {
+        Ok(profiles) => match profiles.get(device) {
+            Some(profile) => {
+                eprintln!("Applying {:+.2} ppm correction for {} from {}", profile.ppm, device, path.display());
+                profile.ppm
+            }
+            None => 0.0,
+        },
+        Err(e) => {
+            eprintln!("Ignoring device profiles: {}", e);
+            0.0
+        }
+    }
+}
+
+// Discriminator rate for calibration, the same as the WFM stage
+const CALIBRATION_FS: f32 = 240000.0;
+
+/// Measure the oscillator error of an SDR against a broadcast station and
+/// save it to the device's profile.
+fn calibrate(iq_source: SourceConfig, device: &str, reference: CalibrationReference, seconds: f64, done_sig: Arc<atomic::AtomicBool>) {
+    let (fs, station) = (iq_source.sample_rate(), iq_source.station());
+    eprintln!("Calibrating {} against {:.2} MHz for {:.0}s...", device, station / 1e6, seconds);
+
+    let (iq_tx, iq_rx) = rradio_dsp::buffer::buf_pair::<Vec<Complex32>>(8);
+    let done_ref = done_sig.clone();
+    let iq_thread = std::thread::spawn(move || {
+        buffer_source(&done_ref, iq_source, iq_tx, None);
+    });
+
+    // Wide enough for the station's full deviation, narrow enough to keep neighbours out
+    let decimation = (fs / CALIBRATION_FS).round() as usize;
+    let taps = rradio_dsp::fir::generate_lowpass_taps(
+        fs as f64, 100_000.0, 16 * decimation + 1, &rradio_dsp::fir::WindowType::Blackman,
+    );
+    let mut calibrator = Calibrator::new(fs / decimation as f32);
+    rradio_dsp::buffer::RecvBufIter::new(iq_rx)
+        .take((seconds * fs as f64) as usize)
+        .resample(taps, 1, decimation)
+        .fm_demodulate_with(ConjugateProductDiscriminator::new())
+        .for_each(|s| calibrator.process(s));
+    done_sig.store(true, atomic::Ordering::SeqCst);
+    iq_thread.join().unwrap();
+
+    if calibrator.duration() < 1.0 {
+        eprintln!("Calibration interrupted after {:.1}s, profile not updated", calibrator.duration());
+        std::process::exit(1);
+    }
+    let result = calibrator.finish(station);
+    eprintln!("Carrier: {:+.1} Hz offset, {:+.2} ppm", result.carrier_offset, result.carrier_ppm);
+    match result.pilot_ppm {
+        Some(pilot_ppm) => eprintln!("Pilot:   {:.1} kHz deviation, {:+.2} ppm", result.pilot_deviation / 1e3, pilot_ppm),
+        None => eprintln!("Pilot:   not found"),
+    }
+
+    let Some(ppm) = result.ppm(reference) else {
+        eprintln!("No {:?} reference to calibrate against, profile not updated", reference);
+        std::process::exit(1);
+    };
+    let Some(path) = Profiles::default_path() else {
+        eprintln!("No config directory for device profiles; pass --ppm {:.2} instead", ppm);
+        std::process::exit(1);
+    };
+    let mut profiles = Profiles::load(&path).unwrap_or_else(|e| panic!("{}", e));
+    profiles.entry(device).ppm = ppm;
+    profiles.save(&path).unwrap_or_else(|e| panic!("{}", e));
+    eprintln!("Saved {:+.2} ppm for {} to {}", ppm, device, path.display());
+}
+
+fn is_archive(path: &str) -> bool {
+    std::path::Path::new(path).extension().is_some_and(|ext| ext == ARCHIVE_EXTENSION)
+}
+
+/// Open a recording by its `.sigmf-meta`, or one from a `.sigmf` archive or
+/// `.sigmf-collection`: `recording` if given, otherwise the first.
+fn open_sigmf(path: &str, recording: Option<&str>, mapped: bool) -> Result<SigmfStreamer, SigmfError> {
+    if path.ends_with(".sigmf-collection") {
+        SigmfCollection::open(path)?.open_recording(recording, mapped)
+    } else if is_archive(path) {
+        SigmfArchive::open(path)?.open_recording(recording, mapped)
+    } else if mapped {
+        SigmfStreamer::new_mapped(path)
+    } else {
+        SigmfStreamer::new(path)
+    }
+}
+
+/// Summarise every recording in an archive or collection, or the single
+/// recording at a `.sigmf-meta` path.
+fn list_sigmf(path: &str) -> Result<(), SigmfError> {
+    let report = |name: &str, recording: Result<SigmfStreamer, SigmfError>| {
+        println!("{}", name);
+        recording.map(|r| print!("{}", report_sigmf(&r)))
+    };
+    if path.ends_with(".sigmf-collection") {
+        let collection = SigmfCollection::open(path)?;
+        if let Some(description) = &collection.description {
+            println!("{}", description);
+        }
+        for (stream, recording) in collection.streams.iter().zip(collection.recordings()) {
+            report(&stream.name, recording)?;
+        }
+    } else if is_archive(path) {
+        let archive = SigmfArchive::open(path)?;
+        if let Some(description) = archive.collection().and_then(|c| c.description.as_ref()) {
+            println!("{}", description);
+        }
+        for (name, recording) in archive.names().into_iter().zip(archive.recordings()) {
+            report(name, recording)?;
+        }
+    } else {
+        report(path, SigmfStreamer::new(path))?;
+    }
+    Ok(())
+}
+
+/// Describe a recording before playing it.
+fn report_sigmf(streamer: &rradio_sdr::sigmf::SigmfStreamer) -> String {
+    let fs = streamer.sample_rate() as f64;
+    let mut report = format!("SigMF: {} at {:.0} S/s, {:.1}s, {} capture(s), {} annotation(s)\n",
+        streamer.datatype().name(), fs, streamer.total_samples() as f64 / fs,
+        streamer.captures().len(), streamer.annotations().len());
+    if let Some(capture) = streamer.current_capture() {
+        report += &format!("SigMF: starting at {:.1}s in the capture at {:.3} MHz{}\n",
+            streamer.position() as f64 / fs,
+            capture.frequency.unwrap_or(0.0) / 1e6,
+            capture.datetime.as_deref().map(|t| format!(" from {}", t)).unwrap_or_default());
+    }
+    for annotation in streamer.annotations() {
+        if let Some(label) = annotation.label.as_deref().or(annotation.comment.as_deref()) {
+            report += &format!("SigMF: {:8.1}s  {}\n", annotation.sample_start as f64 / fs, label);
+        }
+    }
+    report
+}
+
+const DEFAULT_LISTEN: &str = "0.0.0.0:1234";
+
+/// Share a source with rtl_tcp clients until interrupted.
+fn serve(iq_source: SourceConfig, listen: &str, gain: GainControl, done_sig: Arc<atomic::AtomicBool>) {
+    let listener = std::net::TcpListener::bind(listen).unwrap_or_else(|e| panic!("Cannot listen on {}: {}", listen, e));
+    eprintln!("Serving rtl_tcp on {}", listen);
+    let done = done_sig.as_ref();
+    let result = match iq_source {
+        SourceConfig::Pluto { config } => rtl_tcp_server::serve(&listener, "SDR", config.fs,
+            || PlutoSource::connect(&config), Some(&gain), done),
+        SourceConfig::Soapy { config } => rtl_tcp_server::serve(&listener, "SoapySDR", config.fs,
+            || SoapySource::connect(&config), Some(&gain), done),
+        SourceConfig::RtlTcp { config } => rtl_tcp_server::serve(&listener, "rtl_tcp", config.fs,
+            || RtlTcpSource::connect(&config), Some(&gain), done),
+        SourceConfig::File { source, name, .. } => rtl_tcp_server::serve(&listener, name, source.sample_rate(),
+            connect_once(source), None, done),
+    };
+    if let Err(e) = result {
+        eprintln!("rtl_tcp server: {}", e);
+        std::process::exit(1);
+    }
+}
+
 fn main() {
     let args: Vec<String> = std::env::args().skip(1).collect();
 
@@ -705,9 +1179,34 @@ fn main() {
     let mut wav_path: Option<String> = None;
     let mut rds_debug = false;
     let mut rds_metrics = false;
+    let mut rds_pilot_ref = false;
     let mut record_path: Option<String> = None;
     let mut mpx_path: Option<String> = None;
     let mut duration_secs: Option<f64> = None;
+    let mut stereo_blend = StereoBlendConfig::default();
+    let mut region = RegionConfig::default();
+    let mut region_given = false;
+    let mut deemphasis: Option<Option<f32>> = None;
+    let mut mode_name = "wfm".to_string();
+    let mut nbfm_config = NarrowbandFmConfig::default();
+    let mut bfo: f32 = 700.0;
+    let mut discriminator = FmDiscriminator::Polar;
+    let mut correction = IqCorrectionConfig::default();
+    let mut afc = Some(AfcConfig::default());
+    let mut ppm: Option<f64> = None;
+    let mut gain = GainConfig::default();
+    let mut rtl_agc = false;
+    let mut calibration_reference = CalibrationReference::Pilot;
+    let mut listen = DEFAULT_LISTEN.to_string();
+    let mut raw_format = RawIqFormat::default();
+    let mut start_secs: Option<f64> = None;
+    let mut mmap = false;
+    let mut record_format: Option<SigmfDatatype> = None;
+    let mut rotation = RotationLimits::default();
+    let mut pre_trigger_secs: Option<f64> = None;
+    let mut record_triggers: Vec<String> = Vec::new();
+    let mut recording: Option<String> = None;
+    let mut description: Option<String> = None;
     let mut i = 0;
     while i < args.len() {
         if args[i] == "--wav" {
@@ -719,6 +1218,9 @@ fn main() {
         } else if args[i] == "--rds-metrics" {
             rds_metrics = true;
             i += 1;
+        } else if args[i] == "--rds-pilot-ref" {
+            rds_pilot_ref = true;
+            i += 1;
         } else if args[i] == "--record" {
             record_path = Some(args.get(i + 1).expect("Usage: --record <path>").clone());
             i += 2;
@@ -729,12 +1231,176 @@ fn main() {
             duration_secs = Some(args.get(i + 1).expect("Usage: --duration <seconds>")
                 .parse().expect("--duration must be a number"));
             i += 2;
+        } else if args[i] == "--stereo-mode" {
+            stereo_blend.mode = match args.get(i + 1).map(|s| s.as_str()) {
+                Some("auto") => StereoMode::Auto,
+                Some("stereo") => StereoMode::Stereo,
+                Some("mono") => StereoMode::Mono,
+                _ => panic!("Usage: --stereo-mode <auto|stereo|mono>"),
+            };
+            i += 2;
+        } else if args[i] == "--region" {
+            let name = args.get(i + 1).expect("Usage: --region <americas|europe|japan>");
+            region = RegionConfig::preset(name.parse::<Region>().unwrap_or_else(|e| panic!("{}", e)));
+            region_given = true;
+            i += 2;
+        } else if args[i] == "--deemphasis" {
+            deemphasis = Some(args.get(i + 1).and_then(|s| region::parse_deemphasis(s))
+                .expect("Usage: --deemphasis <75|50|none>"));
+            i += 2;
+        } else if args[i] == "--mode" {
+            mode_name = args.get(i + 1).expect("Usage: --mode <wfm|nbfm|am|sam|usb|lsb|cw>").clone();
+            i += 2;
+        } else if args[i] == "--fm-discriminator" {
+            discriminator = match args.get(i + 1).map(|s| s.as_str()) {
+                Some("polar") => FmDiscriminator::Polar,
+                Some("conj") => FmDiscriminator::ConjugateProduct,
+                Some("fast-atan") => FmDiscriminator::FastAtan,
+                Some("quadrature") => FmDiscriminator::Quadrature,
+                _ => panic!("Usage: --fm-discriminator <polar|conj|fast-atan|quadrature>"),
+            };
+            i += 2;
+        } else if args[i] == "--bfo" {
+            bfo = args.get(i + 1).expect("Usage: --bfo <hz>")
+                .parse().expect("--bfo must be a frequency in Hz");
+            i += 2;
+        } else if args[i] == "--channel-khz" {
+            nbfm_config.channel_spacing = match args.get(i + 1).map(|s| s.as_str()) {
+                Some("12.5") => 12500.0,
+                Some("25") => 25000.0,
+                _ => panic!("Usage: --channel-khz <12.5|25>"),
+            };
+            i += 2;
+        } else if args[i] == "--nbfm-deemphasis" {
+            nbfm_config.deemphasis = true;
+            i += 1;
+        } else if args[i] == "--ctcss" {
+            nbfm_config.ctcss_squelch = Some(args.get(i + 1).expect("Usage: --ctcss <tone_hz>")
+                .parse().expect("--ctcss must be a tone frequency in Hz"));
+            i += 2;
+        } else if args[i] == "--no-dc-block" {
+            correction.dc_block = false;
+            i += 1;
+        } else if args[i] == "--no-iq-balance" {
+            correction.iq_balance = false;
+            i += 1;
+        } else if args[i] == "--gain" {
+            let setting = args.get(i + 1).expect("Usage: --gain <db|manual|slow_attack|fast_attack|ELEMENT=db>");
+            gain.apply_setting(setting).unwrap_or_else(|e| panic!("{}", e));
+            i += 2;
+        } else if args[i] == "--rtl-agc" {
+            rtl_agc = true;
+            i += 1;
+        } else if args[i] == "--ppm" {
+            ppm = Some(args.get(i + 1).expect("Usage: --ppm <correction>")
+                .parse().expect("--ppm must be a number"));
+            i += 2;
+        } else if args[i] == "--reference" {
+            let name = args.get(i + 1).expect("Usage: --reference <pilot|carrier>");
+            calibration_reference = name.parse().unwrap_or_else(|e| panic!("{}", e));
+            i += 2;
+        } else if args[i] == "--format" {
+            let name = args.get(i + 1).expect("--format requires <cu8|cs8|cs16|cs32|cf32>");
+            raw_format.datatype = Some(SigmfDatatype::from_extension(name).unwrap_or_else(|| panic!("Unknown IQ format '{}'", name)));
+            i += 2;
+        } else if args[i] == "--rate" {
+            let rate: f32 = args.get(i + 1).and_then(|s| s.parse().ok()).expect("--rate requires <samples_per_second>");
+            raw_format.sample_rate = Some(rate);
+            i += 2;
+        } else if args[i] == "--center" {
+            let mhz: f64 = args.get(i + 1).and_then(|s| s.parse().ok()).expect("--center requires <mhz>");
+            raw_format.frequency = Some(mhz * 1e6);
+            i += 2;
+        } else if args[i] == "--record-format" {
+            let name = args.get(i + 1).expect("--record-format requires <cu8|ci16_le|cf32_le|...>");
+            record_format = Some(SigmfDatatype::parse(name).ok().or_else(|| SigmfDatatype::from_extension(name))
+                .unwrap_or_else(|| panic!("Unknown IQ format '{}'", name)));
+            i += 2;
+        } else if args[i] == "--record-max-seconds" {
+            let secs: f64 = args.get(i + 1).and_then(|s| s.parse().ok()).expect("--record-max-seconds requires <seconds>");
+            rotation.max_seconds = Some(secs);
+            i += 2;
+        } else if args[i] == "--record-max-mb" {
+            let mb: f64 = args.get(i + 1).and_then(|s| s.parse().ok()).expect("--record-max-mb requires <megabytes>");
+            rotation.max_bytes = Some((mb * 1e6) as u64);
+            i += 2;
+        } else if args[i] == "--record-last" {
+            let secs: f64 = args.get(i + 1).and_then(|s| s.parse().ok()).expect("--record-last requires <seconds>");
+            pre_trigger_secs = Some(secs);
+            i += 2;
+        } else if args[i] == "--record-trigger" {
+            let name = args.get(i + 1).expect("--record-trigger requires <sync|pi|squelch|label>");
+            record_triggers.push(trigger_label(name));
+            i += 2;
+        } else if args[i] == "--recording" {
+            recording = Some(args.get(i + 1).expect("--recording requires <name>").clone());
+            i += 2;
+        } else if args[i] == "--description" {
+            description = Some(args.get(i + 1).expect("--description requires <text>").clone());
+            i += 2;
+        } else if args[i] == "--mmap" {
+            mmap = true;
+            i += 1;
+        } else if args[i] == "--start" {
+            let secs: f64 = args.get(i + 1).and_then(|s| s.parse().ok()).expect("--start requires <seconds>");
+            start_secs = Some(secs);
+            i += 2;
+        } else if args[i] == "--listen" {
+            listen = args.get(i + 1).expect("--listen requires <host:port>").clone();
+            i += 2;
+        } else if args[i] == "--no-afc" {
+            afc = None;
+            i += 1;
+        } else if args[i] == "--no-high-blend" {
+            stereo_blend.high_blend_cutoff = None;
+            i += 1;
         } else {
             positional.push(args[i].clone());
             i += 1;
         }
     }
 
+    // An explicit de-emphasis overrides the region preset regardless of flag order
+    if let Some(tau) = deemphasis {
+        region.deemphasis = tau;
+    }
+
+    if pre_trigger_secs.is_none() && !record_triggers.is_empty() {
+        panic!("--record-trigger only applies with --record-last");
+    }
+    if pre_trigger_secs.is_some() && rotation != RotationLimits::default() {
+        panic!("--record-max-seconds and --record-max-mb do not apply with --record-last");
+    }
+
+    let record = record_path.map(|path| RecordSettings {
+        path,
+        format: record_format,
+        mode: match pre_trigger_secs {
+            Some(seconds) => RecordMode::PreTrigger {
+                seconds,
+                triggers: if record_triggers.is_empty() {
+                    ["sync", "pi", "squelch"].iter().map(|name| trigger_label(name)).collect()
+                } else {
+                    record_triggers
+                },
+            },
+            None => RecordMode::Continuous(rotation),
+        },
+    });
+
+    let mode = match mode_name.as_str() {
+        "wfm" => ReceiveMode::Wfm,
+        "nbfm" => ReceiveMode::Nbfm(nbfm_config),
+        "am" => ReceiveMode::Am,
+        "sam" => ReceiveMode::SyncAm,
+        "usb" => ReceiveMode::Ssb(Sideband::Upper),
+        "lsb" => ReceiveMode::Ssb(Sideband::Lower),
+        "cw" => ReceiveMode::Cw { bfo },
+        _ => panic!("Usage: --mode <wfm|nbfm|am|sam|usb|lsb|cw>"),
+    };
+
+    let correction = mode.correction(correction);
+
     // Duration timer: spawn a thread that sets done after the specified time
     if let Some(secs) = duration_secs {
         let done_ref = done_sig.clone();
@@ -750,17 +1416,54 @@ fn main() {
         None => AudioOutput::Playback,
     };
 
+    let gain_control = GainControl::new(gain);
+    // Only a region asked for on the command line moves the station onto its raster
+    let snap_station = region_given && matches!(mode, ReceiveMode::Wfm);
+
     let mut pos = positional.iter().map(|s| s.as_str());
     match pos.next() {
         Some("sigmf") => {
-            let path = pos.next().expect("Usage: rradio sigmf <path> [tune_offset_khz]");
+            let path = pos.next().expect("Usage: rradio sigmf <path> [tune_offset_khz] [--start <seconds>] [--mmap] [--recording <name>]");
             let tune_offset: f32 = pos.next()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(0.0)
                 * 1e3;
-            let streamer = rradio_sdr::sigmf::SigmfStreamer::new(path).expect("Failed to open SigMF file");
-            let source = IqSource::Sigmf { streamer, tune_offset };
-            run(source, audio_output, done_sig, obs_settings, rds_debug, rds_metrics, record_path, mpx_path.clone());
+            let mut streamer = open_sigmf(path, recording.as_deref(), mmap).expect("Failed to open SigMF file");
+            if let Some(seconds) = start_secs {
+                streamer.seek_to_time(seconds).unwrap_or_else(|e| panic!("Cannot start at {}s: {}", seconds, e));
+            }
+            eprint!("{}", report_sigmf(&streamer));
+            // A positive offset shifts the spectrum up, tuning a station that far below centre
+            let tuned = streamer.center_frequency() - tune_offset as f64;
+            streamer.retune(tuned).unwrap_or_else(|e| panic!("Cannot tune offset {} kHz: {}", tune_offset / 1e3, e));
+            let source = SourceConfig::sigmf(streamer);
+            run(source, audio_output, done_sig, obs_settings, rds_debug, rds_metrics, rds_pilot_ref, record, mpx_path.clone(), mode, discriminator, region, stereo_blend, afc);
+        }
+        Some("raw") => {
+            let path = pos.next().expect("Usage: rradio raw <path|-> [tune_offset_khz] [--format <fmt>] [--rate <sps>] [--center <mhz>]");
+            let tune_offset: f32 = pos.next()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0.0)
+                * 1e3;
+            let format = raw_format.or(RawIqFormat::from_filename(path));
+            let mut source = RawIqSource::open(path, format).unwrap_or_else(|e| panic!("{}; pass --format and --rate", e));
+            let tuned = source.center_frequency() - tune_offset as f64;
+            source.retune(tuned).unwrap_or_else(|e| panic!("Cannot tune offset {} kHz: {}", tune_offset / 1e3, e));
+            run(SourceConfig::raw(source), audio_output, done_sig, obs_settings, rds_debug, rds_metrics, rds_pilot_ref, record, mpx_path.clone(), mode, discriminator, region, stereo_blend, afc);
+        }
+        Some("wav") => {
+            let path = pos.next().expect("Usage: rradio wav <path.wav> [tune_offset_khz] [--center <mhz>]");
+            let tune_offset: f32 = pos.next()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0.0)
+                * 1e3;
+            let mut source = WavIqSource::open(path, raw_format.frequency).expect("Failed to open WAV-IQ file");
+            if let Some(auxi) = source.auxi() {
+                eprintln!("WAV-IQ: recorded {} at {:.3} MHz", auxi.start_time, auxi.center_frequency / 1e6);
+            }
+            let tuned = source.center_frequency() - tune_offset as f64;
+            source.retune(tuned).unwrap_or_else(|e| panic!("Cannot tune offset {} kHz: {}", tune_offset / 1e3, e));
+            run(SourceConfig::wav(source), audio_output, done_sig, obs_settings, rds_debug, rds_metrics, rds_pilot_ref, record, mpx_path.clone(), mode, discriminator, region, stereo_blend, afc);
         }
         Some("soapy") => {
             let filter = pos.next().expect("Usage: rradio soapy <filter> [station_mhz]");
@@ -768,33 +1471,179 @@ fn main() {
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(96.1)
                 * 1e6;
+            let station = if snap_station { snap_to_channel(&region, station) } else { station };
             let config = rradio_sdr::soapy::SoapyConfig {
                 filter: filter.to_string(),
                 station,
                 bw: 200e6,
                 fs: 2.4e6,
+                correction,
+                ppm: device_ppm(filter, ppm),
+                gain: gain_control.clone(),
             };
-            run(IqSource::Soapy { config }, audio_output, done_sig, obs_settings, rds_debug, rds_metrics, record_path, mpx_path.clone());
+            spawn_gain_console(gain_control);
+            run(SourceConfig::Soapy { config }, audio_output, done_sig, obs_settings, rds_debug, rds_metrics, rds_pilot_ref, record, mpx_path.clone(), mode, discriminator, region, stereo_blend, afc);
         }
         Some("pluto") => {
             let station: f32 = pos.next()
                 .and_then(|s| s.parse().ok())
                 .unwrap_or(96.1)
                 * 1e6;
+            let station = if snap_station { snap_to_channel(&region, station) } else { station };
             let config = rradio_sdr::pluto::SdrConfig {
-                uri: "ip:pluto.local".to_string(),
+                uri: PLUTO_URI.to_string(),
                 station,
                 bw: 200e6,
                 fs: 2.4e6,
+                correction,
+                ppm: device_ppm(PLUTO_URI, ppm),
+                gain: gain_control.clone(),
+            };
+            spawn_gain_console(gain_control);
+            run(SourceConfig::Pluto { config }, audio_output, done_sig, obs_settings, rds_debug, rds_metrics, rds_pilot_ref, record, mpx_path.clone(), mode, discriminator, region, stereo_blend, afc);
+        }
+        Some("rtltcp") => {
+            let address = pos.next().expect("Usage: rradio rtltcp <host:port> [station_mhz]");
+            let station: f32 = pos.next()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(96.1)
+                * 1e6;
+            let station = if snap_station { snap_to_channel(&region, station) } else { station };
+            let config = rradio_sdr::rtl_tcp::RtlTcpConfig {
+                address: address.to_string(),
+                station,
+                fs: 2.4e6,
+                correction,
+                ppm: device_ppm(address, ppm),
+                gain: gain_control.clone(),
+                rtl_agc,
+            };
+            spawn_gain_console(gain_control);
+            run(SourceConfig::RtlTcp { config }, audio_output, done_sig, obs_settings, rds_debug, rds_metrics, rds_pilot_ref, record, mpx_path.clone(), mode, discriminator, region, stereo_blend, afc);
+        }
+        Some("list") => {
+            let path = pos.next().expect("Usage: rradio list <archive.sigmf|path.sigmf-collection|path.sigmf-meta>");
+            list_sigmf(path).unwrap_or_else(|e| panic!("Cannot list {}: {}", path, e));
+        }
+        Some("archive") => {
+            const USAGE: &str = "Usage: rradio archive <out.sigmf> <recording.sigmf-meta>... [--description <text>]";
+            let path = pos.next().expect(USAGE);
+            let recordings: Vec<&str> = pos.collect();
+            if recordings.is_empty() {
+                panic!("{}", USAGE);
+            }
+            SigmfArchive::create(path, &recordings, description.as_deref())
+                .unwrap_or_else(|e| panic!("Cannot write {}: {}", path, e));
+            eprintln!("Archived {} recording(s) to {}", recordings.len(), path);
+        }
+        Some("calibrate") => {
+            const USAGE: &str = "Usage: rradio calibrate <pluto|soapy <filter>|rtltcp <host:port>> <station_mhz> [--reference <pilot|carrier>] [--duration <seconds>]";
+            let device = pos.next().expect(USAGE);
+            let filter = if device == "soapy" || device == "rtltcp" { Some(pos.next().expect(USAGE)) } else { None };
+            let station: f32 = pos.next().and_then(|s| s.parse::<f32>().ok()).expect(USAGE) * 1e6;
+            // Measure against the bare oscillator, ignoring any saved correction
+            let (source, key) = match (device, filter) {
+                ("pluto", None) => (SourceConfig::Pluto { config: rradio_sdr::pluto::SdrConfig {
+                    uri: PLUTO_URI.to_string(), station, bw: 200e6, fs: 2.4e6, correction, ppm: 0.0, gain: gain_control,
+                } }, PLUTO_URI),
+                ("soapy", Some(filter)) => (SourceConfig::Soapy { config: rradio_sdr::soapy::SoapyConfig {
+                    filter: filter.to_string(), station, bw: 200e6, fs: 2.4e6, correction, ppm: 0.0, gain: gain_control,
+                } }, filter),
+                ("rtltcp", Some(address)) => (SourceConfig::RtlTcp { config: rradio_sdr::rtl_tcp::RtlTcpConfig {
+                    address: address.to_string(), station, fs: 2.4e6, correction, ppm: 0.0, gain: gain_control, rtl_agc,
+                } }, address),
+                _ => panic!("{}", USAGE),
+            };
+            calibrate(source, key, calibration_reference, duration_secs.unwrap_or(10.0), done_sig);
+        }
+        Some("serve") => {
+            const USAGE: &str = "Usage: rradio serve <pluto|soapy <filter>|rtltcp <host:port>|sigmf <path>|raw <path|->|wav <path>> [station_mhz] [--listen <host:port>]";
+            let device = pos.next().expect(USAGE);
+            let name = if device == "pluto" { None } else { Some(pos.next().expect(USAGE)) };
+            let station: f32 = pos.next()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(96.1)
+                * 1e6;
+            let source = match (device, name) {
+                ("pluto", None) => SourceConfig::Pluto { config: rradio_sdr::pluto::SdrConfig {
+                    uri: PLUTO_URI.to_string(), station, bw: 200e6, fs: 2.4e6, correction,
+                    ppm: device_ppm(PLUTO_URI, ppm), gain: gain_control.clone(),
+                } },
+                ("soapy", Some(filter)) => SourceConfig::Soapy { config: rradio_sdr::soapy::SoapyConfig {
+                    filter: filter.to_string(), station, bw: 200e6, fs: 2.4e6, correction,
+                    ppm: device_ppm(filter, ppm), gain: gain_control.clone(),
+                } },
+                ("rtltcp", Some(address)) => SourceConfig::RtlTcp { config: rradio_sdr::rtl_tcp::RtlTcpConfig {
+                    address: address.to_string(), station, fs: 2.4e6, correction,
+                    ppm: device_ppm(address, ppm), gain: gain_control.clone(), rtl_agc,
+                } },
+                ("sigmf", Some(path)) => SourceConfig::sigmf(
+                    open_sigmf(path, recording.as_deref(), false).expect("Failed to open SigMF file"),
+                ),
+                ("raw", Some(path)) => SourceConfig::raw(
+                    RawIqSource::open(path, raw_format.or(RawIqFormat::from_filename(path)))
+                        .unwrap_or_else(|e| panic!("{}; pass --format and --rate", e)),
+                ),
+                ("wav", Some(path)) => SourceConfig::wav(
+                    WavIqSource::open(path, raw_format.frequency).expect("Failed to open WAV-IQ file"),
+                ),
+                _ => panic!("{}", USAGE),
             };
-            run(IqSource::Pluto { config }, audio_output, done_sig, obs_settings, rds_debug, rds_metrics, record_path, mpx_path.clone());
+            serve(source, &listen, gain_control, done_sig);
         }
         _ => {
-            eprintln!("Usage: rradio <source> [options] [--wav <output.wav>]");
+            eprintln!("Usage: rradio <source> [options] [--wav <output.wav>] [--stereo-mode <auto|stereo|mono>] [--no-high-blend] [--rds-pilot-ref]");
+            eprintln!("       [--region <americas|europe|japan>] [--deemphasis <75|50|none>]");
+            eprintln!("       [--mode <wfm|nbfm|am|sam|usb|lsb|cw>] [--channel-khz <12.5|25>] [--nbfm-deemphasis] [--ctcss <tone_hz>] [--bfo <hz>]");
+            eprintln!("       [--fm-discriminator <polar|conj|fast-atan|quadrature>] [--no-dc-block] [--no-iq-balance] [--no-afc] [--ppm <correction>]");
+            eprintln!("       [--gain <db|manual|slow_attack|fast_attack|ELEMENT=db>]... (also typed as 'gain <setting>' while running)");
+            eprintln!("       [--record <base_path>] [--record-format <cu8|ci16_le|cf32_le|...>] (default: the source's encoding, after DC/IQ correction)");
+            eprintln!("       [--record-max-seconds <s>] [--record-max-mb <mb>] (start a new numbered file at either limit)");
+            eprintln!("       [--record-last <s> [--record-trigger <sync|pi|squelch|label>]...] (save the last <s> seconds on each trigger)");
             eprintln!("  rradio pluto [station_mhz]");
             eprintln!("  rradio soapy <filter> [station_mhz]");
-            eprintln!("  rradio sigmf <path.sigmf-meta> [tune_offset_khz]");
+            eprintln!("  rradio rtltcp <host:port> [station_mhz] [--rtl-agc]");
+            eprintln!("  rradio sigmf <path.sigmf-meta|archive.sigmf|path.sigmf-collection> [tune_offset_khz] [--start <seconds>] [--mmap] [--recording <name>]");
+            eprintln!("  rradio wav <path.wav> [tune_offset_khz] [--center <mhz>]");
+            eprintln!("  rradio raw <path.cu8|.cs16|.cf32|-> [tune_offset_khz] [--format <cu8|cs8|cs16|cs32|cf32>] [--rate <sps>] [--center <mhz>]");
+            eprintln!("  rradio list <archive.sigmf|path.sigmf-collection|path.sigmf-meta>");
+            eprintln!("  rradio archive <out.sigmf> <recording.sigmf-meta>... [--description <text>]");
+            eprintln!("  rradio calibrate <pluto|soapy <filter>|rtltcp <host:port>> <station_mhz> [--reference <pilot|carrier>] [--duration <seconds>]");
+            eprintln!("  rradio serve <pluto|soapy <filter>|rtltcp <host:port>|sigmf <path>|raw <path|->|wav <path>> [station_mhz] [--listen <host:port>]");
             std::process::exit(1);
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rradio_dsp::iq_correction::IqCorrector;
+
+    /// Power left in a 2 Hz tone after the front-end correction for `mode`.
+    fn tone_near_dc_power(mode: ReceiveMode) -> f32 {
+        const FS: f32 = 48000.0;
+        let mut corrector = IqCorrector::new(FS, mode.correction(IqCorrectionConfig::default()));
+        let mut tone: Vec<Complex32> = (0..2 * FS as usize)
+            .map(|i| Complex32::from_polar(0.5, 2.0 * std::f32::consts::PI * 2.0 * i as f32 / FS))
+            .collect();
+        corrector.process_block(&mut tone);
+        let tail = &tone[tone.len() / 2..];
+        tail.iter().map(|x| x.norm_sqr()).sum::<f32>() / tail.len() as f32
+    }
+
+    #[test]
+    fn test_soapy_driver() {
+        assert_eq!(soapy_driver("driver=hackrf"), "hackrf");
+        assert_eq!(soapy_driver("serial=0001, driver=airspy"), "airspy");
+        assert_eq!(soapy_driver("rtlsdr"), "rtlsdr");
+    }
+
+    #[test]
+    fn test_cw_keeps_carrier_at_dc() {
+        assert!((tone_near_dc_power(ReceiveMode::Cw { bfo: 700.0 }) - 0.25).abs() < 0.01);
+        assert!((tone_near_dc_power(ReceiveMode::Am) - 0.25).abs() < 0.01);
+        // Wideband FM keeps the notch, which takes the tone down with it
+        assert!(tone_near_dc_power(ReceiveMode::Wfm) < 0.1);
+    }
+}
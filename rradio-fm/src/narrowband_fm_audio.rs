@@ -0,0 +1,218 @@
+use rradio_dsp::deemphasis::Deemphasis;
+use rradio_dsp::filterable::Filter;
+use rradio_dsp::sos::{IirDesign, SosCascade};
+
+/// EIA/TIA-603 CTCSS tones in Hz.
+pub const CTCSS_TONES: [f32; 50] = [
+    67.0, 69.3, 71.9, 74.4, 77.0, 79.7, 82.5, 85.4, 88.5, 91.5,
+    94.8, 97.4, 100.0, 103.5, 107.2, 110.9, 114.8, 118.8, 123.0, 127.3,
+    131.8, 136.5, 141.3, 146.2, 151.4, 156.7, 159.8, 162.2, 165.5, 167.9,
+    171.3, 173.8, 177.3, 179.9, 183.5, 186.2, 189.9, 192.8, 196.6, 199.5,
+    203.5, 206.5, 210.7, 218.1, 225.7, 229.1, 233.6, 241.8, 250.3, 254.1,
+];
+
+#[derive(Debug, Clone)]
+pub struct NarrowbandFmConfig {
+    /// Channel spacing in Hz, 12.5 kHz or 25 kHz. Peak deviation is taken
+    /// as 20% of this (2.5 kHz / 5 kHz).
+    pub channel_spacing: f32,
+    /// Apply the traditional 750 µs de-emphasis.
+    pub deemphasis: bool,
+    /// Only open the audio while this CTCSS tone is present.
+    pub ctcss_squelch: Option<f32>,
+}
+
+impl NarrowbandFmConfig {
+    pub fn deviation(&self) -> f32 {
+        0.2 * self.channel_spacing
+    }
+
+    /// One-sided channel filter cutoff (Carson bandwidth for 3 kHz audio).
+    pub fn channel_cutoff(&self) -> f32 {
+        self.deviation() + 3000.0
+    }
+}
+
+impl Default for NarrowbandFmConfig {
+    fn default() -> Self {
+        NarrowbandFmConfig {
+            channel_spacing: 12500.0,
+            deemphasis: false,
+            ctcss_squelch: None,
+        }
+    }
+}
+
+pub struct NarrowbandFmAudio<I> {
+    input: I,
+    voice_hp: SosCascade<f32>,
+    voice_lp: SosCascade<f32>,
+    deemph: Option<Deemphasis<f32>>,
+    ctcss: CtcssDetector,
+    squelch: Option<f32>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct NarrowbandFmAudioOutput {
+    pub audio: f32,
+    /// Most recently detected CTCSS tone.
+    pub ctcss_tone: Option<f32>,
+    /// Whether the squelch is passing audio.
+    pub open: bool,
+}
+
+impl<I> NarrowbandFmAudio<I> where I: Iterator<Item = f32> {
+    fn new(fs: f32, i: I, config: &NarrowbandFmConfig) -> NarrowbandFmAudio<I> {
+        NarrowbandFmAudio {
+            input: i,
+            // Steep enough to take the 254.1 Hz top CTCSS tone out of the voice band
+            voice_hp: SosCascade::highpass(fs, 300.0, 6, &IirDesign::Elliptic { ripple_db: 0.5, atten_db: 50.0 }),
+            voice_lp: SosCascade::lowpass(fs, 3000.0, 4, &IirDesign::Butterworth),
+            deemph: config.deemphasis.then(|| Deemphasis::new(fs, 750e-6)),
+            ctcss: CtcssDetector::new(fs),
+            squelch: config.ctcss_squelch,
+        }
+    }
+
+    fn process(&mut self, s: f32) -> NarrowbandFmAudioOutput {
+        let ctcss_tone = self.ctcss.process(s);
+        let voice = self.voice_lp.process(self.voice_hp.process(s));
+        let voice = self.deemph.as_mut().map_or(voice, |d| d.process(voice));
+        let open = match self.squelch {
+            Some(tone) => ctcss_tone.is_some_and(|t| (t - tone).abs() < 0.5),
+            None => true,
+        };
+        NarrowbandFmAudioOutput { audio: if open { voice } else { 0.0 }, ctcss_tone, open }
+    }
+}
+
+impl<I> Iterator for NarrowbandFmAudio<I> where I: Iterator<Item = f32> {
+    type Item = NarrowbandFmAudioOutput;
+    fn next(&mut self) -> Option<Self::Item> { let s = self.input.next()?; Some(self.process(s)) }
+}
+
+pub trait NarrowbandFmAudioIterable {
+    fn nbfm_audio(self, fs: f32, config: &NarrowbandFmConfig) -> NarrowbandFmAudio<Self> where Self: Sized;
+}
+impl<I> NarrowbandFmAudioIterable for I where I: Iterator<Item = f32> {
+    fn nbfm_audio(self, fs: f32, config: &NarrowbandFmConfig) -> NarrowbandFmAudio<Self> where Self: Sized { NarrowbandFmAudio::new(fs, self, config) }
+}
+
+// Tones as close as 2.4 Hz apart need about a second of signal to resolve,
+// so the detector runs a Goertzel bank over one-second blocks of the
+// sub-audible band, decimated to 1 kHz.
+const CTCSS_RATE: f32 = 1000.0;
+const CTCSS_BLOCK_S: f32 = 1.0;
+/// Fraction of the sub-audible block energy the strongest tone must hold.
+const CTCSS_THRESHOLD: f32 = 0.5;
+
+struct CtcssDetector {
+    lowpass: SosCascade<f32>,
+    decimation: usize,
+    phase: usize,
+    block: Vec<f32>,
+    block_len: usize,
+    coeffs: Vec<f32>,
+    tone: Option<f32>,
+}
+
+impl CtcssDetector {
+    fn new(fs: f32) -> Self {
+        let decimation = (fs / CTCSS_RATE).round().max(1.0) as usize;
+        let rate = fs / decimation as f32;
+        let block_len = (rate * CTCSS_BLOCK_S) as usize;
+        CtcssDetector {
+            lowpass: SosCascade::lowpass(fs, 270.0, 6, &IirDesign::Elliptic { ripple_db: 0.5, atten_db: 60.0 }),
+            decimation,
+            phase: 0,
+            block: Vec::with_capacity(block_len),
+            block_len,
+            coeffs: CTCSS_TONES.iter()
+                .map(|&f| 2.0 * (2.0 * std::f32::consts::PI * f / rate).cos())
+                .collect(),
+            tone: None,
+        }
+    }
+
+    fn process(&mut self, s: f32) -> Option<f32> {
+        let low = self.lowpass.process(s);
+        self.phase += 1;
+        if self.phase == self.decimation {
+            self.phase = 0;
+            self.block.push(low);
+            if self.block.len() == self.block_len {
+                self.tone = self.detect();
+                self.block.clear();
+            }
+        }
+        self.tone
+    }
+
+    fn detect(&self) -> Option<f32> {
+        let n = self.block.len() as f32;
+        let energy: f32 = self.block.iter().map(|x| x * x).sum();
+        if energy <= 0.0 {
+            return None;
+        }
+        let (best, power) = self.coeffs.iter().enumerate()
+            .map(|(i, &coeff)| {
+                let (s1, s2) = self.block.iter().fold((0.0f32, 0.0f32), |(s1, s2), &x| (x + coeff * s1 - s2, s1));
+                (i, s1 * s1 + s2 * s2 - coeff * s1 * s2)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))?;
+        // A pure tone on the bin gives |X|² = (A·N/2)² against energy A²·N/2
+        (2.0 * power / (n * energy) > CTCSS_THRESHOLD).then_some(CTCSS_TONES[best])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f32 = 48000.0;
+
+    fn voice_with_tone(tone: Option<f32>, seconds: f32) -> Vec<f32> {
+        (0..(FS * seconds) as usize).map(|i| {
+            let t = i as f32 / FS;
+            let voice = 0.5 * (2.0 * std::f32::consts::PI * 1000.0 * t).sin();
+            let ctcss = tone.map_or(0.0, |f| 0.15 * (2.0 * std::f32::consts::PI * f * t).sin());
+            voice + ctcss
+        }).collect()
+    }
+
+    #[test]
+    fn test_ctcss_detect() {
+        for &tone in &[67.0, 151.4, 254.1] {
+            let out: Vec<_> = voice_with_tone(Some(tone), 2.5).into_iter()
+                .nbfm_audio(FS, &NarrowbandFmConfig::default())
+                .collect();
+            assert_eq!(out.last().unwrap().ctcss_tone, Some(tone));
+        }
+        let out: Vec<_> = voice_with_tone(None, 2.5).into_iter()
+            .nbfm_audio(FS, &NarrowbandFmConfig::default())
+            .collect();
+        assert_eq!(out.last().unwrap().ctcss_tone, None);
+    }
+
+    #[test]
+    fn test_ctcss_squelch() {
+        let config = NarrowbandFmConfig { ctcss_squelch: Some(100.0), ..Default::default() };
+        let wrong: Vec<_> = voice_with_tone(Some(123.0), 2.5).into_iter().nbfm_audio(FS, &config).collect();
+        assert!(wrong.iter().all(|o| o.audio == 0.0 && !o.open));
+        let right: Vec<_> = voice_with_tone(Some(100.0), 2.5).into_iter().nbfm_audio(FS, &config).collect();
+        assert!(right[right.len() - 100..].iter().any(|o| o.audio.abs() > 0.1));
+        assert!(!right[0].open && right.last().unwrap().open);
+    }
+
+    #[test]
+    fn test_tone_removed_from_voice() {
+        // A lone CTCSS tone should be well below the voice band output
+        let out: Vec<f32> = (0..FS as usize)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 250.3 * i as f32 / FS).sin())
+            .nbfm_audio(FS, &NarrowbandFmConfig::default())
+            .map(|o| o.audio)
+            .collect();
+        let peak = out[out.len() / 2..].iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        assert!(peak < 0.5 * 0.03, "CTCSS leak {}", peak);
+    }
+}
@@ -10,6 +10,8 @@ pub struct RdsDisplayState {
     pub ps: String,
     pub rt: String,
     pub pi_code: u16,
+    /// RBDS call letters derived from the PI code.
+    pub call_sign: Option<String>,
     pub groups_decoded: u64,
 }
 
@@ -26,10 +28,12 @@ pub struct RdsDecoder {
 
     pi_code: u16,
     groups_decoded: u64,
+    rbds: bool,
 }
 
 impl RdsDecoder {
-    pub fn new() -> Self {
+    /// `rbds` enables North American call-letter decoding of the PI code.
+    pub fn new(rbds: bool) -> Self {
         RdsDecoder {
             ps: [b' '; 8],
             ps_filled: 0,
@@ -40,6 +44,7 @@ impl RdsDecoder {
             rt_last_addr: 0xFF,
             pi_code: 0,
             groups_decoded: 0,
+            rbds,
         }
     }
 
@@ -58,6 +63,11 @@ impl RdsDecoder {
         self.display_state()
     }
 
+    /// Whether all four PS segments have been received.
+    pub fn ps_complete(&self) -> bool {
+        self.ps_filled == 0x0F
+    }
+
     pub fn display_state(&self) -> RdsDisplayState {
         let ps = self.ps.iter()
             .map(|&c| Self::sanitize(c))
@@ -74,6 +84,7 @@ impl RdsDecoder {
             ps,
             rt,
             pi_code: self.pi_code,
+            call_sign: if self.rbds { rbds_call_sign(self.pi_code) } else { None },
             groups_decoded: self.groups_decoded,
         }
     }
@@ -126,6 +137,18 @@ impl RdsDecoder {
     }
 }
 
+/// Call letters for an RBDS PI code (NRSC-4 Annex D), for the K/W
+/// four-letter range only.
+fn rbds_call_sign(pi: u16) -> Option<String> {
+    let (prefix, n) = match pi {
+        0x1000..=0x54A7 => ('K', pi - 0x1000),
+        0x54A8..=0x994F => ('W', pi - 0x54A8),
+        _ => return None,
+    };
+    let letter = |v: u16| (b'A' + v as u8) as char;
+    Some(format!("{}{}{}{}", prefix, letter(n / 676), letter(n / 26 % 26), letter(n % 26)))
+}
+
 /// Renders the RDS display to stderr using ANSI cursor control.
 pub struct RdsDisplay {
     drawn: bool,
@@ -171,7 +194,9 @@ impl RdsDisplay {
         self.last_pi = state.pi_code;
         self.last_groups = state.groups_decoded;
         self.last_synced = self.synced;
-        let pi = if state.pi_code != 0 {
+        let pi = if let Some(ref call) = state.call_sign {
+            format!("{:04X} {}", state.pi_code, call)
+        } else if state.pi_code != 0 {
             format!("{:04X}", state.pi_code)
         } else {
             "----".to_string()
@@ -198,3 +223,16 @@ impl RdsDisplay {
         eprintln!("\u{2514}{}\u{2518}", "\u{2500}".repeat(width));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rbds_call_sign() {
+        assert_eq!(rbds_call_sign(0x1000).as_deref(), Some("KAAA"));
+        assert_eq!(rbds_call_sign(0x54A8).as_deref(), Some("WAAA"));
+        assert_eq!(rbds_call_sign(0x994F).as_deref(), Some("WZZZ"));
+        assert_eq!(rbds_call_sign(0xC000), None);
+    }
+}
@@ -1,7 +1,8 @@
 use num_complex::Complex32;
 
+use rradio_dsp::agc::{Agc, AgcConfig};
 use rradio_dsp::fir;
-use rradio_dsp::osc::{Mixable, MixableIter};
+use rradio_dsp::osc::Osc;
 use rradio_dsp::resample::{RationalResampleable, RationalResampleIter};
 
 // ── Constants ──
@@ -18,6 +19,14 @@ const COSTAS_DAMPING: f32 = 0.707;
 const COSTAS_K_DET: f32 = 0.761594;  // tanh(1) for tanh(I)×Q detector
 const COSTAS_MAX_FREQ_HZ: f32 = 100.0;
 
+// AGC: symmetric RMS levelling to unit power at F_BASE
+const AGC_PRE_TAU_S: f32 = 0.07;
+const AGC_TED_TAU_S: f32 = 0.007;
+
+fn rds_agc(tau: f32) -> AgcConfig {
+    AgcConfig { attack: tau, decay: tau, ..Default::default() }
+}
+
 // Timing loop
 const TIMING_BN_HZ: f32 = 25.0;
 const TIMING_DAMPING: f32 = 1.0;
@@ -58,33 +67,59 @@ fn compute_pi_gains(loop_bw: f32, damping: f32, k_det: f32, update_rate: f32) ->
 /// Input sample rate expected by the RDS demodulator.
 pub const INPUT_FS: f32 = 171e3;
 
-pub struct RdsDemodIter<I: Iterator<Item = f32>> {
-    // NCO mix + LPF + decimate: composed from existing iterator adapters
-    inner: RationalResampleIter<MixableIter<f32, I>, Complex32>,
+/// Brings the 57 kHz RDS subcarrier in the MPX down to 0 Hz.
+///
+/// When the stereo pilot is locked its third harmonic is the subcarrier by
+/// specification, so mixing against it leaves only a static phase for the
+/// Costas loop. Without a pilot (or with `use_pilot` off) a free-running NCO
+/// is used and the Costas loop also takes out the frequency offset.
+pub struct RdsDownconverter {
+    nco: Osc,
+    use_pilot: bool,
+}
+
+impl RdsDownconverter {
+    pub fn new(fs: f32, use_pilot: bool) -> Self {
+        RdsDownconverter { nco: Osc::new(-57e3, fs), use_pilot }
+    }
+
+    /// `pilot_carrier` is the pilot-locked e^j3φ reference, if available.
+    pub fn process(&mut self, mpx: f32, pilot_carrier: Option<Complex32>) -> Complex32 {
+        let nco = self.nco.next();
+        match pilot_carrier {
+            Some(carrier) if self.use_pilot => carrier.conj() * mpx,
+            _ => nco * mpx,
+        }
+    }
+}
+
+/// RDS demodulator for MPX already downconverted by [`RdsDownconverter`],
+/// sampled at [`INPUT_FS`].
+pub struct RdsDemodIter<I: Iterator<Item = Complex32>> {
+    // LPF + decimate
+    inner: RationalResampleIter<I, Complex32>,
     costas: FineCostas,
-    agc_pre: Agc,
+    agc_pre: Agc<Complex32>,
     gardner: PolyphaseGardner,
 }
 
-impl<I: Iterator<Item = f32>> RdsDemodIter<I> {
+impl<I: Iterator<Item = Complex32>> RdsDemodIter<I> {
     pub fn new(iter: I) -> Self {
         let downsample_filter = fir::generate_lowpass_taps(
             INPUT_FS as f64, 2500.0, 1001, &fir::WindowType::Blackman,
         );
-        let inner = iter
-            .mix(-57e3, INPUT_FS)
-            .resample(downsample_filter, 1, PRE_DECIMATE);
+        let inner = iter.resample(downsample_filter, 1, PRE_DECIMATE);
 
         RdsDemodIter {
             inner,
             costas: FineCostas::new(),
-            agc_pre: Agc::new(0.001, 1.0, 1e5),
+            agc_pre: Agc::new(F_BASE, rds_agc(AGC_PRE_TAU_S)),
             gardner: PolyphaseGardner::new(),
         }
     }
 }
 
-impl<I: Iterator<Item = f32>> Iterator for RdsDemodIter<I> {
+impl<I: Iterator<Item = Complex32>> Iterator for RdsDemodIter<I> {
     type Item = Complex32;
 
     fn next(&mut self) -> Option<Complex32> {
@@ -102,10 +137,10 @@ impl<I: Iterator<Item = f32>> Iterator for RdsDemodIter<I> {
 
 pub trait RdsDemodulatable {
     fn rds_demodulate(self) -> RdsDemodIter<Self>
-    where Self: Sized + Iterator<Item = f32>;
+    where Self: Sized + Iterator<Item = Complex32>;
 }
 
-impl<I: Iterator<Item = f32>> RdsDemodulatable for I {
+impl<I: Iterator<Item = Complex32>> RdsDemodulatable for I {
     fn rds_demodulate(self) -> RdsDemodIter<I> {
         RdsDemodIter::new(self)
     }
@@ -199,36 +234,6 @@ impl FineCostas {
     }
 }
 
-// ── AGC ──
-struct Agc {
-    rate: f32,
-    target_power: f32,
-    avg_power: f32,
-    pub gain: f32,
-    max_gain: f32,
-}
-
-impl Agc {
-    fn new(rate: f32, target_rms: f32, max_gain: f32) -> Self {
-        Agc {
-            rate,
-            target_power: target_rms * target_rms,
-            avg_power: 1.0,
-            gain: 1.0,
-            max_gain,
-        }
-    }
-
-    fn process(&mut self, sample: Complex32) -> Complex32 {
-        let power = sample.re * sample.re + sample.im * sample.im;
-        self.avg_power = (1.0 - self.rate) * self.avg_power + self.rate * power;
-        if self.avg_power > 1e-20 {
-            self.gain = (self.target_power / self.avg_power).sqrt().min(self.max_gain);
-        }
-        sample * self.gain
-    }
-}
-
 // ── Polyphase Gardner Timing Recovery ──
 struct PolyphaseGardner {
     // Interpolating filter (lowpass, not RRC)
@@ -257,7 +262,7 @@ struct PolyphaseGardner {
     beta: f32,
 
     // AGC (post-MF, pre-TED)
-    agc: Agc,
+    agc: Agc<Complex32>,
 }
 
 impl PolyphaseGardner {
@@ -325,7 +330,7 @@ impl PolyphaseGardner {
             max_period: sps as f32 + 1.0,
             alpha,
             beta,
-            agc: Agc::new(0.01, 1.0, 1e5),
+            agc: Agc::new(F_BASE, rds_agc(AGC_TED_TAU_S)),
         }
     }
 
@@ -393,3 +398,30 @@ impl PolyphaseGardner {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downconverter_prefers_pilot_carrier() {
+        let fs = 240000.0;
+        // Carrier running 3 Hz off the NCO: only the pilot reference lands it at DC
+        let freq = 57003.0;
+        let mut dc = RdsDownconverter::new(fs, true);
+        let mut nco_only = RdsDownconverter::new(fs, false);
+        let n = fs as usize;
+        let (mut with_pilot, mut without) = (Complex32::new(0.0, 0.0), Complex32::new(0.0, 0.0));
+        for i in 0..n {
+            let phi = 2.0 * std::f64::consts::PI * freq * i as f64 / fs as f64;
+            let carrier = Complex32::from_polar(1.0, phi as f32);
+            let mpx = carrier.im;
+            with_pilot += dc.process(mpx, Some(carrier));
+            without += nco_only.process(mpx, Some(carrier));
+        }
+        // sin(φ)·e^-jφ averages to -j/2
+        let with_pilot = with_pilot / n as f32;
+        assert!((with_pilot - Complex32::new(0.0, -0.5)).norm() < 1e-3, "{}", with_pilot);
+        assert!((without / n as f32).norm() < 0.01);
+    }
+}
@@ -0,0 +1,116 @@
+//! Regional broadcast parameters.
+//!
+//! FM broadcasting differs by ITU region in its pre-emphasis, channel raster
+//! and whether RDS is the North American RBDS variant.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Region {
+    /// 75 µs, 88.1–107.9 MHz on odd 200 kHz channels, RBDS.
+    Americas,
+    /// 50 µs, 87.5–108 MHz on a 100 kHz raster, RDS. Also used in Australia.
+    Europe,
+    /// 50 µs, 76–95 MHz on a 100 kHz raster.
+    Japan,
+}
+
+impl std::str::FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "americas" | "us" => Ok(Region::Americas),
+            "europe" | "eu" | "australia" | "au" => Ok(Region::Europe),
+            "japan" | "jp" => Ok(Region::Japan),
+            _ => Err(format!("unknown region '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegionConfig {
+    /// De-emphasis time constant in seconds, `None` to disable.
+    pub deemphasis: Option<f32>,
+    /// Audio lowpass corner in Hz.
+    pub audio_bandwidth: f32,
+    /// Decode PI codes as RBDS call letters.
+    pub rbds: bool,
+    /// Channel raster in Hz.
+    pub channel_spacing: f32,
+    /// First and last channel centre frequencies in Hz.
+    pub band: (f32, f32),
+}
+
+impl RegionConfig {
+    pub fn preset(region: Region) -> Self {
+        match region {
+            Region::Americas => RegionConfig {
+                deemphasis: Some(75e-6),
+                audio_bandwidth: 17000.0,
+                rbds: true,
+                channel_spacing: 200e3,
+                band: (88.1e6, 107.9e6),
+            },
+            Region::Europe => RegionConfig {
+                deemphasis: Some(50e-6),
+                audio_bandwidth: 17000.0,
+                rbds: false,
+                channel_spacing: 100e3,
+                band: (87.5e6, 108.0e6),
+            },
+            Region::Japan => RegionConfig {
+                deemphasis: Some(50e-6),
+                audio_bandwidth: 17000.0,
+                rbds: false,
+                channel_spacing: 100e3,
+                band: (76.0e6, 95.0e6),
+            },
+        }
+    }
+
+    /// Nearest channel to `freq` within the band.
+    pub fn nearest_channel(&self, freq: f32) -> f32 {
+        let (first, last) = self.band;
+        let n = ((freq as f64 - first as f64) / self.channel_spacing as f64).round();
+        let channel = first as f64 + n * self.channel_spacing as f64;
+        channel.clamp(first as f64, last as f64) as f32
+    }
+}
+
+impl Default for RegionConfig {
+    fn default() -> Self {
+        RegionConfig::preset(Region::Americas)
+    }
+}
+
+/// Parse a `--deemphasis` value: `75`, `50` (µs) or `none`.
+pub fn parse_deemphasis(s: &str) -> Option<Option<f32>> {
+    match s {
+        "none" | "off" => Some(None),
+        _ => s.parse::<f32>().ok().filter(|us| *us > 0.0).map(|us| Some(us * 1e-6)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest_channel() {
+        let us = RegionConfig::preset(Region::Americas);
+        assert_eq!(us.nearest_channel(96.1e6), 96.1e6);
+        // Even tenths are off the US raster, halfway between two channels rounds up
+        assert_eq!(us.nearest_channel(96.18e6), 96.1e6);
+        assert_eq!(us.nearest_channel(96.2e6), 96.3e6);
+        assert_eq!(us.nearest_channel(80.0e6), 88.1e6);
+
+        let eu = RegionConfig::preset(Region::Europe);
+        assert!((eu.nearest_channel(96.23e6) - 96.2e6).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_parse_deemphasis() {
+        assert_eq!(parse_deemphasis("none"), Some(None));
+        assert_eq!(parse_deemphasis("50"), Some(Some(50e-6)));
+        assert_eq!(parse_deemphasis("fast"), None);
+    }
+}
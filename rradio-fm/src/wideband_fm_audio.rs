@@ -1,12 +1,16 @@
+use num_complex::Complex32;
+
 use rradio_dsp::biquad::Biquad;
 use rradio_dsp::deemphasis::Deemphasis;
 use rradio_dsp::filterable::Filter;
-use rradio_dsp::pll::RealPll;
-use num_complex::Complex32;
+use rradio_dsp::pll::{PilotEvent, PilotPll, PilotPllConfig, PilotPllOutput};
+use rradio_dsp::sos::{IirDesign, SosCascade};
+
+use crate::region::RegionConfig;
 
 pub struct WidebandFmAudio<I> {
-    input: I, downmix: StereoDownmixer,
-    l_deemph: Deemphasis<f32>, r_deemph: Deemphasis<f32>,
+    input: I, downmix: StereoDownmixer, blend: StereoBlend,
+    l_deemph: Option<Deemphasis<f32>>, r_deemph: Option<Deemphasis<f32>>,
     l_audio_filt: Biquad<f32>, r_audio_filt: Biquad<f32>,
 }
 
@@ -14,25 +18,73 @@ pub struct WidebandFmAudio<I> {
 pub struct WidebandFmAudioOutput {
     pub left: f32,
     pub right: f32,
+    /// Smoothed pilot amplitude.
     pub stereo_lock: f32,
+    /// Stereo indicator changes.
+    pub stereo_event: Option<PilotEvent>,
+    /// Pilot-locked 57 kHz reference (e^j3φ) while the pilot is locked.
+    pub rds_carrier: Option<Complex32>,
     pub mpx: f32,
 }
 
+/// How the L−R channel is applied to the output matrix.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StereoMode {
+    /// Blend toward mono as pilot lock and stereo-band SNR drop.
+    Auto,
+    /// Full stereo whenever the pilot is present.
+    Stereo,
+    /// Never apply L−R.
+    Mono,
+}
+
+#[derive(Debug, Clone)]
+pub struct StereoBlendConfig {
+    pub mode: StereoMode,
+    /// Stereo is only applied while the pilot PLL reports lock.
+    pub pilot: PilotPllConfig,
+    /// Pilot-to-noise ratio (dB, noise measured in the 23–53 kHz band)
+    /// where L−R is fully muted and fully applied.
+    pub mono_pnr_db: f32,
+    pub stereo_pnr_db: f32,
+    /// Treble cut on L−R while blending: content above this frequency fades
+    /// out ahead of the rest of the stereo signal. `None` disables it.
+    pub high_blend_cutoff: Option<f32>,
+}
+
+impl Default for StereoBlendConfig {
+    fn default() -> Self {
+        StereoBlendConfig {
+            mode: StereoMode::Auto,
+            pilot: PilotPllConfig::default(),
+            mono_pnr_db: 10.0,
+            stereo_pnr_db: 30.0,
+            high_blend_cutoff: Some(2500.0),
+        }
+    }
+}
+
 impl<I> WidebandFmAudio<I> where I: Iterator<Item = f32> {
-    fn new(fs: f32, i: I) -> WidebandFmAudio<I> {
+    fn new(fs: f32, i: I, region: &RegionConfig, blend: StereoBlendConfig) -> WidebandFmAudio<I> {
         WidebandFmAudio {
-            input: i, downmix: StereoDownmixer::new(fs),
-            l_deemph: Deemphasis::new(fs, 75e-6), r_deemph: Deemphasis::new(fs, 75e-6),
-            l_audio_filt: Biquad::lowpass(fs, 17000.0, 0.707),
-            r_audio_filt: Biquad::lowpass(fs, 17000.0, 0.707),
+            input: i, downmix: StereoDownmixer::new(fs, blend.pilot.clone()), blend: StereoBlend::new(fs, blend),
+            l_deemph: region.deemphasis.map(|tau| Deemphasis::new(fs, tau)),
+            r_deemph: region.deemphasis.map(|tau| Deemphasis::new(fs, tau)),
+            l_audio_filt: Biquad::lowpass(fs, region.audio_bandwidth, 0.707),
+            r_audio_filt: Biquad::lowpass(fs, region.audio_bandwidth, 0.707),
         }
     }
     fn process(&mut self, s: f32) -> WidebandFmAudioOutput {
         let mono = s;
-        let StereoDownmixerOutput { stereo, stereo_lock } = self.downmix.process(s);
-        let l = self.l_audio_filt.process(self.l_deemph.process(mono + stereo));
-        let r = self.r_audio_filt.process(self.r_deemph.process(mono - stereo));
-        WidebandFmAudioOutput { left: l, right: r, stereo_lock, mpx: s }
+        let StereoDownmixerOutput { stereo, quad, pilot } = self.downmix.process(s);
+        let stereo = self.blend.process(stereo, quad, &pilot);
+        let (l, r) = (mono + stereo, mono - stereo);
+        let l = self.l_deemph.as_mut().map_or(l, |d| d.process(l));
+        let r = self.r_deemph.as_mut().map_or(r, |d| d.process(r));
+        let l = self.l_audio_filt.process(l);
+        let r = self.r_audio_filt.process(r);
+        WidebandFmAudioOutput { left: l, right: r, stereo_lock: pilot.level, stereo_event: pilot.event,
+            rds_carrier: pilot.locked.then_some(pilot.subcarrier_57k), mpx: s }
     }
 }
 impl<I> Iterator for WidebandFmAudio<I> where I: Iterator<Item = f32> {
@@ -40,25 +92,157 @@ impl<I> Iterator for WidebandFmAudio<I> where I: Iterator<Item = f32> {
     fn next(&mut self) -> Option<Self::Item> { let s = self.input.next()?; Some(self.process(s)) }
 }
 pub trait WidebandFmAudioIterable {
-    fn wfm_audio(self, fs: f32) -> WidebandFmAudio<Self> where Self: Sized;
+    fn wfm_audio(self, fs: f32, region: &RegionConfig, blend: StereoBlendConfig) -> WidebandFmAudio<Self> where Self: Sized;
 }
 impl <I> WidebandFmAudioIterable for I where I: Iterator<Item = f32> {
-    fn wfm_audio(self, fs: f32) -> WidebandFmAudio<Self> where Self: Sized { WidebandFmAudio::new(fs, self) }
+    fn wfm_audio(self, fs: f32, region: &RegionConfig, blend: StereoBlendConfig) -> WidebandFmAudio<Self> where Self: Sized { WidebandFmAudio::new(fs, self, region, blend) }
 }
 
 struct StereoDownmixer {
-    pll: RealPll<Biquad<f32>>,
+    pll: PilotPll,
 }
-struct StereoDownmixerOutput { stereo: f32, stereo_lock: f32 }
+struct StereoDownmixerOutput { stereo: f32, quad: f32, pilot: PilotPllOutput }
 
 impl StereoDownmixer {
-    fn new(fs: f32) -> Self {
-        let pll_loop_filt = Biquad::lowpass(fs, 1000.0, 0.707);
-        let pll = RealPll::new(38e3, fs, 0.05, pll_loop_filt, 2.0);
-        StereoDownmixer { pll }
+    fn new(fs: f32, config: PilotPllConfig) -> Self {
+        StereoDownmixer { pll: PilotPll::new(fs, config) }
     }
     fn process(&mut self, s: f32) -> StereoDownmixerOutput {
-        let pll_out = self.pll.process(s);
-        StereoDownmixerOutput { stereo: pll_out.out * s, stereo_lock: pll_out.lock }
+        let pilot = self.pll.process(s);
+        // ×2 restores the DSB-SC amplitude
+        let carrier = pilot.subcarrier_38k * (2.0 * s);
+        StereoDownmixerOutput { stereo: carrier.im, quad: carrier.re, pilot }
+    }
+}
+
+// L−R is DSB-SC on the in-phase 38 kHz carrier, so the quadrature product
+// carries only the noise that lands in the 23–53 kHz band. Comparing that to
+// the pilot (a fixed 9% of deviation) gives a programme-independent SNR.
+const NOISE_SMOOTH_S: f32 = 0.05;
+const BLEND_ATTACK_S: f32 = 0.02; // toward mono
+const BLEND_RELEASE_S: f32 = 1.0; // toward stereo
+
+struct StereoBlend {
+    config: StereoBlendConfig,
+    noise_filt: SosCascade<f32>,
+    noise_power: f32,
+    noise_alpha: f32,
+    attack_alpha: f32,
+    release_alpha: f32,
+    gain: f32,
+    treble_cut: Option<Biquad<f32>>,
+}
+
+impl StereoBlend {
+    fn new(fs: f32, config: StereoBlendConfig) -> Self {
+        let alpha = |tau: f32| 1.0 - (-1.0 / (tau * fs)).exp();
+        StereoBlend {
+            // Steep enough to keep the 19 kHz pilot image out of the noise estimate
+            noise_filt: SosCascade::lowpass(fs, 13000.0, 6, &IirDesign::Elliptic { ripple_db: 0.5, atten_db: 60.0 }),
+            noise_power: 0.0,
+            noise_alpha: alpha(NOISE_SMOOTH_S),
+            attack_alpha: alpha(BLEND_ATTACK_S),
+            release_alpha: alpha(BLEND_RELEASE_S),
+            gain: 0.0,
+            treble_cut: config.high_blend_cutoff.map(|fc| Biquad::lowpass(fs, fc, 0.707)),
+            config,
+        }
+    }
+
+    /// Pilot-to-noise ratio in dB.
+    fn pnr_db(&self, pilot_level: f32) -> f32 {
+        10.0 * (pilot_level * pilot_level / 2.0 / self.noise_power.max(1e-20)).log10()
+    }
+
+    fn process(&mut self, stereo: f32, quad: f32, pilot: &PilotPllOutput) -> f32 {
+        let q = self.noise_filt.process(quad);
+        self.noise_power += self.noise_alpha * (q * q - self.noise_power);
+
+        let target = match self.config.mode {
+            StereoMode::Mono => 0.0,
+            _ if !pilot.locked => 0.0,
+            StereoMode::Stereo => 1.0,
+            StereoMode::Auto => {
+                let span = self.config.stereo_pnr_db - self.config.mono_pnr_db;
+                ((self.pnr_db(pilot.level) - self.config.mono_pnr_db) / span).clamp(0.0, 1.0)
+            }
+        };
+        let alpha = if target < self.gain { self.attack_alpha } else { self.release_alpha };
+        self.gain += alpha * (target - self.gain);
+
+        match self.treble_cut {
+            // Lows follow the blend gain, treble follows its square
+            Some(ref mut lp) => {
+                let low = lp.process(stereo);
+                self.gain * (low + self.gain * (stereo - low))
+            }
+            None => self.gain * stereo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FS: f32 = 240000.0;
+    const PILOT: f32 = 0.035;
+
+    /// Deterministic uniform noise in [-1, 1).
+    fn noise(seed: &mut u32) -> f32 {
+        *seed ^= *seed << 13;
+        *seed ^= *seed >> 17;
+        *seed ^= *seed << 5;
+        *seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /// MPX with a 1 kHz tone on L only, plus optional white noise.
+    fn mpx(n: usize, noise_amp: f32) -> Vec<f32> {
+        let mut seed = 0x1234_5678;
+        (0..n).map(|i| {
+            let t = i as f64 / FS as f64;
+            let tau = 2.0 * std::f64::consts::PI;
+            let l = 0.1 * (tau * 1000.0 * t).sin();
+            let pilot = PILOT as f64 * (tau * 19000.0 * t).sin();
+            let s = l / 2.0 * (tau * 38000.0 * t).sin();
+            (l / 2.0 + pilot + s) as f32 + noise_amp * noise(&mut seed)
+        }).collect()
+    }
+
+    /// Final blend gain after running the whole signal.
+    fn final_blend(samples: Vec<f32>, config: StereoBlendConfig) -> f32 {
+        let mut wfm = samples.into_iter().wfm_audio(FS, &RegionConfig::default(), config);
+        for _ in &mut wfm {}
+        wfm.blend.gain
+    }
+
+    #[test]
+    fn test_forced_mono() {
+        let config = StereoBlendConfig { mode: StereoMode::Mono, ..Default::default() };
+        let out: Vec<WidebandFmAudioOutput> = mpx(FS as usize, 0.0).into_iter().wfm_audio(FS, &RegionConfig::default(), config).collect();
+        assert!(out.iter().all(|o| o.left == o.right));
+    }
+
+    #[test]
+    fn test_clean_signal_blends_to_stereo() {
+        let blend = final_blend(mpx(3 * FS as usize, 0.0), StereoBlendConfig::default());
+        assert!(blend > 0.9, "blend on clean signal: {}", blend);
+    }
+
+    #[test]
+    fn test_noisy_signal_blends_to_mono() {
+        let clean_blend = final_blend(mpx(3 * FS as usize, 0.0), StereoBlendConfig::default());
+        let noisy_blend = final_blend(mpx(3 * FS as usize, 0.3), StereoBlendConfig::default());
+        assert!(noisy_blend < 0.5 * clean_blend, "clean {} vs noisy {}", clean_blend, noisy_blend);
+    }
+
+    #[test]
+    fn test_mono_broadcast_stays_mono() {
+        // No pilot: the indicator never fires and L−R is never applied
+        let mono: Vec<f32> = (0..FS as usize)
+            .map(|i| 0.1 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / FS).sin())
+            .collect();
+        let out: Vec<WidebandFmAudioOutput> = mono.into_iter().wfm_audio(FS, &RegionConfig::default(), StereoBlendConfig::default()).collect();
+        assert!(out.iter().all(|o| o.stereo_event.is_none() && o.left == o.right));
     }
 }
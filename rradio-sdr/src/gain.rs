@@ -0,0 +1,162 @@
+//! Receive gain settings shared by the hardware sources.
+
+use std::sync::{Arc, Mutex};
+
+/// Hardware gain control loop.
+///
+/// SoapySDR only exposes AGC on or off, so both attack modes switch it on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GainMode {
+    Manual,
+    SlowAttack,
+    FastAttack,
+}
+
+impl GainMode {
+    /// The AD9361 `gain_control_mode` value.
+    pub fn ad9361_name(self) -> &'static str {
+        match self {
+            GainMode::Manual => "manual",
+            GainMode::SlowAttack => "slow_attack",
+            GainMode::FastAttack => "fast_attack",
+        }
+    }
+}
+
+impl std::str::FromStr for GainMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manual" => Ok(GainMode::Manual),
+            "slow_attack" | "slow" | "auto" => Ok(GainMode::SlowAttack),
+            "fast_attack" | "fast" => Ok(GainMode::FastAttack),
+            _ => Err(format!("unknown gain mode '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GainConfig {
+    /// `None` leaves the device's default.
+    pub mode: Option<GainMode>,
+    /// Overall gain in dB. Implies manual mode unless one is set.
+    pub gain: Option<f64>,
+    /// Per-stage gains in dB by element name, e.g. LNA, VGA, TIA (SoapySDR
+    /// only). Like `gain`, these imply manual mode unless one is set.
+    pub elements: Vec<(String, f64)>,
+}
+
+impl GainConfig {
+    /// The mode to put the hardware in, if any.
+    pub fn effective_mode(&self) -> Option<GainMode> {
+        let manual = self.gain.is_some() || !self.elements.is_empty();
+        self.mode.or(manual.then_some(GainMode::Manual))
+    }
+
+    /// Apply one setting: a gain in dB (`30`), a mode (`slow_attack`) or an
+    /// element gain (`LNA=20`).
+    pub fn apply_setting(&mut self, setting: &str) -> Result<(), String> {
+        if let Some((name, value)) = setting.split_once('=') {
+            let db: f64 = value.parse().map_err(|_| format!("bad gain for {}: '{}'", name, value))?;
+            match self.elements.iter_mut().find(|(element, _)| element == name) {
+                Some(element) => element.1 = db,
+                None => self.elements.push((name.to_string(), db)),
+            }
+        } else if let Ok(db) = setting.parse::<f64>() {
+            self.gain = Some(db);
+            self.mode = Some(GainMode::Manual);
+        } else {
+            self.mode = Some(setting.parse()?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct GainState {
+    config: GainConfig,
+    pending: bool,
+}
+
+/// Gain settings that can be changed while a source streams.
+///
+/// Clones share state: the UI side calls `update`, the streaming side picks
+/// the change up with `take_pending` between buffers.
+#[derive(Debug, Clone, Default)]
+pub struct GainControl(Arc<Mutex<GainState>>);
+
+impl GainControl {
+    pub fn new(config: GainConfig) -> Self {
+        GainControl(Arc::new(Mutex::new(GainState { config, pending: false })))
+    }
+
+    /// Current settings, clearing any pending change since the caller will apply them.
+    pub fn current(&self) -> GainConfig {
+        let mut state = self.0.lock().unwrap();
+        state.pending = false;
+        state.config.clone()
+    }
+
+    pub fn update<R>(&self, f: impl FnOnce(&mut GainConfig) -> R) -> R {
+        let mut state = self.0.lock().unwrap();
+        state.pending = true;
+        f(&mut state.config)
+    }
+
+    /// Settings changed since the last `current` or `take_pending`.
+    pub fn take_pending(&self) -> Option<GainConfig> {
+        let mut state = self.0.lock().unwrap();
+        if !state.pending {
+            return None;
+        }
+        state.pending = false;
+        Some(state.config.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let mut config = GainConfig::default();
+        assert_eq!(config.effective_mode(), None);
+
+        config.apply_setting("fast").unwrap();
+        assert_eq!(config.effective_mode(), Some(GainMode::FastAttack));
+
+        config.apply_setting("32.5").unwrap();
+        assert_eq!(config.gain, Some(32.5));
+        assert_eq!(config.effective_mode(), Some(GainMode::Manual));
+
+        config.apply_setting("LNA=20").unwrap();
+        config.apply_setting("VGA=10").unwrap();
+        config.apply_setting("LNA=24").unwrap();
+        assert_eq!(config.elements, vec![("LNA".to_string(), 24.0), ("VGA".to_string(), 10.0)]);
+
+        // Only applied in manual mode, so an element gain alone asks for it
+        let mut elements = GainConfig::default();
+        elements.apply_setting("LNA=20").unwrap();
+        assert_eq!(elements.effective_mode(), Some(GainMode::Manual));
+
+        assert!(config.apply_setting("loud").is_err());
+        assert!(config.apply_setting("TIA=x").is_err());
+    }
+
+    #[test]
+    fn test_control_pending() {
+        let control = GainControl::new(GainConfig::default());
+        let streamer = control.clone();
+        assert!(streamer.take_pending().is_none());
+
+        control.update(|g| g.apply_setting("40")).unwrap();
+        assert_eq!(streamer.take_pending().unwrap().gain, Some(40.0));
+        assert!(streamer.take_pending().is_none());
+
+        control.update(|g| g.apply_setting("slow")).unwrap();
+        assert_eq!(streamer.current().mode, Some(GainMode::SlowAttack));
+        assert!(streamer.take_pending().is_none());
+    }
+}
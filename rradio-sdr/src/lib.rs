@@ -1,3 +1,15 @@
 pub mod pluto;
 pub mod soapy;
 pub mod sigmf;
+pub mod sigmf_archive;
+pub mod recorder;
+pub mod raw;
+pub mod wav;
+pub mod profile;
+pub mod gain;
+pub mod source;
+pub mod rtl_tcp;
+pub mod rtl_tcp_server;
+
+#[cfg(test)]
+mod test_util;
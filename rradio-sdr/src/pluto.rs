@@ -1,8 +1,14 @@
 use industrial_io as iio;
 use num_complex::Complex32;
+use rradio_dsp::iq_correction::{IqCorrectionConfig, IqCorrector};
+
+use crate::gain::{GainConfig, GainControl, GainMode};
+use crate::source::{IqSource, SourceError};
 
 static PLUTO_SDR_STREAM_SIZE: usize = 256 * 1024;
 static PLUTO_SDR_SCALE: f32 = 4096.0;
+/// Nominal AD9361 reference clock on the Pluto.
+static PLUTO_XO_HZ: f64 = 40e6;
 
 #[derive(Clone)]
 pub struct SdrConfig {
@@ -10,18 +16,24 @@ pub struct SdrConfig {
     pub station: f32,
     pub bw: f32,
     pub fs: f32,
+    pub correction: IqCorrectionConfig,
+    /// Reference oscillator error in ppm, positive when it runs fast.
+    pub ppm: f64,
+    pub gain: GainControl,
 }
 
 pub struct PlutoSdr {
     adc: iio::Device,
     phy: iio::Device,
-    streaming: bool
+    streaming: bool,
+    corrector: IqCorrector,
 }
 
 pub struct PlutoSdrIqStreamer {
     rx_chan_i: iio::Channel,
     rx_chan_q: iio::Channel,
     rx_buf: iio::Buffer,
+    corrector: IqCorrector,
 }
 
 impl PlutoSdr {
@@ -29,17 +41,24 @@ impl PlutoSdr {
         let adc = ctx.find_device("cf-ad9361-lpc")?;
         let phy = ctx.find_device("ad9361-phy")?;
 
-        Some(PlutoSdr { adc, phy, streaming: false })
+        let corrector = IqCorrector::new(1.0, IqCorrectionConfig::OFF);
+        Some(PlutoSdr { adc, phy, streaming: false, corrector })
     }
 
     /// Create a fully configured PlutoSdr from a config, ready to stream.
     pub fn connect(config: &SdrConfig) -> Result<PlutoSdr, iio::Error> {
         let ctx = iio::Context::from_uri(&config.uri)?;
-        let sdr = PlutoSdr::new(ctx)
+        let mut sdr = PlutoSdr::new(ctx)
             .ok_or_else(|| iio::Error::General("Failed to find AD9361 devices".to_string()))?;
+        // Leave the factory-calibrated reference alone unless asked to correct it
+        if config.ppm != 0.0 {
+            sdr.set_xo_correction(config.ppm)?;
+        }
         sdr.set_center(config.station)?;
         sdr.set_rf_bandwidth(config.bw)?;
         sdr.set_sampling_freq(config.fs)?;
+        sdr.set_gain(&config.gain.current())?;
+        sdr.set_iq_correction(config.fs, config.correction);
         Ok(sdr)
     }
 
@@ -50,6 +69,13 @@ impl PlutoSdr {
             .attr_write_int("frequency", center as i64)
     }
 
+    /// Tell the AD9361 its reference is off by `ppm` from the nominal 40 MHz,
+    /// so LO and sample clock synthesis compensate. This replaces the
+    /// factory-calibrated value until the Pluto reboots.
+    pub fn set_xo_correction(&self, ppm: f64) -> Result<(), iio::Error> {
+        self.phy.attr_write_int("xo_correction", (PLUTO_XO_HZ * (1.0 + ppm * 1e-6)).round() as i64)
+    }
+
     pub fn set_rf_bandwidth(&self, bw: f32) -> Result<(), iio::Error> {
         self.phy
             .find_channel("voltage0", iio::Direction::Output)
@@ -64,6 +90,27 @@ impl PlutoSdr {
             .attr_write_int("sampling_frequency", samp_freq as i64)
     }
 
+    /// DC and IQ imbalance correction applied to streams started after this.
+    pub fn set_iq_correction(&mut self, fs: f32, config: IqCorrectionConfig) {
+        self.corrector = IqCorrector::new(fs, config);
+    }
+
+    /// Gain mode and manual gain. The AD9361 has no separately settable
+    /// stages, so element gains are ignored.
+    pub fn set_gain(&self, gain: &GainConfig) -> Result<(), iio::Error> {
+        let rx = self.phy
+            .find_channel("voltage0", iio::Direction::Input)
+            .ok_or(iio::Error::General("Missing channel".to_string()))?;
+        if let Some(mode) = gain.effective_mode() {
+            rx.attr_write_str("gain_control_mode", mode.ad9361_name())?;
+        }
+        // hardwaregain is read-only under AGC
+        if let (Some(db), Some(GainMode::Manual)) = (gain.gain, gain.effective_mode()) {
+            rx.attr_write_float("hardwaregain", db)?;
+        }
+        Ok(())
+    }
+
     pub fn start_iq(&mut self) -> Result<PlutoSdrIqStreamer, iio::Error> {
         if self.streaming {
             return Err(iio::Error::General("Already streaming".to_string()));
@@ -92,6 +139,7 @@ impl PlutoSdr {
             rx_chan_i,
             rx_chan_q,
             rx_buf,
+            corrector: self.corrector.clone(),
         })
     }
 
@@ -116,7 +164,66 @@ impl PlutoSdrIqStreamer {
             i_it.zip(q_it)
                 .map(|(&i, &q)| Complex32::new((i as f32) / PLUTO_SDR_SCALE, (q as f32) / PLUTO_SDR_SCALE))
         );
+        self.corrector.process_block(data);
+
+        Ok(())
+    }
+}
 
+/// A connected, streaming Pluto.
+pub struct PlutoSource {
+    sdr: PlutoSdr,
+    streamer: Option<PlutoSdrIqStreamer>,
+    gain: GainControl,
+    fs: f32,
+    center: f64,
+}
+
+impl PlutoSource {
+    pub fn connect(config: &SdrConfig) -> Result<PlutoSource, SourceError> {
+        let mut sdr = PlutoSdr::connect(config).map_err(|e| SourceError::Connect(format!("{:?}", e)))?;
+        let streamer = sdr.start_iq().map_err(|e| SourceError::Connect(format!("start_iq: {:?}", e)))?;
+        Ok(PlutoSource {
+            sdr,
+            streamer: Some(streamer),
+            gain: config.gain.clone(),
+            fs: config.fs,
+            center: config.station as f64,
+        })
+    }
+}
+
+impl IqSource for PlutoSource {
+    fn sample_rate(&self) -> f32 {
+        self.fs
+    }
+
+    fn center_frequency(&self) -> f64 {
+        self.center
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        let streamer = self.streamer.as_mut().ok_or_else(|| SourceError::Read("stream closed".to_string()))?;
+        streamer.collect_iq(buf).map_err(|e| SourceError::Read(format!("{:?}", e)))?;
+        if let Some(gain) = self.gain.take_pending() {
+            match self.sdr.set_gain(&gain) {
+                Ok(()) => eprintln!("SDR gain updated"),
+                Err(e) => eprintln!("SDR gain update failed: {:?}", e),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        self.sdr.set_center(frequency as f32).map_err(|e| SourceError::Retune(format!("{:?}", e)))?;
+        self.center = frequency;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        if let Some(streamer) = self.streamer.take() {
+            self.sdr.stop_iq(streamer);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,108 @@
+//! Per-device settings that persist between runs, such as the measured
+//! frequency error of the reference oscillator.
+//!
+//! Profiles live in one JSON file keyed by device: the libiio URI for a
+//! Pluto, the SoapySDR args string otherwise.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(String),
+    BadFile(String),
+}
+
+impl std::fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileError::Io(msg) => write!(f, "io error: {}", msg),
+            ProfileError::BadFile(msg) => write!(f, "bad profile file: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceProfile {
+    /// Reference oscillator error in parts per million; positive when it runs fast.
+    #[serde(default)]
+    pub ppm: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    devices: BTreeMap<String, DeviceProfile>,
+}
+
+impl Profiles {
+    /// `$RRADIO_PROFILES`, else `$XDG_CONFIG_HOME/rradio/devices.json`,
+    /// else `~/.config/rradio/devices.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("RRADIO_PROFILES") {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("rradio").join("devices.json"))
+    }
+
+    /// Load profiles; a missing file is an empty set.
+    pub fn load(path: &Path) -> Result<Profiles, ProfileError> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Profiles::default()),
+            Err(e) => return Err(ProfileError::Io(format!("{}: {}", path.display(), e))),
+        };
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| ProfileError::BadFile(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ProfileError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| ProfileError::Io(format!("{}: {}", dir.display(), e)))?;
+        }
+        let file = File::create(path)
+            .map_err(|e| ProfileError::Io(format!("{}: {}", path.display(), e)))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), self)
+            .map_err(|e| ProfileError::Io(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn get(&self, device: &str) -> Option<&DeviceProfile> {
+        self.devices.get(device)
+    }
+
+    pub fn entry(&mut self, device: &str) -> &mut DeviceProfile {
+        self.devices.entry(device.to_string()).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new("profiles");
+        let path = dir.path().join("profiles.json");
+
+        let mut profiles = Profiles::load(&path).unwrap();
+        assert!(profiles.get("ip:pluto.local").is_none());
+        profiles.entry("ip:pluto.local").ppm = -12.5;
+        profiles.entry("driver=rtlsdr").ppm = 48.0;
+        profiles.save(&path).unwrap();
+
+        let loaded = Profiles::load(&path).unwrap();
+        assert_eq!(loaded.get("ip:pluto.local").unwrap().ppm, -12.5);
+        assert_eq!(loaded.get("driver=rtlsdr").unwrap().ppm, 48.0);
+    }
+}
@@ -0,0 +1,198 @@
+//! Headerless IQ files and pipes: `.cu8`, `.cs16`, `.cf32` and the like,
+//! or `rtl_sdr -` output on stdin.
+//!
+//! Nothing in the stream says how to read it, so the format comes from the
+//! caller, falling back to what the filename gives away.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use num_complex::Complex32;
+
+use crate::sigmf::{RecordingTuner, SigmfDatatype, SigmfError};
+use crate::source::{IqSource, SourceError};
+
+/// Path that reads from stdin.
+pub const STDIN_PATH: &str = "-";
+
+/// Samples per `read_into` block; small enough to keep pipes responsive.
+const RAW_BLOCK: usize = 64 * 1024;
+
+/// What is known about a raw stream's encoding. Unknown fields are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RawIqFormat {
+    pub datatype: Option<SigmfDatatype>,
+    pub sample_rate: Option<f32>,
+    /// Centre frequency in Hz.
+    pub frequency: Option<f64>,
+}
+
+impl RawIqFormat {
+    /// Guess the format from a filename.
+    ///
+    /// The extension gives the datatype (`.cu8`, `.cs16`, `.cf32`, ...).
+    /// Underscore- or dash-separated tokens with units give the rest, e.g.
+    /// `pass_137.1MHz_1.024Msps.cu8`, and gqrx's
+    /// `gqrx_YYYYMMDD_HHMMSS_<freq>_<rate>_fc.raw` is understood too.
+    pub fn from_filename(path: &str) -> RawIqFormat {
+        let path = Path::new(path);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        let mut format = RawIqFormat { datatype: SigmfDatatype::from_extension(ext), ..Default::default() };
+
+        let tokens: Vec<&str> = stem.split(['_', '-']).collect();
+        if let ["gqrx", _, _, freq, rate, "fc"] = tokens.as_slice() {
+            format.datatype = Some(SigmfDatatype::CF32_LE);
+            format.frequency = freq.parse().ok();
+            format.sample_rate = rate.parse().ok();
+            return format;
+        }
+        for token in tokens {
+            if let Some(hz) = parse_unit(token, "hz") {
+                format.frequency = Some(hz);
+            } else if let Some(sps) = parse_unit(token, "sps").or_else(|| parse_unit(token, "s")) {
+                format.sample_rate = Some(sps as f32);
+            } else if format.datatype.is_none() {
+                format.datatype = SigmfDatatype::from_extension(token);
+            }
+        }
+        format
+    }
+
+    /// Fill anything unknown here from `other`.
+    pub fn or(self, other: RawIqFormat) -> RawIqFormat {
+        RawIqFormat {
+            datatype: self.datatype.or(other.datatype),
+            sample_rate: self.sample_rate.or(other.sample_rate),
+            frequency: self.frequency.or(other.frequency),
+        }
+    }
+}
+
+/// A number with an optional k/M/G prefix followed by `unit`, case-insensitive:
+/// `96.1MHz`, `2.4Msps`, `250ks`.
+fn parse_unit(token: &str, unit: &str) -> Option<f64> {
+    let lower = token.to_ascii_lowercase();
+    let value = lower.strip_suffix(unit)?;
+    let (number, scale) = match value.chars().last()? {
+        'k' => (&value[..value.len() - 1], 1e3),
+        'm' => (&value[..value.len() - 1], 1e6),
+        'g' => (&value[..value.len() - 1], 1e9),
+        _ => (value, 1.0),
+    };
+    number.parse::<f64>().ok().filter(|v| *v > 0.0).map(|v| v * scale)
+}
+
+pub struct RawIqSource {
+    reader: BufReader<Box<dyn Read + Send>>,
+    datatype: SigmfDatatype,
+    sample_rate: f32,
+    tuner: RecordingTuner,
+    bytes: Vec<u8>,
+}
+
+impl RawIqSource {
+    /// Open `path`, or stdin for `-`. The datatype and sample rate must be
+    /// known; a missing centre frequency is taken as 0.
+    pub fn open(path: &str, format: RawIqFormat) -> Result<RawIqSource, SigmfError> {
+        let datatype = format.datatype
+            .ok_or_else(|| SigmfError::UnsupportedDatatype(format!("{}: unknown datatype", path)))?;
+        let sample_rate = format.sample_rate
+            .ok_or_else(|| SigmfError::BadFile(format!("{}: unknown sample rate", path)))?;
+        let reader: Box<dyn Read + Send> = if path == STDIN_PATH {
+            Box::new(std::io::stdin())
+        } else {
+            Box::new(File::open(path).map_err(|e| SigmfError::BadFile(format!("{}: {}", path, e)))?)
+        };
+        Ok(RawIqSource::from_reader(reader, datatype, sample_rate, format.frequency.unwrap_or(0.0)))
+    }
+
+    pub fn from_reader(reader: Box<dyn Read + Send>, datatype: SigmfDatatype, sample_rate: f32, frequency: f64) -> Self {
+        RawIqSource {
+            reader: BufReader::new(reader),
+            datatype,
+            sample_rate,
+            tuner: RecordingTuner::new(sample_rate, frequency),
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn datatype(&self) -> SigmfDatatype {
+        self.datatype
+    }
+}
+
+impl IqSource for RawIqSource {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn center_frequency(&self) -> f64 {
+        self.tuner.tuned()
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        buf.resize(RAW_BLOCK, Complex32::new(0.0, 0.0));
+        let n = self.datatype.read_samples(&mut self.reader, buf, &mut self.bytes)
+            .map_err(|e| SourceError::Read(e.to_string()))?;
+        buf.truncate(n);
+        self.tuner.apply(buf);
+        Ok(n)
+    }
+
+    /// Shift the stream so `frequency` sits at 0 Hz.
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        self.tuner.retune(frequency)
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_filename() {
+        let format = RawIqFormat::from_filename("captures/pass_137.1MHz_1.024Msps.cu8");
+        assert_eq!(format.datatype, Some(SigmfDatatype::CU8));
+        assert_eq!(format.frequency, Some(137.1e6));
+        assert_eq!(format.sample_rate, Some(1.024e6));
+
+        let format = RawIqFormat::from_filename("gqrx_20250920_101500_96100000_2400000_fc.raw");
+        assert_eq!(format.datatype, Some(SigmfDatatype::CF32_LE));
+        assert_eq!(format.frequency, Some(96.1e6));
+        assert_eq!(format.sample_rate, Some(2.4e6));
+
+        let format = RawIqFormat::from_filename("keyfob-433.92MHz-250ks-cs16.bin");
+        assert_eq!(format.datatype, Some(SigmfDatatype::CI16_LE));
+        assert_eq!(format.frequency, Some(433.92e6));
+        assert_eq!(format.sample_rate, Some(250e3));
+
+        let format = RawIqFormat::from_filename("capture.bin");
+        assert_eq!(format, RawIqFormat::default());
+        let flags = RawIqFormat { sample_rate: Some(2.048e6), ..Default::default() };
+        assert_eq!(flags.or(RawIqFormat::from_filename("x.cu8")).datatype, Some(SigmfDatatype::CU8));
+    }
+
+    #[test]
+    fn test_reads_cs16_stream() {
+        // Three samples and a stray byte, as a truncated pipe might leave
+        let mut data = Vec::new();
+        for (re, im) in [(16384i16, -16384i16), (0, 32767), (-32768, 0)] {
+            data.extend(re.to_le_bytes());
+            data.extend(im.to_le_bytes());
+        }
+        data.push(0x7f);
+        let mut source = RawIqSource::from_reader(Box::new(std::io::Cursor::new(data)), SigmfDatatype::CI16_LE, 1000.0, 0.0);
+
+        let mut buf = Vec::new();
+        assert_eq!(source.read_into(&mut buf).unwrap(), 3);
+        assert_eq!(buf[0], Complex32::new(0.5, -0.5));
+        assert_eq!(buf[2], Complex32::new(-1.0, 0.0));
+        assert_eq!(source.read_into(&mut buf).unwrap(), 0);
+    }
+}
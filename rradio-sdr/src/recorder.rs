@@ -0,0 +1,325 @@
+//! Unattended IQ recording on top of `SigmfWriter`: one long capture split
+//! into files of bounded length or size, or a rolling window held in memory
+//! and saved only when something of interest is logged.
+//!
+//! Annotations and retunes go to one `RecordingLog` for the whole stream;
+//! each file picks up the part that falls within it.
+
+use std::collections::VecDeque;
+use std::thread::JoinHandle;
+
+use num_complex::Complex32;
+
+use crate::sigmf::{RecordingLog, SigmfAnnotation, SigmfDatatype, SigmfError, SigmfWriter};
+
+/// What every file of a recording shares.
+#[derive(Debug, Clone)]
+pub struct RecorderConfig {
+    /// Path without extension. Split or triggered recordings add `-0000`,
+    /// `-0001`, ... to it.
+    pub base_path: String,
+    pub sample_rate: f64,
+    /// Centre frequency in Hz at the start.
+    pub frequency: f64,
+    pub hw: String,
+    pub datatype: SigmfDatatype,
+}
+
+/// When to move on to a new file; whichever limit is reached first.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RotationLimits {
+    pub max_seconds: Option<f64>,
+    /// Size of the data file in bytes.
+    pub max_bytes: Option<u64>,
+}
+
+impl RotationLimits {
+    /// Samples per file, or `None` to keep one file.
+    fn max_samples(&self, sample_rate: f64, datatype: SigmfDatatype) -> Option<u64> {
+        let by_time = self.max_seconds.map(|s| (s * sample_rate) as u64);
+        let by_size = self.max_bytes.map(|b| b / datatype.sample_size() as u64);
+        let max = match (by_time, by_size) {
+            (Some(t), Some(s)) => Some(t.min(s)),
+            (t, s) => t.or(s),
+        };
+        max.map(|m| m.max(1))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordMode {
+    /// Record everything, starting a new file at the limits.
+    Continuous(RotationLimits),
+    /// Hold the last `seconds` of IQ and save them whenever an annotation
+    /// whose label starts with one of `triggers` is logged.
+    PreTrigger { seconds: f64, triggers: Vec<String> },
+}
+
+enum State {
+    Continuous {
+        max_samples: Option<u64>,
+        writer: Option<SigmfWriter>,
+    },
+    PreTrigger {
+        /// The window, encoded as it will be saved.
+        ring: VecDeque<u8>,
+        /// Size of the window in bytes.
+        capacity: usize,
+        triggers: Vec<String>,
+        /// Triggers before this were covered by the last file saved.
+        armed_from: u64,
+        encoded: Vec<u8>,
+        /// Windows being written out on their own threads.
+        saving: Vec<JoinHandle<Result<(), SigmfError>>>,
+    },
+}
+
+pub struct Recorder {
+    config: RecorderConfig,
+    log: RecordingLog,
+    state: State,
+    /// Samples received so far.
+    position: u64,
+    files: usize,
+}
+
+impl Recorder {
+    /// Nothing is written until samples arrive.
+    pub fn new(config: RecorderConfig, mode: RecordMode) -> Recorder {
+        let state = match mode {
+            RecordMode::Continuous(limits) => State::Continuous {
+                max_samples: limits.max_samples(config.sample_rate, config.datatype),
+                writer: None,
+            },
+            RecordMode::PreTrigger { seconds, triggers } => {
+                let capacity = (seconds * config.sample_rate).max(1.0) as usize * config.datatype.sample_size();
+                State::PreTrigger {
+                    ring: VecDeque::with_capacity(capacity),
+                    capacity,
+                    triggers,
+                    armed_from: 0,
+                    encoded: Vec::new(),
+                    saving: Vec::new(),
+                }
+            }
+        };
+        Recorder { log: RecordingLog::new(config.sample_rate), config, state, position: 0, files: 0 }
+    }
+
+    /// A handle for adding annotations and retunes while recording.
+    pub fn log(&self) -> RecordingLog {
+        self.log.clone()
+    }
+
+    /// Number of files started so far.
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    fn next_path(&mut self, numbered: bool) -> String {
+        let path = if numbered {
+            format!("{}-{:04}", self.config.base_path, self.files)
+        } else {
+            self.config.base_path.clone()
+        };
+        self.files += 1;
+        path
+    }
+
+    /// Start a file whose first sample is `first_sample` in the log.
+    fn open(&mut self, numbered: bool, first_sample: u64) -> Result<SigmfWriter, SigmfError> {
+        let path = self.next_path(numbered);
+        let frequency = self.log.frequency_at(first_sample, self.config.frequency);
+        SigmfWriter::with_log(&path, self.config.sample_rate, frequency, &self.config.hw,
+            self.config.datatype, self.log.clone(), first_sample)
+    }
+
+    /// Write `window`, whose first sample is `first_sample` in the log, to
+    /// the next numbered file on a thread of its own, so the stream being
+    /// recorded doesn't wait on the disk.
+    fn save_window(&mut self, window: Vec<u8>, first_sample: u64, trigger: SigmfAnnotation) -> JoinHandle<Result<(), SigmfError>> {
+        let path = self.next_path(true);
+        let frequency = self.log.frequency_at(first_sample, self.config.frequency);
+        let samples = window.len() / self.config.datatype.sample_size();
+        // Our own copy, since the shared log forgets this stretch as the window moves on
+        let log = self.log.copy_range(first_sample, first_sample + samples as u64);
+        let config = self.config.clone();
+        std::thread::spawn(move || {
+            let mut writer = SigmfWriter::with_log(&path, config.sample_rate, frequency, &config.hw,
+                config.datatype, log, first_sample)?;
+            writer.write_encoded(&window)?;
+            writer.finalize()?;
+            eprintln!("Recorder: saved {:.1}s around '{}'",
+                samples as f64 / config.sample_rate, trigger.label.unwrap_or_default());
+            Ok(())
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[Complex32]) -> Result<(), SigmfError> {
+        match self.state {
+            State::Continuous { .. } => self.write_continuous(samples),
+            State::PreTrigger { .. } => self.write_pre_trigger(samples),
+        }
+    }
+
+    fn write_continuous(&mut self, mut samples: &[Complex32]) -> Result<(), SigmfError> {
+        while !samples.is_empty() {
+            let State::Continuous { max_samples, writer } = &mut self.state else { unreachable!() };
+            let max_samples = *max_samples;
+            let mut current = match writer.take() {
+                Some(writer) => writer,
+                None => self.open(max_samples.is_some(), self.position)?,
+            };
+
+            let room = max_samples.map_or(samples.len(), |max| ((max - current.samples_written()) as usize).min(samples.len()));
+            current.write_samples(&samples[..room])?;
+            self.position += room as u64;
+            samples = &samples[room..];
+
+            if max_samples.is_some_and(|max| current.samples_written() >= max) {
+                current.finalize()?;
+                // Late annotations for a finished file have nowhere to go
+                self.log.forget_before(self.position);
+            } else {
+                let State::Continuous { writer, .. } = &mut self.state else { unreachable!() };
+                *writer = Some(current);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_pre_trigger(&mut self, samples: &[Complex32]) -> Result<(), SigmfError> {
+        let sample_size = self.config.datatype.sample_size();
+        let State::PreTrigger { ring, capacity, triggers, armed_from, encoded, saving } = &mut self.state else { unreachable!() };
+        // Report a failed save once its thread is done
+        if let Some(done) = saving.iter().position(|handle| handle.is_finished()) {
+            join_save(saving.swap_remove(done))?;
+        }
+
+        encoded.clear();
+        self.config.datatype.encode_block(samples, encoded);
+        ring.extend(encoded.iter());
+        let excess = ring.len().saturating_sub(*capacity);
+        ring.drain(..excess);
+        self.position += samples.len() as u64;
+        let ring_start = self.position - (ring.len() / sample_size) as u64;
+
+        // Late reports may still name a sample that has left the window
+        let found = self.log.find(*armed_from, self.position, triggers);
+        if let Some(trigger) = found {
+            *armed_from = self.position;
+            let (front, back) = ring.as_slices();
+            let window = [front, back].concat();
+            let handle = self.save_window(window, ring_start, trigger);
+            let State::PreTrigger { saving, .. } = &mut self.state else { unreachable!() };
+            saving.push(handle);
+        }
+        self.log.forget_before(ring_start);
+        Ok(())
+    }
+
+    /// Close the open file, if any, and wait for saved windows to be
+    /// written. Samples held for a trigger are dropped.
+    pub fn finalize(self) -> Result<(), SigmfError> {
+        match self.state {
+            State::Continuous { writer: Some(writer), .. } => writer.finalize(),
+            State::PreTrigger { saving, .. } => {
+                // Wait for every save, then report the first failure
+                let results: Vec<Result<(), SigmfError>> = saving.into_iter().map(join_save).collect();
+                results.into_iter().collect()
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn join_save(handle: JoinHandle<Result<(), SigmfError>>) -> Result<(), SigmfError> {
+    handle.join().unwrap_or_else(|_| Err(SigmfError::BadFile("recorder save thread panicked".to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sigmf::SigmfStreamer;
+    use crate::test_util::TempDir;
+    use num_complex::c32;
+
+    fn config(dir: &TempDir) -> RecorderConfig {
+        RecorderConfig {
+            base_path: dir.file("recording"),
+            sample_rate: 1000.0,
+            frequency: 100e6,
+            hw: "test".to_string(),
+            datatype: SigmfDatatype::CF32_LE,
+        }
+    }
+
+    /// Open file `index` of a recording and read all its samples.
+    fn read_file(config: &RecorderConfig, index: usize) -> (SigmfStreamer, Vec<Complex32>) {
+        let mut file = SigmfStreamer::new(&format!("{}-{:04}.sigmf-meta", config.base_path, index)).unwrap();
+        let samples: Vec<Complex32> = file.by_ref().collect();
+        (file, samples)
+    }
+
+    #[test]
+    fn test_rotates_files() {
+        let dir = TempDir::new("recorder-rotate");
+        let config = config(&dir);
+        // Four samples of cf32 by time, five by size
+        let limits = RotationLimits { max_seconds: Some(0.004), max_bytes: Some(40) };
+        let mut recorder = Recorder::new(config.clone(), RecordMode::Continuous(limits));
+        let log = recorder.log();
+        log.retune(5, 101e6);
+        log.mark(6, "event", None);
+
+        let samples: Vec<Complex32> = (0..10).map(|i| c32(i as f32, 0.0)).collect();
+        for chunk in samples.chunks(3) {
+            recorder.write_samples(chunk).unwrap();
+        }
+        assert_eq!(recorder.files(), 3);
+        recorder.finalize().unwrap();
+
+        let (first, data) = read_file(&config, 0);
+        assert_eq!(data, &samples[..4]);
+        assert_eq!(first.captures().len(), 1);
+        let (second, data) = read_file(&config, 1);
+        assert_eq!(data, &samples[4..8]);
+        let captures: Vec<(u64, Option<f64>)> = second.captures().iter().map(|c| (c.sample_start, c.frequency)).collect();
+        assert_eq!(captures, vec![(0, Some(100e6)), (1, Some(101e6))]);
+        assert_eq!(second.annotations()[0].sample_start, 2);
+        let (third, data) = read_file(&config, 2);
+        assert_eq!(data, &samples[8..]);
+        assert_eq!(third.captures()[0].frequency, Some(101e6));
+        assert!(third.annotations().is_empty());
+    }
+
+    #[test]
+    fn test_saves_window_on_trigger() {
+        let dir = TempDir::new("recorder-trigger");
+        let config = config(&dir);
+        let mut recorder = Recorder::new(config.clone(), RecordMode::PreTrigger {
+            seconds: 0.004,
+            triggers: vec!["RDS PI".to_string()],
+        });
+        let log = recorder.log();
+        let samples: Vec<Complex32> = (0..16).map(|i| c32(i as f32, 0.0)).collect();
+
+        log.mark(2, "stereo", None);
+        recorder.write_samples(&samples[..8]).unwrap();
+        assert_eq!(recorder.files(), 0);
+
+        // Reported late, as the RDS decoder does
+        log.mark(7, "RDS PI 2F0C", None);
+        recorder.write_samples(&samples[8..10]).unwrap();
+        assert_eq!(recorder.files(), 1);
+        // Already saved, so no second file
+        recorder.write_samples(&samples[10..]).unwrap();
+        assert_eq!(recorder.files(), 1);
+        recorder.finalize().unwrap();
+
+        let (file, data) = read_file(&config, 0);
+        assert_eq!(data, &samples[6..10]);
+        let labels: Vec<(u64, Option<&str>)> = file.annotations().iter().map(|a| (a.sample_start, a.label.as_deref())).collect();
+        assert_eq!(labels, vec![(1, Some("RDS PI 2F0C"))]);
+    }
+}
@@ -0,0 +1,349 @@
+//! Client for RTL-SDR dongles shared over the network by `rtl_tcp`.
+//!
+//! The server opens with a 12-byte header: `RTL0`, then the tuner type and
+//! its number of gain steps as big-endian u32s. After that it streams
+//! interleaved unsigned 8-bit IQ, and takes 5-byte commands: a command byte
+//! and a big-endian u32 parameter.
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::time::Duration;
+
+use num_complex::Complex32;
+use rradio_dsp::iq_correction::{IqCorrectionConfig, IqCorrector};
+
+use crate::gain::{GainConfig, GainControl, GainMode};
+use crate::sigmf::cu8_to_complex;
+use crate::source::{IqSource, SourceError};
+
+pub const RTL_TCP_MAGIC: &[u8; 4] = b"RTL0";
+
+/// Samples per `read_into` block.
+const RTL_TCP_BLOCK: usize = 16 * 1024;
+const RTL_TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// rtl_tcp command bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RtlTcpCommand {
+    SetFrequency = 0x01,
+    SetSampleRate = 0x02,
+    /// 0 for tuner AGC, 1 for manual gain.
+    SetGainMode = 0x03,
+    /// Tenths of a dB.
+    SetGain = 0x04,
+    /// Whole ppm.
+    SetFrequencyCorrection = 0x05,
+    /// Stage in the upper 16 bits, tenths of a dB in the lower.
+    SetIfGain = 0x06,
+    /// RTL2832 digital AGC on or off.
+    SetAgcMode = 0x08,
+}
+
+impl RtlTcpCommand {
+    pub fn from_byte(byte: u8) -> Option<RtlTcpCommand> {
+        match byte {
+            0x01 => Some(RtlTcpCommand::SetFrequency),
+            0x02 => Some(RtlTcpCommand::SetSampleRate),
+            0x03 => Some(RtlTcpCommand::SetGainMode),
+            0x04 => Some(RtlTcpCommand::SetGain),
+            0x05 => Some(RtlTcpCommand::SetFrequencyCorrection),
+            0x06 => Some(RtlTcpCommand::SetIfGain),
+            0x08 => Some(RtlTcpCommand::SetAgcMode),
+            _ => None,
+        }
+    }
+
+    pub fn packet(self, param: u32) -> [u8; 5] {
+        let p = param.to_be_bytes();
+        [self as u8, p[0], p[1], p[2], p[3]]
+    }
+}
+
+/// Tuner chip named by its rtl_tcp header code.
+pub fn tuner_name(tuner: u32) -> &'static str {
+    match tuner {
+        1 => "E4000",
+        2 => "FC0012",
+        3 => "FC0013",
+        4 => "FC2580",
+        5 => "R820T",
+        6 => "R828D",
+        _ => "unknown tuner",
+    }
+}
+
+#[derive(Clone)]
+pub struct RtlTcpConfig {
+    /// `host:port` of the rtl_tcp server.
+    pub address: String,
+    pub station: f32,
+    pub fs: f32,
+    pub correction: IqCorrectionConfig,
+    /// Reference oscillator error in ppm, positive when it runs fast. The
+    /// rtl_tcp protocol only takes whole ppm, so this is rounded when sent.
+    pub ppm: f64,
+    /// Gain elements named `IF1`..`IF6` set the E4000's IF stages.
+    pub gain: GainControl,
+    pub rtl_agc: bool,
+}
+
+pub struct RtlTcpSource {
+    stream: Option<TcpStream>,
+    tuner: u32,
+    gain_steps: u32,
+    gain: GainControl,
+    corrector: IqCorrector,
+    raw: Vec<u8>,
+    fs: f32,
+    center: f64,
+}
+
+impl RtlTcpSource {
+    pub fn connect(config: &RtlTcpConfig) -> Result<RtlTcpSource, SourceError> {
+        let connect_err = |e: std::io::Error| SourceError::Connect(format!("{}: {}", config.address, e));
+        let mut stream = TcpStream::connect(&config.address).map_err(connect_err)?;
+        stream.set_read_timeout(Some(RTL_TCP_TIMEOUT)).map_err(connect_err)?;
+        stream.set_nodelay(true).map_err(connect_err)?;
+
+        let mut header = [0u8; 12];
+        stream.read_exact(&mut header).map_err(connect_err)?;
+        if &header[..4] != RTL_TCP_MAGIC {
+            return Err(SourceError::Connect(format!("{}: not an rtl_tcp server", config.address)));
+        }
+        let tuner = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let gain_steps = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+
+        let mut source = RtlTcpSource {
+            stream: Some(stream),
+            tuner,
+            gain_steps,
+            gain: config.gain.clone(),
+            corrector: IqCorrector::new(config.fs, config.correction),
+            raw: vec![0; 2 * RTL_TCP_BLOCK],
+            fs: config.fs,
+            center: config.station as f64,
+        };
+        let setup = |source: &mut RtlTcpSource| -> Result<(), SourceError> {
+            source.send(RtlTcpCommand::SetSampleRate, config.fs as u32)?;
+            source.send(RtlTcpCommand::SetFrequencyCorrection, config.ppm.round() as i32 as u32)?;
+            source.send(RtlTcpCommand::SetFrequency, config.station as u32)?;
+            source.send(RtlTcpCommand::SetAgcMode, config.rtl_agc as u32)?;
+            source.set_gain(&config.gain.current())
+        };
+        setup(&mut source).map_err(|e| SourceError::Connect(e.to_string()))?;
+        Ok(source)
+    }
+
+    /// Tuner chip reported by the server, e.g. `R820T`.
+    pub fn tuner(&self) -> &'static str {
+        tuner_name(self.tuner)
+    }
+
+    pub fn gain_steps(&self) -> u32 {
+        self.gain_steps
+    }
+
+    pub fn send(&mut self, command: RtlTcpCommand, param: u32) -> Result<(), SourceError> {
+        let stream = self.stream.as_mut().ok_or_else(|| SourceError::Read("stream closed".to_string()))?;
+        stream.write_all(&command.packet(param))
+            .map_err(|e| SourceError::Read(format!("{:?} command: {}", command, e)))
+    }
+
+    pub fn set_gain(&mut self, gain: &GainConfig) -> Result<(), SourceError> {
+        if let Some(mode) = gain.effective_mode() {
+            self.send(RtlTcpCommand::SetGainMode, (mode == GainMode::Manual) as u32)?;
+        }
+        if let (Some(db), Some(GainMode::Manual)) = (gain.gain, gain.effective_mode()) {
+            self.send(RtlTcpCommand::SetGain, (db * 10.0).round() as i32 as u32)?;
+        }
+        for (name, db) in &gain.elements {
+            let stage = name.strip_prefix("IF").and_then(|n| n.parse::<u16>().ok())
+                .ok_or_else(|| SourceError::Unsupported(format!("gain element {}", name)))?;
+            let tenths = (db * 10.0).round() as i16 as u16;
+            self.send(RtlTcpCommand::SetIfGain, ((stage as u32) << 16) | tenths as u32)?;
+        }
+        Ok(())
+    }
+}
+
+impl IqSource for RtlTcpSource {
+    fn sample_rate(&self) -> f32 {
+        self.fs
+    }
+
+    fn center_frequency(&self) -> f64 {
+        self.center
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        if let Some(gain) = self.gain.take_pending() {
+            match self.set_gain(&gain) {
+                Ok(()) => eprintln!("rtl_tcp gain updated"),
+                Err(e) => eprintln!("rtl_tcp gain update failed: {}", e),
+            }
+        }
+        let stream = self.stream.as_mut().ok_or_else(|| SourceError::Read("stream closed".to_string()))?;
+        // A server that closes has lost its dongle or been stopped: an error, not the end
+        stream.read_exact(&mut self.raw).map_err(|e| SourceError::Read(e.to_string()))?;
+
+        buf.clear();
+        buf.extend(self.raw.chunks_exact(2).map(|iq| cu8_to_complex(iq[0], iq[1])));
+        self.corrector.process_block(buf);
+        Ok(buf.len())
+    }
+
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        self.send(RtlTcpCommand::SetFrequency, frequency as u32).map_err(|e| match e {
+            SourceError::Read(msg) => SourceError::Retune(msg),
+            e => e,
+        })?;
+        self.center = frequency;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    use crate::sigmf::{complex_to_cu8, SigmfDatatype, SigmfStreamer, SigmfWriter};
+    use crate::test_util::TempDir;
+
+    /// A recording of a slow tone in `dir`, small enough to stream in a test.
+    fn write_recording(dir: &TempDir) -> String {
+        let base = dir.file("tone");
+        let mut writer = SigmfWriter::new(&base, 240000.0, 100e6, "test", SigmfDatatype::CF32_LE).unwrap();
+        let samples: Vec<Complex32> = (0..3 * RTL_TCP_BLOCK)
+            .map(|i| Complex32::from_polar(0.9, i as f32 * 0.01))
+            .collect();
+        writer.write_samples(&samples).unwrap();
+        writer.finalize().unwrap();
+        base
+    }
+
+    /// Serves one client: the header, then the recording as cu8, while
+    /// passing received commands back.
+    fn fake_server(base: &str) -> (String, mpsc::Receiver<(RtlTcpCommand, u32)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let meta = format!("{}.sigmf-meta", base);
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut commands = conn.try_clone().unwrap();
+            std::thread::spawn(move || {
+                let mut packet = [0u8; 5];
+                while commands.read_exact(&mut packet).is_ok() {
+                    let param = u32::from_be_bytes([packet[1], packet[2], packet[3], packet[4]]);
+                    let _ = tx.send((RtlTcpCommand::from_byte(packet[0]).unwrap(), param));
+                }
+            });
+
+            let mut header = RTL_TCP_MAGIC.to_vec();
+            header.extend(5u32.to_be_bytes());
+            header.extend(29u32.to_be_bytes());
+            conn.write_all(&header).unwrap();
+            let bytes: Vec<u8> = SigmfStreamer::new(&meta).unwrap()
+                .flat_map(complex_to_cu8)
+                .collect();
+            let _ = conn.write_all(&bytes);
+            let _ = conn.shutdown(Shutdown::Write);
+        });
+        (address, rx)
+    }
+
+    fn config(address: String, gain: GainConfig) -> RtlTcpConfig {
+        RtlTcpConfig {
+            address,
+            station: 100.1e6,
+            fs: 240000.0,
+            correction: IqCorrectionConfig::OFF,
+            ppm: -3.0,
+            gain: GainControl::new(gain),
+            rtl_agc: false,
+        }
+    }
+
+    #[test]
+    fn test_streams_recording() {
+        let dir = TempDir::new("rtltcp-stream");
+        let base = write_recording(&dir);
+        let (address, commands) = fake_server(&base);
+        let mut gain = GainConfig::default();
+        gain.apply_setting("29.7").unwrap();
+        let mut source = RtlTcpSource::connect(&config(address, gain)).unwrap();
+        assert_eq!(source.tuner(), "R820T");
+        assert_eq!(source.gain_steps(), 29);
+
+        let mut buf = Vec::new();
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            assert_eq!(source.read_into(&mut buf).unwrap(), RTL_TCP_BLOCK);
+            received.extend_from_slice(&buf);
+        }
+        let original: Vec<Complex32> = SigmfStreamer::new(&format!("{}.sigmf-meta", base)).unwrap().collect();
+        assert!(received.iter().zip(&original).all(|(a, b)| (a - b).norm() < 0.02));
+
+        // Server has run dry: a live source reports that as an error
+        assert!(source.read_into(&mut buf).is_err());
+
+        source.retune(101.1e6).unwrap();
+        source.close().unwrap();
+        let sent: Vec<(RtlTcpCommand, u32)> = commands.iter().collect();
+        assert_eq!(sent, vec![
+            (RtlTcpCommand::SetSampleRate, 240000),
+            (RtlTcpCommand::SetFrequencyCorrection, -3i32 as u32),
+            (RtlTcpCommand::SetFrequency, 100_100_000),
+            (RtlTcpCommand::SetAgcMode, 0),
+            (RtlTcpCommand::SetGainMode, 1),
+            (RtlTcpCommand::SetGain, 297),
+            (RtlTcpCommand::SetFrequency, 101_100_000),
+        ]);
+    }
+
+    #[test]
+    fn test_retune_after_server_closes() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (close_tx, close_rx) = mpsc::channel::<()>();
+        std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            let mut header = RTL_TCP_MAGIC.to_vec();
+            header.extend(5u32.to_be_bytes());
+            header.extend(29u32.to_be_bytes());
+            conn.write_all(&header).unwrap();
+            let _ = close_rx.recv();
+        });
+        let mut source = RtlTcpSource::connect(&config(address, GainConfig::default())).unwrap();
+        close_tx.send(()).unwrap();
+        let mut buf = Vec::new();
+        assert!(matches!(source.read_into(&mut buf), Err(SourceError::Read(_))));
+
+        // The first write to a closed connection can still be accepted locally
+        let error = (0..10).find_map(|_| source.retune(101.1e6).err());
+        assert!(matches!(error, Some(SourceError::Retune(_))), "{:?}", error);
+        assert_eq!(source.center_frequency(), 100.1e6);
+    }
+
+    #[test]
+    fn test_rejects_other_servers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (mut conn, _) = listener.accept().unwrap();
+            conn.write_all(b"HTTP/1.1 400").unwrap();
+        });
+        let result = RtlTcpSource::connect(&config(address, GainConfig::default()));
+        assert!(matches!(result, Err(SourceError::Connect(_))));
+    }
+}
@@ -0,0 +1,395 @@
+//! Serve any `IqSource` to rtl_tcp clients such as gqrx or SDR++.
+//!
+//! One client at a time, as rtl_tcp itself does. Tune commands retune the
+//! source; sample-rate commands are met by decimating when the source rate
+//! is a whole multiple of the request. Gain commands are passed to a
+//! `GainControl` when the source has one. Output is paced to real time so
+//! recordings replay at the speed they were made.
+//!
+//! The source is read continuously whether or not a client is connected,
+//! so live hardware never backs up while the server waits.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use num_complex::Complex32;
+use rradio_dsp::buffer::{buf_pair, RecvBuf};
+use rradio_dsp::fir::{generate_lowpass_taps, Fir, WindowType};
+
+use crate::gain::{GainControl, GainMode};
+use crate::rtl_tcp::{RtlTcpCommand, RTL_TCP_MAGIC};
+use crate::sigmf::complex_to_cu8;
+use crate::source::{stream_with_reconnect, IqSource, SourceError};
+
+/// Advertised tuner: an R820T and its 29 gain steps, which clients know how to drive.
+const SERVED_TUNER: u32 = 5;
+const SERVED_GAIN_STEPS: u32 = 29;
+
+/// Lowpass and keep every `factor`th sample, across block boundaries.
+struct Decimator {
+    fir: Fir<Complex32>,
+    factor: usize,
+    phase: usize,
+}
+
+impl Decimator {
+    fn new(fs: f32, factor: usize) -> Self {
+        let cutoff = 0.4 * fs as f64 / factor as f64;
+        let taps = generate_lowpass_taps(fs as f64, cutoff, 16 * factor + 1, &WindowType::Blackman);
+        Decimator { fir: Fir::new(taps), factor, phase: 0 }
+    }
+
+    fn process(&mut self, input: &[Complex32], output: &mut Vec<Complex32>) {
+        for &x in input {
+            self.fir.push(x);
+            self.phase += 1;
+            if self.phase == self.factor {
+                self.phase = 0;
+                output.push(self.fir.execute());
+            }
+        }
+    }
+}
+
+/// Source rate over requested rate, if it divides evenly.
+fn decimation_for(source_fs: f32, requested: u32) -> Option<usize> {
+    if requested == 0 {
+        return None;
+    }
+    let ratio = source_fs as f64 / requested as f64;
+    let factor = ratio.round();
+    (factor >= 1.0 && (ratio - factor).abs() < 1e-6).then_some(factor as usize)
+}
+
+/// Tune requests from clients, applied by the streaming thread between
+/// blocks. The last request is kept so a reconnected source gets it again.
+#[derive(Clone, Default)]
+struct TuneRequest(Arc<Mutex<Option<f64>>>);
+
+/// A source that follows `TuneRequest`.
+struct ClientTuned<S> {
+    source: S,
+    request: TuneRequest,
+    tuned: Option<f64>,
+}
+
+impl<S: IqSource> IqSource for ClientTuned<S> {
+    fn sample_rate(&self) -> f32 {
+        self.source.sample_rate()
+    }
+
+    fn center_frequency(&self) -> f64 {
+        self.source.center_frequency()
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        let requested = *self.request.0.lock().unwrap();
+        if let Some(frequency) = requested.filter(|&f| self.tuned != Some(f)) {
+            // Tried once per request, so a bad frequency isn't retried every block
+            self.tuned = Some(frequency);
+            if let Err(e) = self.source.retune(frequency) {
+                eprintln!("rtl_tcp: cannot tune {:.3} MHz: {}", frequency / 1e6, e);
+            }
+        }
+        self.source.read_into(buf)
+    }
+
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        self.source.retune(frequency)
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        self.source.close()
+    }
+}
+
+/// The connected client and what it has asked for.
+struct Client {
+    conn: TcpStream,
+    peer: SocketAddr,
+    commands: mpsc::Receiver<(u8, u32)>,
+    decimator: Option<Decimator>,
+}
+
+impl Client {
+    /// Send the rtl_tcp greeting and start reading commands. `None` if the
+    /// client has already gone.
+    fn start(conn: TcpStream, peer: SocketAddr) -> Option<Client> {
+        conn.set_nonblocking(false).and_then(|_| conn.set_nodelay(true)).ok()?;
+        let mut header = RTL_TCP_MAGIC.to_vec();
+        header.extend(SERVED_TUNER.to_be_bytes());
+        header.extend(SERVED_GAIN_STEPS.to_be_bytes());
+        (&conn).write_all(&header).ok()?;
+
+        // Commands arrive on their own thread so reads never stall the stream
+        let (tx, commands) = mpsc::channel();
+        let mut reader = conn.try_clone().ok()?;
+        std::thread::spawn(move || {
+            let mut packet = [0u8; 5];
+            while reader.read_exact(&mut packet).is_ok() {
+                let param = u32::from_be_bytes([packet[1], packet[2], packet[3], packet[4]]);
+                if tx.send((packet[0], param)).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(Client { conn, peer, commands, decimator: None })
+    }
+
+    fn handle_commands(&mut self, source_fs: f32, tune: &TuneRequest, gain: Option<&GainControl>) {
+        for (command, param) in self.commands.try_iter() {
+            match RtlTcpCommand::from_byte(command) {
+                Some(RtlTcpCommand::SetFrequency) => *tune.0.lock().unwrap() = Some(param as f64),
+                Some(RtlTcpCommand::SetSampleRate) => {
+                    self.decimator = match decimation_for(source_fs, param) {
+                        Some(1) => None,
+                        Some(factor) => Some(Decimator::new(source_fs, factor)),
+                        None => {
+                            eprintln!("rtl_tcp: cannot serve {} S/s from a {} S/s source, sending {} S/s",
+                                param, source_fs, source_fs);
+                            None
+                        }
+                    };
+                }
+                Some(RtlTcpCommand::SetGainMode) => {
+                    if let Some(gain) = gain {
+                        let mode = if param == 0 { GainMode::SlowAttack } else { GainMode::Manual };
+                        gain.update(|g| g.mode = Some(mode));
+                    }
+                }
+                Some(RtlTcpCommand::SetGain) => {
+                    if let Some(gain) = gain {
+                        gain.update(|g| {
+                            g.gain = Some(param as i32 as f64 / 10.0);
+                            g.mode = Some(GainMode::Manual);
+                        });
+                    }
+                }
+                // Frequency correction, IF gain, RTL AGC and the rest have no equivalent here
+                _ => {}
+            }
+        }
+    }
+
+    /// Send one block as cu8. `false` once the client has gone.
+    fn send(&mut self, block: &[Complex32], decimated: &mut Vec<Complex32>, bytes: &mut Vec<u8>) -> bool {
+        let samples = match self.decimator.as_mut() {
+            Some(decimator) => {
+                decimated.clear();
+                decimator.process(block, decimated);
+                &decimated[..]
+            }
+            None => block,
+        };
+        bytes.clear();
+        bytes.extend(samples.iter().flat_map(|&x| complex_to_cu8(x)));
+        self.conn.write_all(bytes).is_ok()
+    }
+}
+
+/// Stream the source made by `connect` to rtl_tcp clients on `listener`,
+/// one at a time, until `done` is set or the source ends.
+///
+/// The source runs through `stream_with_reconnect`, so hardware that drops
+/// out is reconnected, and its blocks are drained whether or not a client
+/// is connected. `sample_rate` is the rate the source will deliver.
+pub fn serve<S, F>(
+    listener: &TcpListener,
+    name: &str,
+    sample_rate: f32,
+    mut connect: F,
+    gain: Option<&GainControl>,
+    done: &AtomicBool,
+) -> Result<(), SourceError>
+where
+    S: IqSource,
+    F: FnMut() -> Result<S, SourceError> + Send,
+{
+    listener.set_nonblocking(true).map_err(|e| SourceError::Connect(e.to_string()))?;
+    let tune = TuneRequest::default();
+    let (blocks_tx, blocks) = buf_pair::<Vec<Complex32>>(8);
+    std::thread::scope(|scope| {
+        let source_tune = tune.clone();
+        scope.spawn(move || {
+            let connect = || connect().map(|source| ClientTuned { source, request: source_tune.clone(), tuned: None });
+            stream_with_reconnect(done, name, connect, blocks_tx, |_, _| {});
+        });
+        // Returning drops `blocks`, which stops the streaming thread
+        relay(listener, blocks, sample_rate, &tune, gain, done)
+    })
+}
+
+/// Hand each block to the client, if there is one, paced to real time.
+fn relay(
+    listener: &TcpListener,
+    mut blocks: RecvBuf<Vec<Complex32>>,
+    source_fs: f32,
+    tune: &TuneRequest,
+    gain: Option<&GainControl>,
+    done: &AtomicBool,
+) -> Result<(), SourceError> {
+    let mut client: Option<Client> = None;
+    let mut decimated = Vec::new();
+    let mut bytes = Vec::new();
+    let started = Instant::now();
+    let mut streamed: u64 = 0;
+    while let Some(block) = blocks.get() {
+        if client.is_none() {
+            match listener.accept() {
+                Ok((conn, peer)) => {
+                    eprintln!("rtl_tcp client {} connected", peer);
+                    client = Client::start(conn, peer);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(SourceError::Connect(e.to_string())),
+            }
+        }
+        if let Some(current) = client.as_mut() {
+            current.handle_commands(source_fs, tune, gain);
+            if !current.send(&block, &mut decimated, &mut bytes) {
+                eprintln!("rtl_tcp client {} disconnected", current.peer);
+                client = None;
+            }
+        }
+
+        // Live sources already run at their own rate; this only holds back a recording
+        streamed += block.len() as u64;
+        blocks.release(block);
+        let due = Duration::from_secs_f64(streamed as f64 / source_fs as f64);
+        if let Some(ahead) = due.checked_sub(started.elapsed()) {
+            std::thread::sleep(ahead);
+        }
+    }
+    if !done.load(Ordering::SeqCst) {
+        eprintln!("Source ended");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gain::GainConfig;
+    use crate::rtl_tcp::{RtlTcpConfig, RtlTcpSource};
+    use rradio_dsp::iq_correction::IqCorrectionConfig;
+
+    const FS: f32 = 480000.0;
+
+    /// `blocks` blocks of a tone 10 kHz above whatever it is tuned to.
+    /// Clones share the centre frequency.
+    #[derive(Clone)]
+    struct ToneSource {
+        center: Arc<Mutex<f64>>,
+        phase: f32,
+        blocks: usize,
+    }
+
+    impl ToneSource {
+        fn new(blocks: usize) -> Self {
+            ToneSource { center: Arc::default(), phase: 0.0, blocks }
+        }
+    }
+
+    impl IqSource for ToneSource {
+        fn sample_rate(&self) -> f32 {
+            FS
+        }
+
+        fn center_frequency(&self) -> f64 {
+            *self.center.lock().unwrap()
+        }
+
+        fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+            buf.clear();
+            if self.blocks == 0 {
+                return Ok(0);
+            }
+            self.blocks -= 1;
+            for _ in 0..8192 {
+                buf.push(Complex32::from_polar(0.5, self.phase));
+                self.phase = (self.phase + 2.0 * std::f32::consts::PI * 10000.0 / FS) % (2.0 * std::f32::consts::PI);
+            }
+            Ok(buf.len())
+        }
+
+        fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+            *self.center.lock().unwrap() = frequency;
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<(), SourceError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_decimation_for() {
+        assert_eq!(decimation_for(2.4e6, 2_400_000), Some(1));
+        assert_eq!(decimation_for(2.4e6, 1_200_000), Some(2));
+        assert_eq!(decimation_for(2.4e6, 2_048_000), None);
+        assert_eq!(decimation_for(2.4e6, 0), None);
+    }
+
+    #[test]
+    fn test_serves_rtl_tcp_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let done = std::sync::Arc::new(AtomicBool::new(false));
+        let gain = GainControl::new(GainConfig::default());
+
+        let source = ToneSource::new(usize::MAX);
+        let server_source = source.clone();
+        let server_done = done.clone();
+        let server_gain = gain.clone();
+        let server = std::thread::spawn(move || {
+            let mut server_source = Some(server_source);
+            let connect = || server_source.take().ok_or_else(|| SourceError::Connect("gone".to_string()));
+            serve(&listener, "tone", FS, connect, Some(&server_gain), &server_done).unwrap();
+        });
+
+        // Half the source rate, so the server decimates by two
+        let mut client_gain = GainConfig::default();
+        client_gain.apply_setting("20").unwrap();
+        let mut client = RtlTcpSource::connect(&RtlTcpConfig {
+            address,
+            station: 145.5e6,
+            fs: FS / 2.0,
+            correction: IqCorrectionConfig::OFF,
+            ppm: 0.0,
+            gain: GainControl::new(client_gain),
+            rtl_agc: false,
+        }).unwrap();
+        assert_eq!(client.tuner(), "R820T");
+
+        // Skip what was sent before the rate change took effect and the filter settled
+        let mut buf = Vec::new();
+        for _ in 0..8 {
+            client.read_into(&mut buf).unwrap();
+        }
+        // 10 kHz at 240 kHz turns 2π/24 per sample
+        let step = buf.windows(2).map(|w| (w[1] * w[0].conj()).arg()).sum::<f32>() / (buf.len() - 1) as f32;
+        assert!((step - 2.0 * std::f32::consts::PI / 24.0).abs() < 0.01, "step {}", step);
+        assert!(buf.iter().all(|x| (x.norm() - 0.5).abs() < 0.05));
+
+        client.close().unwrap();
+        done.store(true, Ordering::SeqCst);
+        server.join().unwrap();
+        assert_eq!(source.center_frequency(), 145.5e6);
+        let served_gain = gain.current();
+        assert_eq!(served_gain.gain, Some(20.0));
+        assert_eq!(served_gain.mode, Some(GainMode::Manual));
+    }
+
+    #[test]
+    fn test_drains_source_without_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let done = AtomicBool::new(false);
+        let mut source = Some(ToneSource::new(4));
+        let connect = || source.take().ok_or_else(|| SourceError::Connect("gone".to_string()));
+        // Returns once the source runs out, though nobody ever connected
+        serve(&listener, "tone", FS, connect, None, &done).unwrap();
+    }
+}
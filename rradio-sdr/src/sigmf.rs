@@ -1,13 +1,20 @@
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 use num_complex::{c32, Complex32};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use memmap2::Mmap;
+use rradio_dsp::osc::Osc;
+
+use crate::source::{IqSource, SourceError};
 
 #[derive(Debug)]
 pub enum SigmfError {
     BadFile(String),
     UnsupportedDatatype(String),
+    OutOfRange(String),
 }
 
 impl std::fmt::Display for SigmfError {
@@ -15,6 +22,7 @@ impl std::fmt::Display for SigmfError {
         match self {
             SigmfError::BadFile(msg) => write!(f, "bad file: {}", msg),
             SigmfError::UnsupportedDatatype(dt) => write!(f, "unsupported datatype: {}", dt),
+            SigmfError::OutOfRange(msg) => write!(f, "out of range: {}", msg),
         }
     }
 }
@@ -24,6 +32,10 @@ impl std::error::Error for SigmfError {}
 #[derive(Deserialize)]
 struct SigmfMetaFile {
     global: SigmfGlobal,
+    #[serde(default)]
+    captures: Vec<SigmfCapture>,
+    #[serde(default)]
+    annotations: Vec<SigmfAnnotation>,
 }
 
 #[derive(Deserialize)]
@@ -32,58 +44,498 @@ struct SigmfGlobal {
     datatype: String,
     #[serde(rename = "core:sample_rate")]
     sample_rate: f64,
+    /// Header bytes before the first sample in the data file.
+    #[serde(rename = "core:offset", default)]
+    offset: u64,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum SigmfDatatype {
-    Ci8,
-    Cu8,
-    Ci16Le,
-    Ci32Le,
-    Cf32Le,
+/// A capture segment: from `sample_start` on, the recording was made with
+/// these settings.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SigmfCapture {
+    #[serde(rename = "core:sample_start", default)]
+    pub sample_start: u64,
+    /// Centre frequency in Hz.
+    #[serde(rename = "core:frequency", skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<f64>,
+    /// ISO 8601 UTC time of `sample_start`.
+    #[serde(rename = "core:datetime", skip_serializing_if = "Option::is_none")]
+    pub datetime: Option<String>,
 }
 
-impl SigmfDatatype {
-    fn parse(s: &str) -> Result<SigmfDatatype, SigmfError> {
+/// A labelled stretch of samples, optionally bounded in frequency.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct SigmfAnnotation {
+    #[serde(rename = "core:sample_start")]
+    pub sample_start: u64,
+    #[serde(rename = "core:sample_count", skip_serializing_if = "Option::is_none")]
+    pub sample_count: Option<u64>,
+    #[serde(rename = "core:freq_lower_edge", skip_serializing_if = "Option::is_none")]
+    pub freq_lower_edge: Option<f64>,
+    #[serde(rename = "core:freq_upper_edge", skip_serializing_if = "Option::is_none")]
+    pub freq_upper_edge: Option<f64>,
+    #[serde(rename = "core:label", skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(rename = "core:comment", skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct RecordingLogState {
+    annotations: Vec<SigmfAnnotation>,
+    /// Centre frequency changes as (sample, frequency), in order.
+    retunes: Vec<(u64, f64)>,
+}
+
+/// Metadata gathered from any thread while a recording is made: annotations
+/// and retunes, each placed by sample index from the start of the stream.
+///
+/// Clones share the same state; each `SigmfWriter` holding it picks up what
+/// falls within its own samples, so one log can span several files.
+#[derive(Debug, Clone)]
+pub struct RecordingLog {
+    sample_rate: f64,
+    state: Arc<Mutex<RecordingLogState>>,
+}
+
+impl RecordingLog {
+    pub fn add(&self, annotation: SigmfAnnotation) {
+        self.state.lock().unwrap().annotations.push(annotation);
+    }
+
+    /// Mark the point `sample` samples into the recording.
+    pub fn mark(&self, sample: u64, label: &str, comment: Option<String>) {
+        self.add(SigmfAnnotation {
+            sample_start: sample,
+            label: Some(label.to_string()),
+            comment,
+            ..Default::default()
+        });
+    }
+
+    /// Mark the point `seconds` into the recording, for stages that run
+    /// at a lower rate than the IQ.
+    pub fn mark_at(&self, seconds: f64, label: &str, comment: Option<String>) {
+        self.mark((seconds * self.sample_rate).round() as u64, label, comment);
+    }
+
+    /// The centre frequency became `frequency` Hz from `sample` on.
+    pub fn retune(&self, sample: u64, frequency: f64) {
+        self.state.lock().unwrap().retunes.push((sample, frequency));
+    }
+
+    pub(crate) fn new(sample_rate: f64) -> Self {
+        RecordingLog { sample_rate, state: Arc::default() }
+    }
+
+    /// The centre frequency at `sample`, or `initial` if it was never retuned.
+    pub(crate) fn frequency_at(&self, sample: u64, initial: f64) -> f64 {
+        let state = self.state.lock().unwrap();
+        state.retunes.iter().rev().find(|(at, _)| *at <= sample).map_or(initial, |(_, f)| *f)
+    }
+
+    /// The earliest annotation in `start..end` whose label begins with one
+    /// of `prefixes`.
+    pub(crate) fn find(&self, start: u64, end: u64, prefixes: &[String]) -> Option<SigmfAnnotation> {
+        let state = self.state.lock().unwrap();
+        state.annotations.iter()
+            .filter(|a| (start..end).contains(&a.sample_start))
+            .filter(|a| a.label.as_ref().is_some_and(|label| prefixes.iter().any(|p| label.starts_with(p.as_str()))))
+            .min_by_key(|a| a.sample_start)
+            .cloned()
+    }
+
+    /// Drop what lies before `sample`, keeping the frequency in force there.
+    pub(crate) fn forget_before(&self, sample: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.annotations.retain(|a| a.sample_start >= sample);
+        let in_force = state.retunes.iter().rposition(|(at, _)| *at < sample);
+        if let Some(index) = in_force {
+            state.retunes.drain(..index);
+        }
+    }
+
+    /// A separate log with what bears on `start..end`, for a file written
+    /// while this one moves on.
+    pub(crate) fn copy_range(&self, start: u64, end: u64) -> RecordingLog {
+        let state = self.state.lock().unwrap();
+        let copy = RecordingLogState {
+            annotations: state.annotations.iter().filter(|a| (start..end).contains(&a.sample_start)).cloned().collect(),
+            retunes: state.retunes.iter().filter(|(at, _)| *at < end).copied().collect(),
+        };
+        RecordingLog { sample_rate: self.sample_rate, state: Arc::new(Mutex::new(copy)) }
+    }
+
+    /// Annotations in `start..end`, moved to count from `start`.
+    fn annotations_within(&self, start: u64, end: u64) -> Vec<SigmfAnnotation> {
+        let state = self.state.lock().unwrap();
+        state.annotations.iter()
+            .filter(|a| (start..end).contains(&a.sample_start))
+            .map(|a| SigmfAnnotation { sample_start: a.sample_start - start, ..a.clone() })
+            .collect()
+    }
+}
+
+/// Encoding of one scalar value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl SampleFormat {
+    fn parse(s: &str) -> Option<SampleFormat> {
         match s {
-            "ci8" => Ok(SigmfDatatype::Ci8),
-            "cu8" => Ok(SigmfDatatype::Cu8),
-            "ci16_le" => Ok(SigmfDatatype::Ci16Le),
-            "ci32_le" => Ok(SigmfDatatype::Ci32Le),
-            "cf32_le" => Ok(SigmfDatatype::Cf32Le),
-            _ => Err(SigmfError::UnsupportedDatatype(s.to_string())),
+            "i8" => Some(SampleFormat::I8),
+            "u8" => Some(SampleFormat::U8),
+            "i16" => Some(SampleFormat::I16),
+            "u16" => Some(SampleFormat::U16),
+            "i32" => Some(SampleFormat::I32),
+            "u32" => Some(SampleFormat::U32),
+            "f32" => Some(SampleFormat::F32),
+            "f64" => Some(SampleFormat::F64),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SampleFormat::I8 => "i8",
+            SampleFormat::U8 => "u8",
+            SampleFormat::I16 => "i16",
+            SampleFormat::U16 => "u16",
+            SampleFormat::I32 => "i32",
+            SampleFormat::U32 => "u32",
+            SampleFormat::F32 => "f32",
+            SampleFormat::F64 => "f64",
         }
     }
+
+    fn size(self) -> usize {
+        match self {
+            SampleFormat::I8 | SampleFormat::U8 => 1,
+            SampleFormat::I16 | SampleFormat::U16 => 2,
+            SampleFormat::I32 | SampleFormat::U32 | SampleFormat::F32 => 4,
+            SampleFormat::F64 => 8,
+        }
+    }
+}
+
+/// A SigMF `core:datatype`, shared with the raw IQ source.
+///
+/// Real data is read as complex with a zero imaginary part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigmfDatatype {
+    pub format: SampleFormat,
+    pub complex: bool,
+    pub big_endian: bool,
+}
+
+impl SigmfDatatype {
+    pub const CI8: SigmfDatatype = SigmfDatatype { format: SampleFormat::I8, complex: true, big_endian: false };
+    pub const CU8: SigmfDatatype = SigmfDatatype { format: SampleFormat::U8, complex: true, big_endian: false };
+    pub const CI16_LE: SigmfDatatype = SigmfDatatype { format: SampleFormat::I16, complex: true, big_endian: false };
+    pub const CI32_LE: SigmfDatatype = SigmfDatatype { format: SampleFormat::I32, complex: true, big_endian: false };
+    pub const CF32_LE: SigmfDatatype = SigmfDatatype { format: SampleFormat::F32, complex: true, big_endian: false };
+
+    /// Parse a datatype such as `cf32_le`, `ri16_be` or `cu8`.
+    pub fn parse(s: &str) -> Result<SigmfDatatype, SigmfError> {
+        let unsupported = || SigmfError::UnsupportedDatatype(s.to_string());
+        let (body, big_endian) = match s.rsplit_once('_') {
+            Some((body, "le")) => (body, false),
+            Some((body, "be")) => (body, true),
+            Some(_) => return Err(unsupported()),
+            None => (s, false),
+        };
+        let complex = match body.as_bytes().first() {
+            Some(b'c') => true,
+            Some(b'r') => false,
+            _ => return Err(unsupported()),
+        };
+        let format = SampleFormat::parse(&body[1..]).ok_or_else(unsupported)?;
+        // Byte order is required for multi-byte values and meaningless for single bytes
+        if (format.size() == 1) != (body.len() == s.len()) {
+            return Err(unsupported());
+        }
+        Ok(SigmfDatatype { format, complex, big_endian })
+    }
+
+    /// The `core:datatype` string.
+    pub fn name(self) -> String {
+        let kind = if self.complex { "c" } else { "r" };
+        match (self.format.size(), self.big_endian) {
+            (1, _) => format!("{}{}", kind, self.format.name()),
+            (_, false) => format!("{}{}_le", kind, self.format.name()),
+            (_, true) => format!("{}{}_be", kind, self.format.name()),
+        }
+    }
+
+    /// The datatype for a bare IQ file extension or format name as used by
+    /// rtl_sdr, inspectrum and friends, e.g. `cu8`, `cs16`, `cf32`.
+    pub fn from_extension(ext: &str) -> Option<SigmfDatatype> {
+        match ext.to_ascii_lowercase().as_str() {
+            "cs8" | "ci8" => Some(SigmfDatatype::CI8),
+            "cu8" | "u8" => Some(SigmfDatatype::CU8),
+            "cs16" | "ci16" | "s16" => Some(SigmfDatatype::CI16_LE),
+            "cs32" | "ci32" => Some(SigmfDatatype::CI32_LE),
+            "cf32" | "fc32" | "cfile" => Some(SigmfDatatype::CF32_LE),
+            _ => None,
+        }
+    }
+
+    /// Bytes per sample, both parts for complex data.
+    pub fn sample_size(self) -> usize {
+        self.format.size() * if self.complex { 2 } else { 1 }
+    }
+
+    /// Decode whole samples from `bytes` into `out`, as many as both hold.
+    /// Integers are scaled to ±1; the datatype is matched once per call.
+    pub fn decode_block(self, bytes: &[u8], out: &mut [Complex32]) -> usize {
+        let be = self.big_endian;
+        let complex = self.complex;
+        let size = self.format.size();
+        macro_rules! convert {
+            ($t:ty, $to_f32:expr) => {{
+                let value = |b: &[u8]| {
+                    let raw = b.try_into().unwrap();
+                    $to_f32(if be { <$t>::from_be_bytes(raw) } else { <$t>::from_le_bytes(raw) })
+                };
+                let samples = out.iter_mut().zip(bytes.chunks_exact(self.sample_size()));
+                if complex {
+                    samples.map(|(x, b)| *x = c32(value(&b[..size]), value(&b[size..]))).count()
+                } else {
+                    samples.map(|(x, b)| *x = c32(value(b), 0.0)).count()
+                }
+            }};
+        }
+        match self.format {
+            SampleFormat::I8 => convert!(i8, |v: i8| v as f32 / 128.0),
+            SampleFormat::U8 => convert!(u8, |v: u8| (v as f32 - 128.0) / 128.0),
+            SampleFormat::I16 => convert!(i16, |v: i16| v as f32 / 32768.0),
+            SampleFormat::U16 => convert!(u16, |v: u16| (v as f32 - 32768.0) / 32768.0),
+            SampleFormat::I32 => convert!(i32, |v: i32| v as f32 / 2147483648.0),
+            SampleFormat::U32 => convert!(u32, |v: u32| ((v as f64 - 2147483648.0) / 2147483648.0) as f32),
+            SampleFormat::F32 => convert!(f32, |v: f32| v),
+            SampleFormat::F64 => convert!(f64, |v: f64| v as f32),
+        }
+    }
+
+    /// Append `samples` to `bytes` in this encoding. Integers saturate
+    /// outside ±1; real datatypes keep only the in-phase part.
+    pub fn encode_block(self, samples: &[Complex32], bytes: &mut Vec<u8>) {
+        let be = self.big_endian;
+        let complex = self.complex;
+        bytes.reserve(samples.len() * self.sample_size());
+        macro_rules! convert {
+            ($from_f32:expr) => {{
+                let mut push = |v: f32| {
+                    let value = $from_f32(v);
+                    bytes.extend(if be { value.to_be_bytes() } else { value.to_le_bytes() });
+                };
+                for x in samples {
+                    push(x.re);
+                    if complex {
+                        push(x.im);
+                    }
+                }
+            }};
+        }
+        // Float to integer `as` casts saturate
+        match self.format {
+            SampleFormat::I8 => convert!(|v: f32| (v * 128.0).round() as i8),
+            SampleFormat::U8 => convert!(|v: f32| (v * 128.0 + 128.0).round() as u8),
+            SampleFormat::I16 => convert!(|v: f32| (v * 32768.0).round() as i16),
+            SampleFormat::U16 => convert!(|v: f32| (v * 32768.0 + 32768.0).round() as u16),
+            SampleFormat::I32 => convert!(|v: f32| (v as f64 * 2147483648.0).round() as i32),
+            SampleFormat::U32 => convert!(|v: f32| (v as f64 * 2147483648.0 + 2147483648.0).round() as u32),
+            SampleFormat::F32 => convert!(|v: f32| v),
+            SampleFormat::F64 => convert!(|v: f32| v as f64),
+        }
+    }
+
+    /// Fill `out` with samples read from `reader`, stopping early only at
+    /// end of stream, and return how many were read. `bytes` is scratch
+    /// space; a trailing partial sample is dropped.
+    pub(crate) fn read_samples(
+        self,
+        reader: &mut impl Read,
+        out: &mut [Complex32],
+        bytes: &mut Vec<u8>,
+    ) -> std::io::Result<usize> {
+        let size = self.sample_size();
+        bytes.resize(out.len() * size, 0);
+        let mut filled = 0;
+        while filled < bytes.len() {
+            match reader.read(&mut bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.decode_block(&bytes[..filled], out))
+    }
+}
+
+/// Offset-binary 8-bit IQ, as RTL-SDR dongles produce it.
+pub(crate) fn cu8_to_complex(i: u8, q: u8) -> Complex32 {
+    c32((i as f32 - 128.0) / 128.0, (q as f32 - 128.0) / 128.0)
+}
+
+/// Inverse of `cu8_to_complex`, saturating outside ±1.
+pub(crate) fn complex_to_cu8(x: Complex32) -> [u8; 2] {
+    let quantize = |v: f32| (v * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+    [quantize(x.re), quantize(x.im)]
+}
+
+/// Digital retune of a recording within its bandwidth.
+pub(crate) struct RecordingTuner {
+    sample_rate: f32,
+    recorded_frequency: f64,
+    shift: Option<Osc>,
+    tuned: f64,
+}
+
+impl RecordingTuner {
+    pub(crate) fn new(sample_rate: f32, recorded_frequency: f64) -> Self {
+        RecordingTuner { sample_rate, recorded_frequency, shift: None, tuned: recorded_frequency }
+    }
+
+    pub(crate) fn tuned(&self) -> f64 {
+        self.tuned
+    }
+
+    /// Shift the recording so `frequency` sits at 0 Hz. With no recorded
+    /// centre frequency, `frequency` is relative to the centre.
+    pub(crate) fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        let offset = frequency - self.recorded_frequency;
+        if offset.abs() > self.sample_rate as f64 / 2.0 {
+            return Err(SourceError::Unsupported(format!(
+                "{:.3} MHz is outside the recorded bandwidth", frequency / 1e6)));
+        }
+        self.shift = (offset != 0.0).then(|| Osc::new(-offset as f32, self.sample_rate));
+        self.tuned = frequency;
+        Ok(())
+    }
+
+    /// The recording moved to a new centre; keep the same offset from it.
+    pub(crate) fn set_recorded_frequency(&mut self, frequency: f64) {
+        self.tuned += frequency - self.recorded_frequency;
+        self.recorded_frequency = frequency;
+    }
+
+    pub(crate) fn apply(&mut self, buf: &mut [Complex32]) {
+        if let Some(shift) = self.shift.as_mut() {
+            buf.iter_mut().for_each(|x| *x *= shift.next());
+        }
+    }
+}
+
+/// Where the sample bytes come from.
+enum SigmfData {
+    File(BufReader<File>),
+    /// The whole data file mapped, and the byte offset of the next sample.
+    Mapped { map: Mmap, next: usize },
 }
 
 pub struct SigmfStreamer {
-    sample_file: BufReader<File>,
+    data: SigmfData,
     datatype: SigmfDatatype,
     sample_rate: f32,
+    data_offset: u64,
+    total_samples: u64,
+    captures: Vec<SigmfCapture>,
+    annotations: Vec<SigmfAnnotation>,
+    /// Index of the next sample to be read.
+    position: u64,
+    /// Index into `captures` of the segment `position` is in.
+    capture: usize,
+    tuner: RecordingTuner,
+    bytes: Vec<u8>,
 }
 
+/// Samples per `read_into` block.
+const SIGMF_BLOCK: usize = 256 * 1024;
+
 impl SigmfStreamer {
     /// Open a SigMF recording. Pass the path to the `.sigmf-meta` file;
     /// the `.sigmf-data` file is discovered automatically.
     pub fn new(meta_path: &str) -> Result<SigmfStreamer, SigmfError> {
-        let meta_path = Path::new(meta_path);
+        Self::open(meta_path, false)
+    }
+
+    /// Open a SigMF recording with its data file memory-mapped, which saves
+    /// a copy per block when replaying from fast storage.
+    pub fn new_mapped(meta_path: &str) -> Result<SigmfStreamer, SigmfError> {
+        Self::open(meta_path, true)
+    }
 
-        let meta_file = File::open(meta_path)
+    fn open(meta_path: &str, mapped: bool) -> Result<SigmfStreamer, SigmfError> {
+        let meta_path = Path::new(meta_path);
+        let meta = std::fs::read(meta_path)
             .map_err(|e| SigmfError::BadFile(format!("{}: {}", meta_path.display(), e)))?;
-        let meta: SigmfMetaFile = serde_json::from_reader(BufReader::new(meta_file))
+        let data_path = meta_path.with_extension("sigmf-data");
+        let data_file = File::open(&data_path)
+            .map_err(|e| SigmfError::BadFile(format!("{}: {}", data_path.display(), e)))?;
+        let data_len = data_file.metadata()
+            .map_err(|e| SigmfError::BadFile(format!("{}: {}", data_path.display(), e)))?
+            .len();
+        Self::from_parts(&meta, data_file, 0, data_len, mapped, &data_path.display().to_string())
+    }
+
+    /// A recording described by the metadata JSON `meta`, whose data is the
+    /// `data_len` bytes from `data_start` in `data_file`. `name` is for errors.
+    pub(crate) fn from_parts(
+        meta: &[u8],
+        mut data_file: File,
+        data_start: u64,
+        data_len: u64,
+        mapped: bool,
+        name: &str,
+    ) -> Result<SigmfStreamer, SigmfError> {
+        let meta: SigmfMetaFile = serde_json::from_slice(meta)
             .map_err(|e| SigmfError::BadFile(format!("invalid metadata: {}", e)))?;
 
         let datatype = SigmfDatatype::parse(&meta.global.datatype)?;
         let sample_rate = meta.global.sample_rate as f32;
+        let mut captures = meta.captures;
+        captures.sort_by_key(|c| c.sample_start);
+        let mut annotations = meta.annotations;
+        annotations.sort_by_key(|a| a.sample_start);
 
-        let data_path = meta_path.with_extension("sigmf-data");
-        let data_file = File::open(&data_path)
-            .map_err(|e| SigmfError::BadFile(format!("{}: {}", data_path.display(), e)))?;
+        let data_offset = data_start + meta.global.offset;
+        data_file.seek(SeekFrom::Start(data_offset))
+            .map_err(|e| SigmfError::BadFile(format!("{}: {}", name, e)))?;
+        let total_samples = data_len.saturating_sub(meta.global.offset) / datatype.sample_size() as u64;
+        let data = if mapped {
+            // SAFETY: the recording is opened read-only and nothing in this
+            // process writes it; truncating it underneath us is on the user
+            let map = unsafe { Mmap::map(&data_file) }
+                .map_err(|e| SigmfError::BadFile(format!("{}: {}", name, e)))?;
+            SigmfData::Mapped { map, next: data_offset as usize }
+        } else {
+            SigmfData::File(BufReader::new(data_file))
+        };
 
+        let recorded_frequency = captures.first().and_then(|c| c.frequency).unwrap_or(0.0);
         Ok(SigmfStreamer {
-            sample_file: BufReader::new(data_file),
+            data,
             datatype,
             sample_rate,
+            data_offset,
+            total_samples,
+            captures,
+            annotations,
+            position: 0,
+            capture: 0,
+            tuner: RecordingTuner::new(sample_rate, recorded_frequency),
+            bytes: Vec::new(),
         })
     }
 
@@ -91,137 +543,336 @@ impl SigmfStreamer {
         self.sample_rate
     }
 
-    fn read_ci8(&mut self) -> Option<Complex32> {
-        let mut buf = [0u8; 2];
-        self.sample_file.read_exact(&mut buf).ok()?;
-        Some(c32(buf[0] as i8 as f32 / 128.0, buf[1] as i8 as f32 / 128.0))
+    pub fn datatype(&self) -> SigmfDatatype {
+        self.datatype
+    }
+
+    pub fn captures(&self) -> &[SigmfCapture] {
+        &self.captures
+    }
+
+    pub fn annotations(&self) -> &[SigmfAnnotation] {
+        &self.annotations
+    }
+
+    /// Samples in the data file.
+    pub fn total_samples(&self) -> u64 {
+        self.total_samples
+    }
+
+    /// Index of the next sample to be read.
+    pub fn position(&self) -> u64 {
+        self.position
     }
 
-    fn read_cu8(&mut self) -> Option<Complex32> {
-        let mut buf = [0u8; 2];
-        self.sample_file.read_exact(&mut buf).ok()?;
-        Some(c32(
-            (buf[0] as f32 - 128.0) / 128.0,
-            (buf[1] as f32 - 128.0) / 128.0,
-        ))
+    /// The capture segment the next sample belongs to.
+    pub fn current_capture(&self) -> Option<&SigmfCapture> {
+        self.captures.get(self.capture)
     }
 
-    fn read_ci16_le(&mut self) -> Option<Complex32> {
-        let mut buf = [0u8; 4];
-        self.sample_file.read_exact(&mut buf).ok()?;
-        let re = i16::from_le_bytes([buf[0], buf[1]]);
-        let im = i16::from_le_bytes([buf[2], buf[3]]);
-        Some(c32(re as f32 / 32768.0, im as f32 / 32768.0))
+    /// Continue reading from sample `sample`, counted from the start of the recording.
+    pub fn seek_to_sample(&mut self, sample: u64) -> Result<(), SigmfError> {
+        if sample > self.total_samples {
+            return Err(SigmfError::OutOfRange(format!(
+                "sample {} is past the end ({} samples)", sample, self.total_samples)));
+        }
+        let byte = self.data_offset + sample * self.datatype.sample_size() as u64;
+        match &mut self.data {
+            SigmfData::File(file) => {
+                file.seek(SeekFrom::Start(byte)).map_err(|e| SigmfError::BadFile(format!("seek error: {}", e)))?;
+            }
+            SigmfData::Mapped { next, .. } => *next = byte as usize,
+        }
+        self.position = sample;
+        self.update_capture();
+        Ok(())
     }
 
-    fn read_ci32_le(&mut self) -> Option<Complex32> {
-        let mut buf = [0u8; 8];
-        self.sample_file.read_exact(&mut buf).ok()?;
-        let re = i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
-        let im = i32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
-        Some(c32(re as f32 / 2147483648.0, im as f32 / 2147483648.0))
+    /// Continue reading from `seconds` into the recording.
+    pub fn seek_to_time(&mut self, seconds: f64) -> Result<(), SigmfError> {
+        if seconds < 0.0 {
+            return Err(SigmfError::OutOfRange(format!("{}s is before the start", seconds)));
+        }
+        self.seek_to_sample((seconds * self.sample_rate as f64).round() as u64)
     }
 
-    fn read_cf32_le(&mut self) -> Option<Complex32> {
-        let mut buf = [0u8; 8];
-        self.sample_file.read_exact(&mut buf).ok()?;
-        let re = f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
-        let im = f32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
-        Some(c32(re, im))
+    /// Move to the capture segment containing `position`, following its frequency.
+    fn update_capture(&mut self) {
+        let capture = self.captures.iter().rposition(|c| c.sample_start <= self.position).unwrap_or(0);
+        if capture != self.capture {
+            self.capture = capture;
+            if let Some(frequency) = self.captures[capture].frequency {
+                self.tuner.set_recorded_frequency(frequency);
+            }
+        }
+    }
+
+    /// Samples left before the next capture segment starts.
+    fn samples_to_next_capture(&self) -> Option<u64> {
+        self.captures.get(self.capture + 1).map(|c| c.sample_start - self.position)
+    }
+}
+
+impl SigmfStreamer {
+    /// Fill `out` with the next samples and return how many were read; 0 at
+    /// the end of the recording.
+    ///
+    /// Blocks stop at capture boundaries, so the centre frequency holds for
+    /// a whole block.
+    pub fn read_block(&mut self, out: &mut [Complex32]) -> std::io::Result<usize> {
+        self.update_capture();
+        // The data may be followed by more of an archive
+        let left = self.total_samples.saturating_sub(self.position);
+        let max = self.samples_to_next_capture().map_or(left, |n| n.min(left)).min(out.len() as u64) as usize;
+        let out = &mut out[..max];
+        let n = match &mut self.data {
+            SigmfData::File(file) => self.datatype.read_samples(file, out, &mut self.bytes)?,
+            SigmfData::Mapped { map, next } => {
+                let start = (*next).min(map.len());
+                let n = self.datatype.decode_block(&map[start..], out);
+                *next = start + n * self.datatype.sample_size();
+                n
+            }
+        };
+        self.position += n as u64;
+        self.tuner.apply(&mut out[..n]);
+        Ok(n)
     }
 }
 
 impl Iterator for SigmfStreamer {
     type Item = Complex32;
 
+    /// Samples as recorded, without the retune shift.
     fn next(&mut self) -> Option<Self::Item> {
-        match self.datatype {
-            SigmfDatatype::Ci8 => self.read_ci8(),
-            SigmfDatatype::Cu8 => self.read_cu8(),
-            SigmfDatatype::Ci16Le => self.read_ci16_le(),
-            SigmfDatatype::Ci32Le => self.read_ci32_le(),
-            SigmfDatatype::Cf32Le => self.read_cf32_le(),
+        if self.position >= self.total_samples {
+            return None;
         }
+        let size = self.datatype.sample_size();
+        let mut sample = [Complex32::new(0.0, 0.0)];
+        let n = match &mut self.data {
+            SigmfData::File(file) => {
+                let mut buf = [0u8; 16];
+                file.read_exact(&mut buf[..size]).ok()?;
+                self.datatype.decode_block(&buf[..size], &mut sample)
+            }
+            SigmfData::Mapped { map, next } => {
+                let n = self.datatype.decode_block(map.get(*next..)?, &mut sample);
+                *next += n * size;
+                n
+            }
+        };
+        self.position += n as u64;
+        (n == 1).then_some(sample[0])
     }
 }
 
-/// SigMF recording writer. Writes cf32_le IQ data and a compliant metadata file.
+impl IqSource for SigmfStreamer {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn center_frequency(&self) -> f64 {
+        self.tuner.tuned()
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        buf.resize(SIGMF_BLOCK, Complex32::new(0.0, 0.0));
+        let n = self.read_block(buf).map_err(|e| SourceError::Read(e.to_string()))?;
+        buf.truncate(n);
+        Ok(n)
+    }
+
+    /// Shift the recording so `frequency` sits at 0 Hz. With no recorded
+    /// centre frequency, `frequency` is relative to the centre.
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        self.tuner.retune(frequency)
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        Ok(())
+    }
+}
+
+/// SigMF recording writer. Writes IQ data in any datatype and keeps a
+/// compliant metadata file beside it from the start.
 pub struct SigmfWriter {
     data_file: std::io::BufWriter<File>,
     meta_path: std::path::PathBuf,
+    datatype: SigmfDatatype,
     sample_rate: f64,
     frequency: f64,
     hw: String,
     samples_written: u64,
-    datetime: String,
+    start_secs: u64,
+    captures: Vec<SigmfCapture>,
+    log: RecordingLog,
+    /// Where this file's first sample sits in the log's timeline.
+    first_sample: u64,
+    /// Retunes before this point in the log have been made captures.
+    retunes_from: u64,
+    bytes: Vec<u8>,
+    meta_written: std::time::Instant,
 }
 
+/// How often the metadata is rewritten while recording.
+const META_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
 impl SigmfWriter {
     /// Create a new SigMF recording. `base_path` is the path without extension;
-    /// `.sigmf-data` and `.sigmf-meta` will be appended.
-    pub fn new(base_path: &str, sample_rate: f64, frequency: f64, hw: &str) -> Result<SigmfWriter, SigmfError> {
+    /// `.sigmf-data` and `.sigmf-meta` will be appended. Samples are stored
+    /// as `datatype`, e.g. the source's own `ci16_le` or `cu8`.
+    pub fn new(base_path: &str, sample_rate: f64, frequency: f64, hw: &str, datatype: SigmfDatatype) -> Result<SigmfWriter, SigmfError> {
+        SigmfWriter::with_log(base_path, sample_rate, frequency, hw, datatype, RecordingLog::new(sample_rate), 0)
+    }
+
+    /// A recording of the samples from `first_sample` on in `log`'s timeline.
+    pub(crate) fn with_log(
+        base_path: &str,
+        sample_rate: f64,
+        frequency: f64,
+        hw: &str,
+        datatype: SigmfDatatype,
+        log: RecordingLog,
+        first_sample: u64,
+    ) -> Result<SigmfWriter, SigmfError> {
         let data_path = format!("{}.sigmf-data", base_path);
         let data_file = File::create(&data_path)
             .map_err(|e| SigmfError::BadFile(format!("{}: {}", data_path, e)))?;
 
-        // ISO 8601 UTC timestamp
-        let now = std::time::SystemTime::now()
+        let start_secs = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
-        let datetime = format_utc_timestamp(now);
 
-        Ok(SigmfWriter {
+        let mut writer = SigmfWriter {
             data_file: std::io::BufWriter::new(data_file),
             meta_path: std::path::PathBuf::from(format!("{}.sigmf-meta", base_path)),
+            datatype,
             sample_rate,
             frequency,
             hw: hw.to_string(),
             samples_written: 0,
-            datetime,
-        })
+            start_secs,
+            captures: vec![SigmfCapture {
+                sample_start: 0,
+                frequency: Some(frequency),
+                datetime: Some(format_utc_timestamp(start_secs)),
+            }],
+            log,
+            first_sample,
+            retunes_from: first_sample,
+            bytes: Vec::new(),
+            meta_written: std::time::Instant::now(),
+        };
+        // A recording that never finalizes still has metadata
+        writer.write_meta()?;
+        Ok(writer)
+    }
+
+    /// A handle for adding annotations and retunes while recording.
+    pub fn log(&self) -> RecordingLog {
+        self.log.clone()
+    }
+
+    pub fn samples_written(&self) -> u64 {
+        self.samples_written
     }
 
     /// Write a batch of IQ samples.
     pub fn write_samples(&mut self, samples: &[Complex32]) -> Result<(), SigmfError> {
+        let mut bytes = std::mem::take(&mut self.bytes);
+        bytes.clear();
+        self.datatype.encode_block(samples, &mut bytes);
+        let result = self.write_encoded(&bytes);
+        self.bytes = bytes;
+        result
+    }
+
+    /// Write whole samples already encoded in this file's datatype.
+    pub(crate) fn write_encoded(&mut self, bytes: &[u8]) -> Result<(), SigmfError> {
         use std::io::Write;
-        for s in samples {
-            self.data_file.write_all(&s.re.to_le_bytes())
-                .map_err(|e| SigmfError::BadFile(format!("write error: {}", e)))?;
-            self.data_file.write_all(&s.im.to_le_bytes())
-                .map_err(|e| SigmfError::BadFile(format!("write error: {}", e)))?;
+        self.data_file.write_all(bytes)
+            .map_err(|e| SigmfError::BadFile(format!("write error: {}", e)))?;
+        self.samples_written += (bytes.len() / self.datatype.sample_size()) as u64;
+
+        let new_capture = self.take_retunes();
+        if new_capture || self.meta_written.elapsed() >= META_INTERVAL {
+            self.data_file.flush()
+                .map_err(|e| SigmfError::BadFile(format!("flush error: {}", e)))?;
+            self.write_meta()?;
         }
-        self.samples_written += samples.len() as u64;
         Ok(())
     }
 
-    /// Finalize the recording: flush data and write the metadata file.
-    pub fn finalize(mut self) -> Result<(), SigmfError> {
-        use std::io::Write;
-        self.data_file.flush()
-            .map_err(|e| SigmfError::BadFile(format!("flush error: {}", e)))?;
+    /// Start a capture segment for each retune within the samples written
+    /// so far. Returns whether any was added.
+    fn take_retunes(&mut self) -> bool {
+        let state = self.log.state.lock().unwrap();
+        let end = self.first_sample + self.samples_written;
+        let mut added = false;
+        let retunes = state.retunes.iter().filter(|(at, _)| (self.retunes_from..end).contains(at));
+        for &(at, frequency) in retunes {
+            let sample = at - self.first_sample;
+            let last = self.captures.last_mut().unwrap();
+            if last.frequency == Some(frequency) {
+                continue;
+            }
+            if last.sample_start == sample {
+                last.frequency = Some(frequency);
+            } else {
+                let offset = (sample as f64 / self.sample_rate) as u64;
+                self.captures.push(SigmfCapture {
+                    sample_start: sample,
+                    frequency: Some(frequency),
+                    datetime: Some(format_utc_timestamp(self.start_secs + offset)),
+                });
+            }
+            added = true;
+        }
+        self.retunes_from = end;
+        added
+    }
+
+    /// Write the metadata file as it stands, replacing the old one in a
+    /// single rename so a reader never sees half of it.
+    fn write_meta(&mut self) -> Result<(), SigmfError> {
+        // Events after the last sample written happened outside the recording
+        let mut annotations = self.log.annotations_within(self.first_sample, self.first_sample + self.samples_written);
+        annotations.sort_by_key(|a| a.sample_start);
 
         let meta = serde_json::json!({
             "global": {
-                "core:datatype": "cf32_le",
+                "core:datatype": self.datatype.name(),
                 "core:sample_rate": self.sample_rate,
                 "core:version": "1.2.0",
                 "core:hw": self.hw,
                 "core:recorder": "rradio",
                 "core:description": format!("FM recording at {:.1} MHz", self.frequency / 1e6),
             },
-            "captures": [{
-                "core:sample_start": 0,
-                "core:frequency": self.frequency,
-                "core:datetime": self.datetime,
-            }],
-            "annotations": []
+            "captures": self.captures,
+            "annotations": annotations,
         });
 
-        let meta_file = File::create(&self.meta_path)
-            .map_err(|e| SigmfError::BadFile(format!("{}: {}", self.meta_path.display(), e)))?;
+        let tmp_path = self.meta_path.with_extension("sigmf-meta.tmp");
+        let meta_file = File::create(&tmp_path)
+            .map_err(|e| SigmfError::BadFile(format!("{}: {}", tmp_path.display(), e)))?;
         serde_json::to_writer_pretty(std::io::BufWriter::new(meta_file), &meta)
             .map_err(|e| SigmfError::BadFile(format!("metadata write error: {}", e)))?;
+        std::fs::rename(&tmp_path, &self.meta_path)
+            .map_err(|e| SigmfError::BadFile(format!("{}: {}", self.meta_path.display(), e)))?;
+        self.meta_written = std::time::Instant::now();
+        Ok(())
+    }
+
+    /// Finalize the recording: flush data and write the metadata file.
+    pub fn finalize(mut self) -> Result<(), SigmfError> {
+        use std::io::Write;
+        self.data_file.flush()
+            .map_err(|e| SigmfError::BadFile(format!("flush error: {}", e)))?;
+
+        self.take_retunes();
+        self.write_meta()?;
 
         let duration = self.samples_written as f64 / self.sample_rate;
         eprintln!("SigMF: wrote {} samples ({:.1}s) to {}",
@@ -262,6 +913,7 @@ fn format_utc_timestamp(epoch_secs: u64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::TempDir;
 
     #[test]
     fn try_stream_file() -> Result<(), SigmfError> {
@@ -271,4 +923,148 @@ mod tests {
         assert_ne!(sum, c32(0.0, 0.0));
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_datatypes() {
+        assert_eq!(SigmfDatatype::parse("cu8").unwrap(), SigmfDatatype::CU8);
+        assert_eq!(SigmfDatatype::parse("cf32_le").unwrap(), SigmfDatatype::CF32_LE);
+        for name in ["ri16_le", "cf32_be", "ru8", "cf64_le", "cu16_be"] {
+            assert_eq!(SigmfDatatype::parse(name).unwrap().name(), name);
+        }
+        for name in ["cu8_le", "ci16", "xi16_le", "cf32_me", "ci64_le"] {
+            assert!(SigmfDatatype::parse(name).is_err(), "{} accepted", name);
+        }
+
+        let mut out = [c32(0.0, 0.0); 2];
+        let be = SigmfDatatype::parse("ci16_be").unwrap();
+        assert_eq!(be.decode_block(&[0x40, 0x00, 0xc0, 0x00, 0x7f], &mut out), 1);
+        assert_eq!(out[0], c32(0.5, -0.5));
+        let real = SigmfDatatype::parse("rf32_le").unwrap();
+        assert_eq!(real.sample_size(), 4);
+        let bytes: Vec<u8> = [0.25f32, -1.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(real.decode_block(&bytes, &mut out), 2);
+        assert_eq!(out, [c32(0.25, 0.0), c32(-1.0, 0.0)]);
+        assert_eq!(SigmfDatatype::CU8.decode_block(&[0, 255, 128, 64], &mut out), 2);
+        assert_eq!(out[0], cu8_to_complex(0, 255));
+        assert_eq!(out[1], cu8_to_complex(128, 64));
+    }
+
+    #[test]
+    fn test_captures_and_seek() {
+        // Ten samples counting up, retuned from 100 to 101 MHz at sample 6
+        let dir = TempDir::new("sigmf-seek");
+        let base = dir.path().join("tone");
+        let data: Vec<u8> = (0..10).flat_map(|i| [(i as f32).to_be_bytes(), 0f32.to_be_bytes()]).flatten().collect();
+        std::fs::write(base.with_extension("sigmf-data"), data).unwrap();
+        let meta = serde_json::json!({
+            "global": { "core:datatype": "cf32_be", "core:sample_rate": 1000.0, "core:version": "1.2.0" },
+            "captures": [
+                { "core:sample_start": 6, "core:frequency": 101e6 },
+                { "core:sample_start": 0, "core:frequency": 100e6, "core:datetime": "2025-09-20T10:15:00Z" },
+            ],
+            "annotations": [{ "core:sample_start": 2, "core:sample_count": 3, "core:label": "burst" }],
+        });
+        let meta_path = base.with_extension("sigmf-meta");
+        std::fs::write(&meta_path, meta.to_string()).unwrap();
+
+        let mut file = SigmfStreamer::new(meta_path.to_str().unwrap()).unwrap();
+        assert_eq!(file.total_samples(), 10);
+        assert_eq!(file.captures()[0].datetime.as_deref(), Some("2025-09-20T10:15:00Z"));
+        assert_eq!(file.annotations()[0].label.as_deref(), Some("burst"));
+        assert_eq!(file.center_frequency(), 100e6);
+
+        // Tuned 10 Hz above centre, the offset carries across the retune
+        file.retune(100e6 + 10.0).unwrap();
+        file.seek_to_time(0.004).unwrap();
+        let mut buf = Vec::new();
+        assert_eq!(file.read_into(&mut buf).unwrap(), 2);
+        assert!((buf[0].norm() - 4.0).abs() < 1e-5);
+        assert_eq!(file.read_into(&mut buf).unwrap(), 4);
+        assert!((buf[0].norm() - 6.0).abs() < 1e-5);
+        assert_eq!(file.center_frequency(), 101e6 + 10.0);
+        assert_eq!(file.read_into(&mut buf).unwrap(), 0);
+
+        file.seek_to_sample(1).unwrap();
+        assert_eq!(file.current_capture().unwrap().frequency, Some(100e6));
+        assert_eq!(file.next(), Some(c32(1.0, 0.0)));
+        assert!(file.seek_to_sample(11).is_err());
+
+        // Mapped reads match, blocks still stopping at the capture boundary
+        let mut mapped = SigmfStreamer::new_mapped(meta_path.to_str().unwrap()).unwrap();
+        let mut out = [c32(0.0, 0.0); 16];
+        assert_eq!(mapped.read_block(&mut out).unwrap(), 6);
+        assert_eq!(out[5], c32(5.0, 0.0));
+        mapped.seek_to_sample(8).unwrap();
+        assert_eq!(mapped.read_block(&mut out).unwrap(), 2);
+        assert_eq!(&out[..2], &[c32(8.0, 0.0), c32(9.0, 0.0)]);
+        assert_eq!(mapped.read_block(&mut out).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_writer_annotations() {
+        let dir = TempDir::new("sigmf-annotate");
+        let base = dir.file("marks");
+        let mut writer = SigmfWriter::new(&base, 1000.0, 96.1e6, "test", SigmfDatatype::CF32_LE).unwrap();
+        let log = writer.log();
+        std::thread::spawn(move || {
+            log.mark_at(0.5, "stereo", None);
+            log.mark(100, "PI C201", None);
+            log.mark(5000, "reconnect", Some("after the recording ended".to_string()));
+        }).join().unwrap();
+        writer.write_samples(&[c32(0.0, 0.0); 1000]).unwrap();
+        writer.finalize().unwrap();
+
+        let meta_path = format!("{}.sigmf-meta", base);
+        let file = SigmfStreamer::new(&meta_path).unwrap();
+        let marks: Vec<(u64, Option<&str>)> = file.annotations().iter()
+            .map(|a| (a.sample_start, a.label.as_deref()))
+            .collect();
+        assert_eq!(marks, vec![(100, Some("PI C201")), (500, Some("stereo"))]);
+    }
+
+    #[test]
+    fn test_writer_datatype_and_captures() {
+        let dir = TempDir::new("sigmf-datatype");
+        let base = dir.file("cu8");
+        let meta_path = format!("{}.sigmf-meta", base);
+        let mut writer = SigmfWriter::new(&base, 1000.0, 100e6, "test", SigmfDatatype::CU8).unwrap();
+        let log = writer.log();
+        // Metadata exists before the recording is finalized
+        assert_eq!(SigmfStreamer::new(&meta_path).unwrap().total_samples(), 0);
+
+        let samples: Vec<Complex32> = (0..8).map(|i| c32(i as f32 / 8.0, -0.5)).collect();
+        log.retune(4, 101e6);
+        log.retune(6, 101e6);
+        writer.write_samples(&samples[..5]).unwrap();
+        log.retune(6, 102e6);
+        writer.write_samples(&samples[5..]).unwrap();
+        // Written at the new capture, without waiting for finalize
+        assert_eq!(SigmfStreamer::new(&meta_path).unwrap().captures().len(), 3);
+        writer.finalize().unwrap();
+
+        assert_eq!(std::fs::metadata(format!("{}.sigmf-data", base)).unwrap().len(), 16);
+        let mut file = SigmfStreamer::new(&meta_path).unwrap();
+        assert_eq!(file.datatype(), SigmfDatatype::CU8);
+        let captures: Vec<(u64, Option<f64>)> = file.captures().iter().map(|c| (c.sample_start, c.frequency)).collect();
+        assert_eq!(captures, vec![(0, Some(100e6)), (4, Some(101e6)), (6, Some(102e6))]);
+        let mut out = [c32(0.0, 0.0); 8];
+        assert_eq!(file.read_block(&mut out).unwrap(), 4);
+        assert_eq!(out[2], c32(0.25, -0.5));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let samples = [c32(0.5, -0.25), c32(-1.0, 0.75), c32(2.0, -2.0)];
+        for name in ["ci8", "cu8", "ci16_le", "cu16_be", "ci32_be", "cu32_le", "cf32_be", "cf64_le"] {
+            let datatype = SigmfDatatype::parse(name).unwrap();
+            let mut bytes = Vec::new();
+            datatype.encode_block(&samples, &mut bytes);
+            assert_eq!(bytes.len(), samples.len() * datatype.sample_size());
+            let mut out = [c32(0.0, 0.0); 3];
+            assert_eq!(datatype.decode_block(&bytes, &mut out), 3);
+            assert_eq!(&out[..2], &samples[..2], "{}", name);
+            // Out of range saturates in integer formats
+            assert!(out[2].re >= 0.99 && out[2].im <= -1.0, "{} {}", name, out[2]);
+        }
+    }
+}
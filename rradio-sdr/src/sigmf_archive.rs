@@ -0,0 +1,390 @@
+//! SigMF archives and Collections, for shipping sets of recordings as one file.
+//!
+//! An archive (`.sigmf`) is an uncompressed tar holding a directory of
+//! `.sigmf-meta` / `.sigmf-data` pairs, plus a `.sigmf-collection` when it
+//! has more than one. Sample data is read straight out of the tar, so an
+//! archived recording streams, seeks and maps like a loose one.
+//!
+//! A collection lists recordings by name with the SHA-512 of each one's
+//! metadata, which is checked when the recording is opened.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::sigmf::{SigmfError, SigmfStreamer};
+
+/// File extension of an archive.
+pub const ARCHIVE_EXTENSION: &str = "sigmf";
+
+/// A recording as a collection lists it.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SigmfStreamRef {
+    /// Path of the recording without extension, relative to the collection.
+    pub name: String,
+    /// Lowercase hex SHA-512 of the recording's `.sigmf-meta` file.
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SigmfCollection {
+    #[serde(rename = "core:version")]
+    pub version: String,
+    #[serde(rename = "core:description", default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "core:streams", default)]
+    pub streams: Vec<SigmfStreamRef>,
+    /// Directory the stream names are relative to.
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SigmfCollectionFile {
+    collection: SigmfCollection,
+}
+
+fn sha512_hex(bytes: &[u8]) -> String {
+    Sha512::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_meta(path: &Path) -> Result<Vec<u8>, SigmfError> {
+    std::fs::read(path).map_err(|e| SigmfError::BadFile(format!("{}: {}", path.display(), e)))
+}
+
+/// A relative path as a stream name, with `/` between its parts.
+fn stream_name(path: &Path) -> String {
+    path.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/")
+}
+
+/// Drop a `.sigmf-meta` or `.sigmf-data` extension, if there is one.
+fn base_path(path: &str) -> &str {
+    path.strip_suffix(".sigmf-meta").or_else(|| path.strip_suffix(".sigmf-data")).unwrap_or(path)
+}
+
+impl SigmfCollection {
+    /// An empty collection of recordings in `dir`.
+    pub fn new(dir: &str, description: Option<&str>) -> SigmfCollection {
+        SigmfCollection {
+            version: "1.2.0".to_string(),
+            description: description.map(str::to_string),
+            streams: Vec::new(),
+            dir: PathBuf::from(dir),
+        }
+    }
+
+    /// Read a `.sigmf-collection` file.
+    pub fn open(path: &str) -> Result<SigmfCollection, SigmfError> {
+        let bytes = read_meta(Path::new(path))?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        SigmfCollection::parse(&bytes, dir)
+    }
+
+    fn parse(bytes: &[u8], dir: &Path) -> Result<SigmfCollection, SigmfError> {
+        let file: SigmfCollectionFile = serde_json::from_slice(bytes)
+            .map_err(|e| SigmfError::BadFile(format!("invalid collection: {}", e)))?;
+        Ok(SigmfCollection { dir: dir.to_path_buf(), ..file.collection })
+    }
+
+    fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec_pretty(&SigmfCollectionFile { collection: self.clone() }).unwrap()
+    }
+
+    /// List the recording at `path` (with or without its extension), which
+    /// must be inside the collection's directory.
+    pub fn add(&mut self, path: &str) -> Result<(), SigmfError> {
+        let base = Path::new(base_path(path));
+        let name = base.strip_prefix(&self.dir)
+            .map_err(|_| SigmfError::BadFile(format!("{} is outside {}", base.display(), self.dir.display())))?;
+        let meta = read_meta(&base.with_extension("sigmf-meta"))?;
+        self.streams.push(SigmfStreamRef { name: stream_name(name), hash: sha512_hex(&meta) });
+        Ok(())
+    }
+
+    /// Write the collection file to `path`.
+    pub fn save(&self, path: &str) -> Result<(), SigmfError> {
+        std::fs::write(path, self.to_json()).map_err(|e| SigmfError::BadFile(format!("{}: {}", path, e)))
+    }
+
+    fn stream(&self, name: &str) -> Option<&SigmfStreamRef> {
+        self.streams.iter().find(|s| s.name == name)
+    }
+
+    /// Open the recording called `name`, or the first one.
+    pub fn open_recording(&self, name: Option<&str>, mapped: bool) -> Result<SigmfStreamer, SigmfError> {
+        let stream = match name {
+            Some(name) => self.stream(name),
+            None => self.streams.first(),
+        }.ok_or_else(|| SigmfError::BadFile(format!("no recording '{}' in the collection", name.unwrap_or_default())))?;
+        let meta_path = self.dir.join(format!("{}.sigmf-meta", stream.name));
+        check_hash(stream, &read_meta(&meta_path)?)?;
+        let meta_path = meta_path.to_string_lossy();
+        if mapped { SigmfStreamer::new_mapped(&meta_path) } else { SigmfStreamer::new(&meta_path) }
+    }
+
+    /// Open each listed recording in turn.
+    pub fn recordings(&self) -> impl Iterator<Item = Result<SigmfStreamer, SigmfError>> + '_ {
+        self.streams.iter().map(|stream| self.open_recording(Some(&stream.name), false))
+    }
+}
+
+fn check_hash(stream: &SigmfStreamRef, meta: &[u8]) -> Result<(), SigmfError> {
+    if !stream.hash.eq_ignore_ascii_case(&sha512_hex(meta)) {
+        return Err(SigmfError::BadFile(format!("{}: metadata does not match the collection's hash", stream.name)));
+    }
+    Ok(())
+}
+
+/// One recording's metadata and where its data sits in the archive.
+struct ArchiveMember {
+    /// Path without extension inside the archive's directory, as a
+    /// collection names it.
+    name: String,
+    meta: Option<Vec<u8>>,
+    data: Option<(u64, u64)>,
+}
+
+/// `path` inside the archive without the archive's own directory or the
+/// extension, e.g. `corpus/site-a/pass.sigmf-meta` is `site-a/pass`.
+fn member_name(path: &Path) -> String {
+    let mut components = path.components();
+    if path.components().count() > 1 {
+        components.next();
+    }
+    stream_name(&components.as_path().with_extension(""))
+}
+
+/// The member called `name`, added if this is the first of its files.
+fn member(members: &mut Vec<ArchiveMember>, name: String) -> &mut ArchiveMember {
+    let index = members.iter().position(|m| m.name == name).unwrap_or_else(|| {
+        members.push(ArchiveMember { name, meta: None, data: None });
+        members.len() - 1
+    });
+    &mut members[index]
+}
+
+pub struct SigmfArchive {
+    path: PathBuf,
+    members: Vec<ArchiveMember>,
+    collection: Option<SigmfCollection>,
+}
+
+impl SigmfArchive {
+    /// Index a `.sigmf` archive. Only metadata is read; data is left in place.
+    pub fn open(path: &str) -> Result<SigmfArchive, SigmfError> {
+        let bad_file = |e: &dyn std::fmt::Display| SigmfError::BadFile(format!("{}: {}", path, e));
+        let file = File::open(path).map_err(|e| bad_file(&e))?;
+        let mut archive = tar::Archive::new(file);
+
+        let mut members: Vec<ArchiveMember> = Vec::new();
+        let mut collection = None;
+        for entry in archive.entries_with_seek().map_err(|e| bad_file(&e))? {
+            let mut entry = entry.map_err(|e| bad_file(&e))?;
+            let entry_path = entry.path().map_err(|e| bad_file(&e))?.into_owned();
+            let Some(ext) = entry_path.extension().and_then(|e| e.to_str()) else { continue };
+            let name = member_name(&entry_path);
+            match ext {
+                "sigmf-meta" => {
+                    let mut meta = Vec::new();
+                    entry.read_to_end(&mut meta).map_err(|e| bad_file(&e))?;
+                    member(&mut members, name).meta = Some(meta);
+                }
+                "sigmf-data" => {
+                    let data = (entry.raw_file_position(), entry.size());
+                    member(&mut members, name).data = Some(data);
+                }
+                "sigmf-collection" => {
+                    let mut bytes = Vec::new();
+                    entry.read_to_end(&mut bytes).map_err(|e| bad_file(&e))?;
+                    collection = Some(SigmfCollection::parse(&bytes, Path::new(""))?);
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(member) = members.iter().find(|m| m.meta.is_none() || m.data.is_none()) {
+            let missing = if member.meta.is_none() { "sigmf-meta" } else { "sigmf-data" };
+            return Err(bad_file(&format!("{} has no .{}", member.name, missing)));
+        }
+        if members.is_empty() {
+            return Err(bad_file(&"no recordings"));
+        }
+        Ok(SigmfArchive { path: PathBuf::from(path), members, collection })
+    }
+
+    /// Names of the recordings, in the order they are stored.
+    pub fn names(&self) -> Vec<&str> {
+        self.members.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    pub fn collection(&self) -> Option<&SigmfCollection> {
+        self.collection.as_ref()
+    }
+
+    /// Open the recording called `name`, or the first one.
+    pub fn open_recording(&self, name: Option<&str>, mapped: bool) -> Result<SigmfStreamer, SigmfError> {
+        let member = match name {
+            Some(name) => self.members.iter().find(|m| m.name == name)
+                .ok_or_else(|| SigmfError::BadFile(format!("{}: no recording '{}'", self.path.display(), name)))?,
+            None => &self.members[0],
+        };
+        let (meta, (data_start, data_len)) = (member.meta.as_deref().unwrap(), member.data.unwrap());
+        if let Some(stream) = self.collection.as_ref().and_then(|c| c.stream(&member.name)) {
+            check_hash(stream, meta)?;
+        }
+        let file = File::open(&self.path)
+            .map_err(|e| SigmfError::BadFile(format!("{}: {}", self.path.display(), e)))?;
+        let name = format!("{}/{}", self.path.display(), member.name);
+        SigmfStreamer::from_parts(meta, file, data_start, data_len, mapped, &name)
+    }
+
+    /// Open each recording in turn.
+    pub fn recordings(&self) -> impl Iterator<Item = Result<SigmfStreamer, SigmfError>> + '_ {
+        self.members.iter().map(|m| self.open_recording(Some(&m.name), false))
+    }
+
+    /// Pack the recordings at `paths` into a new archive at `path`. With
+    /// more than one, a collection listing them is included.
+    pub fn create(path: &str, paths: &[&str], description: Option<&str>) -> Result<(), SigmfError> {
+        let bad_file = |e: &dyn std::fmt::Display| SigmfError::BadFile(format!("{}: {}", path, e));
+        let dir = Path::new(path).file_stem().map(|s| s.to_string_lossy().into_owned())
+            .ok_or_else(|| bad_file(&"archive needs a file name"))?;
+        let file = File::create(path).map_err(|e| bad_file(&e))?;
+        let mut builder = tar::Builder::new(file);
+        builder.mode(tar::HeaderMode::Deterministic);
+
+        let mut collection = SigmfCollection::new("", description);
+        for recording in paths {
+            let base = Path::new(base_path(recording));
+            let name = base.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if collection.stream(&name).is_some() {
+                return Err(bad_file(&format!("more than one recording named '{}'", name)));
+            }
+            let meta = read_meta(&base.with_extension("sigmf-meta"))?;
+            collection.streams.push(SigmfStreamRef { name: name.clone(), hash: sha512_hex(&meta) });
+
+            append(&mut builder, &format!("{}/{}.sigmf-meta", dir, name), &meta).map_err(|e| bad_file(&e))?;
+            let data_path = base.with_extension("sigmf-data");
+            builder.append_path_with_name(&data_path, format!("{}/{}.sigmf-data", dir, name))
+                .map_err(|e| SigmfError::BadFile(format!("{}: {}", data_path.display(), e)))?;
+        }
+        if paths.len() > 1 {
+            append(&mut builder, &format!("{}/{}.sigmf-collection", dir, dir), &collection.to_json()).map_err(|e| bad_file(&e))?;
+        }
+        builder.into_inner().map_err(|e| bad_file(&e))?;
+        Ok(())
+    }
+}
+
+fn append(builder: &mut tar::Builder<File>, name: &str, bytes: &[u8]) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bytes.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, name, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sigmf::{SigmfDatatype, SigmfWriter};
+    use crate::test_util::TempDir;
+    use num_complex::c32;
+
+    /// Write a short recording at `base` whose samples count up from `first`.
+    fn record(base: &Path, first: f32, frequency: f64) {
+        let mut writer = SigmfWriter::new(&base.to_string_lossy(), 1000.0, frequency, "test", SigmfDatatype::CF32_LE).unwrap();
+        let samples: Vec<_> = (0..5).map(|i| c32(first + i as f32, 0.0)).collect();
+        writer.write_samples(&samples).unwrap();
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let temp = TempDir::new("archive");
+        let dir = temp.path();
+        record(&dir.join("first"), 0.0, 94.9e6);
+        record(&dir.join("second"), 10.0, 99.7e6);
+
+        let archive_path = dir.join("corpus.sigmf");
+        let archive_path = archive_path.to_str().unwrap();
+        let first = dir.join("first.sigmf-meta");
+        let second = dir.join("second");
+        SigmfArchive::create(archive_path, &[first.to_str().unwrap(), second.to_str().unwrap()], Some("test corpus")).unwrap();
+
+        let archive = SigmfArchive::open(archive_path).unwrap();
+        assert_eq!(archive.names(), vec!["first", "second"]);
+        let collection = archive.collection().unwrap();
+        assert_eq!(collection.description.as_deref(), Some("test corpus"));
+        assert_eq!(collection.streams.len(), 2);
+
+        let mut second = archive.open_recording(Some("second"), false).unwrap();
+        assert_eq!(second.total_samples(), 5);
+        assert_eq!(second.captures()[0].frequency, Some(99.7e6));
+        second.seek_to_sample(3).unwrap();
+        assert_eq!(second.by_ref().collect::<Vec<_>>(), vec![c32(13.0, 0.0), c32(14.0, 0.0)]);
+
+        // The first recording must not run on into the rest of the tar
+        let mapped = archive.open_recording(None, true).unwrap();
+        assert_eq!(mapped.collect::<Vec<_>>(), (0..5).map(|i| c32(i as f32, 0.0)).collect::<Vec<_>>());
+        let totals: Vec<u64> = archive.recordings().map(|r| r.unwrap().total_samples()).collect();
+        assert_eq!(totals, vec![5, 5]);
+    }
+
+    #[test]
+    fn test_archive_member_names() {
+        let temp = TempDir::new("archive-names");
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("site-b")).unwrap();
+        record(&dir.join("pass"), 0.0, 94.9e6);
+        record(&dir.join("site-b").join("pass"), 0.0, 99.7e6);
+
+        // Two recordings would land on the same name inside the archive
+        let duplicate = dir.join("duplicate.sigmf");
+        let (a, b) = (dir.join("pass"), dir.join("site-b").join("pass"));
+        assert!(SigmfArchive::create(duplicate.to_str().unwrap(), &[a.to_str().unwrap(), b.to_str().unwrap()], None).is_err());
+
+        // Recordings in subdirectories are named as the collection lists them
+        let meta = std::fs::read(dir.join("pass.sigmf-meta")).unwrap();
+        let nested = dir.join("nested.sigmf");
+        let mut builder = tar::Builder::new(File::create(&nested).unwrap());
+        append(&mut builder, "nested/site-a/pass.sigmf-meta", &meta).unwrap();
+        builder.append_path_with_name(dir.join("pass.sigmf-data"), "nested/site-a/pass.sigmf-data").unwrap();
+        let mut collection = SigmfCollection::new("", None);
+        collection.streams.push(SigmfStreamRef { name: "site-a/pass".to_string(), hash: sha512_hex(b"edited") });
+        append(&mut builder, "nested/nested.sigmf-collection", &collection.to_json()).unwrap();
+        builder.into_inner().unwrap();
+
+        let archive = SigmfArchive::open(nested.to_str().unwrap()).unwrap();
+        assert_eq!(archive.names(), vec!["site-a/pass"]);
+        assert!(archive.open_recording(None, false).is_err());
+    }
+
+    #[test]
+    fn test_collection() {
+        let temp = TempDir::new("collection");
+        let dir = temp.path();
+        record(&dir.join("a"), 0.0, 96.1e6);
+        record(&dir.join("b"), 0.0, 97.1e6);
+
+        let mut collection = SigmfCollection::new(dir.to_str().unwrap(), None);
+        collection.add(dir.join("a.sigmf-meta").to_str().unwrap()).unwrap();
+        collection.add(dir.join("b").to_str().unwrap()).unwrap();
+        assert!(collection.add("/elsewhere/c").is_err());
+        let path = dir.join("session.sigmf-collection");
+        collection.save(path.to_str().unwrap()).unwrap();
+
+        let collection = SigmfCollection::open(path.to_str().unwrap()).unwrap();
+        let names: Vec<&str> = collection.streams.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(collection.streams[0].hash.len(), 128);
+        let frequencies: Vec<Option<f64>> = collection.recordings().map(|r| r.unwrap().captures()[0].frequency).collect();
+        assert_eq!(frequencies, vec![Some(96.1e6), Some(97.1e6)]);
+
+        // An edited recording no longer matches
+        record(&dir.join("b"), 0.0, 100.5e6);
+        assert!(collection.recordings().nth(1).unwrap().is_err());
+    }
+}
@@ -1,6 +1,10 @@
 use num_complex::Complex32;
+use rradio_dsp::iq_correction::{IqCorrectionConfig, IqCorrector};
 use soapysdr::{Device, Direction, RxStream};
 
+use crate::gain::{GainConfig, GainControl, GainMode};
+use crate::source::{IqSource, SourceError};
+
 static SOAPY_STREAM_SIZE: usize = 32 * 1024;
 
 #[derive(Clone)]
@@ -9,24 +13,58 @@ pub struct SoapyConfig {
     pub station: f32,
     pub bw: f32,
     pub fs: f32,
+    pub correction: IqCorrectionConfig,
+    /// Reference oscillator error in ppm, positive when it runs fast.
+    pub ppm: f64,
+    pub gain: GainControl,
 }
 
 pub struct SoapySdr {
     device: Device,
+    corrector: IqCorrector,
 }
 
 pub struct SoapySdrIqStreamer {
     stream: RxStream<Complex32>,
     buf: Vec<Complex32>,
+    corrector: IqCorrector,
 }
 
 impl SoapySdr {
     pub fn connect(config: &SoapyConfig) -> Result<SoapySdr, soapysdr::Error> {
         let device = Device::new(&*config.filter)?;
+        if config.ppm != 0.0 {
+            device.set_frequency_correction(Direction::Rx, 0, config.ppm)?;
+        }
         device.set_frequency(Direction::Rx, 0, config.station as f64, ())?;
         device.set_sample_rate(Direction::Rx, 0, config.fs as f64)?;
         device.set_bandwidth(Direction::Rx, 0, config.bw as f64)?;
-        Ok(SoapySdr { device })
+        let corrector = IqCorrector::new(config.fs, config.correction);
+        let sdr = SoapySdr { device, corrector };
+        sdr.set_gain(&config.gain.current())?;
+        Ok(sdr)
+    }
+
+    pub fn set_frequency(&self, frequency: f64) -> Result<(), soapysdr::Error> {
+        self.device.set_frequency(Direction::Rx, 0, frequency, ())
+    }
+
+    /// Hardware AGC on or off, then, in manual mode only, overall and
+    /// per-element gains.
+    pub fn set_gain(&self, gain: &GainConfig) -> Result<(), soapysdr::Error> {
+        if let Some(mode) = gain.effective_mode() {
+            self.device.set_gain_mode(Direction::Rx, 0, mode != GainMode::Manual)?;
+        }
+        if gain.effective_mode() != Some(GainMode::Manual) {
+            return Ok(());
+        }
+        if let Some(db) = gain.gain {
+            self.device.set_gain(Direction::Rx, 0, db)?;
+        }
+        for (name, db) in &gain.elements {
+            self.device.set_gain_element(Direction::Rx, 0, name.as_str(), *db)?;
+        }
+        Ok(())
     }
 
     pub fn start_iq(&self) -> Result<SoapySdrIqStreamer, soapysdr::Error> {
@@ -39,6 +77,7 @@ impl SoapySdr {
         Ok(SoapySdrIqStreamer {
             stream,
             buf: vec![Complex32::new(0.0, 0.0); buf_size],
+            corrector: self.corrector.clone(),
         })
     }
 }
@@ -48,6 +87,7 @@ impl SoapySdrIqStreamer {
         let n = self.stream.read(&mut [&mut self.buf], 1_000_000)?;
         data.clear();
         data.extend_from_slice(&self.buf[..n]);
+        self.corrector.process_block(data);
         Ok(())
     }
 
@@ -55,3 +95,65 @@ impl SoapySdrIqStreamer {
         self.stream.deactivate(None)
     }
 }
+
+/// A connected, streaming SoapySDR device.
+pub struct SoapySource {
+    sdr: SoapySdr,
+    streamer: Option<SoapySdrIqStreamer>,
+    gain: GainControl,
+    fs: f32,
+    center: f64,
+}
+
+impl SoapySource {
+    pub fn connect(config: &SoapyConfig) -> Result<SoapySource, SourceError> {
+        let sdr = SoapySdr::connect(config).map_err(|e| SourceError::Connect(e.to_string()))?;
+        let streamer = sdr.start_iq().map_err(|e| SourceError::Connect(format!("start_iq: {}", e)))?;
+        Ok(SoapySource {
+            sdr,
+            streamer: Some(streamer),
+            gain: config.gain.clone(),
+            fs: config.fs,
+            center: config.station as f64,
+        })
+    }
+}
+
+impl IqSource for SoapySource {
+    fn sample_rate(&self) -> f32 {
+        self.fs
+    }
+
+    fn center_frequency(&self) -> f64 {
+        self.center
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        let streamer = self.streamer.as_mut().ok_or_else(|| SourceError::Read("stream closed".to_string()))?;
+        streamer.collect_iq(buf).map_err(|e| SourceError::Read(e.to_string()))?;
+        // A live stream never ends; an empty read is a glitch
+        if buf.is_empty() {
+            return Err(SourceError::Read("no samples".to_string()));
+        }
+        if let Some(gain) = self.gain.take_pending() {
+            match self.sdr.set_gain(&gain) {
+                Ok(()) => eprintln!("SoapySDR gain updated"),
+                Err(e) => eprintln!("SoapySDR gain update failed: {}", e),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        self.sdr.set_frequency(frequency).map_err(|e| SourceError::Retune(e.to_string()))?;
+        self.center = frequency;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        match self.streamer.take() {
+            Some(streamer) => streamer.deactivate().map_err(|e| SourceError::Read(format!("deactivate: {}", e))),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! The interface every IQ source implements, and the streaming loop that
+//! drives any of them.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use num_complex::Complex32;
+use rradio_dsp::buffer::SendBuf;
+
+#[derive(Debug)]
+pub enum SourceError {
+    /// Opening or configuring the source failed.
+    Connect(String),
+    /// A read failed; the stream may recover.
+    Read(String),
+    /// Setting the centre frequency failed; the source stays where it was.
+    Retune(String),
+    /// The source can't do what was asked, e.g. retune a recording.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SourceError::Connect(msg) => write!(f, "connect failed: {}", msg),
+            SourceError::Read(msg) => write!(f, "read failed: {}", msg),
+            SourceError::Retune(msg) => write!(f, "retune failed: {}", msg),
+            SourceError::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+/// A stream of complex baseband samples.
+pub trait IqSource {
+    /// Samples per second.
+    fn sample_rate(&self) -> f32;
+
+    /// Frequency in Hz that 0 Hz in the baseband corresponds to, or 0 if unknown.
+    fn center_frequency(&self) -> f64;
+
+    /// Replace the contents of `buf` with the next block of samples and
+    /// return how many there are. Zero means the stream has ended; live
+    /// sources report a failed read as an error instead.
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError>;
+
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError>;
+
+    /// Stop streaming and release the hardware.
+    fn close(&mut self) -> Result<(), SourceError>;
+}
+
+impl<S: IqSource + ?Sized> IqSource for Box<S> {
+    fn sample_rate(&self) -> f32 {
+        (**self).sample_rate()
+    }
+
+    fn center_frequency(&self) -> f64 {
+        (**self).center_frequency()
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        (**self).read_into(buf)
+    }
+
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        (**self).retune(frequency)
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        (**self).close()
+    }
+}
+
+/// Something that happened to the stream, for logging alongside the samples.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceEvent {
+    /// The source was opened again after the connection was lost.
+    Reconnected,
+    /// A read failed and its block is missing from the stream.
+    DroppedBuffer(String),
+    /// The centre frequency changed to this many Hz.
+    Retuned(f64),
+}
+
+const MAX_RETRIES: u32 = 3;
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Stream blocks from the source made by `connect` into `out` until `done`
+/// is set, the receiver goes away or the source ends.
+///
+/// Failed connects are retried every second, and after `MAX_RETRIES`
+/// consecutive failed reads the source is closed and connected again.
+/// `on_event` hears of each reconnect, lost block and change of centre
+/// frequency along with the number of samples streamed before it.
+pub fn stream_with_reconnect<S, F, E>(done: &AtomicBool, name: &str, mut connect: F, mut out: SendBuf<Vec<Complex32>>, mut on_event: E)
+where
+    S: IqSource,
+    F: FnMut() -> Result<S, SourceError>,
+    E: FnMut(u64, SourceEvent),
+{
+    let mut streamed: u64 = 0;
+    let mut connected_before = false;
+    while !done.load(Ordering::SeqCst) {
+        let mut source = match connect() {
+            Ok(source) => {
+                eprintln!("{} connected", name);
+                if connected_before {
+                    on_event(streamed, SourceEvent::Reconnected);
+                }
+                connected_before = true;
+                source
+            }
+            Err(e) => {
+                eprintln!("{} {}, retrying in 1s...", name, e);
+                std::thread::sleep(RECONNECT_DELAY);
+                continue;
+            }
+        };
+
+        // Stream until error or shutdown
+        let mut consecutive_errors: u32 = 0;
+        let mut frequency = source.center_frequency();
+        while !done.load(Ordering::SeqCst) {
+            let Some(mut buf) = out.get() else {
+                // Receiver is gone
+                let _ = source.close();
+                return;
+            };
+            match source.read_into(&mut buf) {
+                Ok(0) => {
+                    let _ = source.close();
+                    return;
+                }
+                Ok(n) => {
+                    consecutive_errors = 0;
+                    // The block just read is the first at a new frequency
+                    if source.center_frequency() != frequency {
+                        frequency = source.center_frequency();
+                        on_event(streamed, SourceEvent::Retuned(frequency));
+                    }
+                    streamed += n as u64;
+                    out.commit(buf);
+                }
+                Err(e) => {
+                    on_event(streamed, SourceEvent::DroppedBuffer(e.to_string()));
+                    consecutive_errors += 1;
+                    if consecutive_errors >= MAX_RETRIES {
+                        eprintln!("{} {} ({} consecutive failures), reconnecting...", name, e, consecutive_errors);
+                        break;
+                    }
+                    eprintln!("{} {} (attempt {}/{})", name, e, consecutive_errors, MAX_RETRIES);
+                    std::thread::sleep(RETRY_DELAY);
+                }
+            }
+        }
+
+        // Tear down before reconnecting
+        if let Err(e) = source.close() {
+            eprintln!("{} close: {}", name, e);
+        }
+
+        if !done.load(Ordering::SeqCst) {
+            eprintln!("{} connection lost, reconnecting in 1s...", name);
+            std::thread::sleep(RECONNECT_DELAY);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rradio_dsp::buffer::{buf_pair, RecvBufIter};
+
+    /// Yields `blocks` four-sample blocks, failing the third read
+    /// `failures_left` times. Reports 100 Hz from block `retuned_at` on.
+    struct FakeSource {
+        next: usize,
+        blocks: usize,
+        failures_left: u32,
+        retuned_at: usize,
+    }
+
+    impl IqSource for FakeSource {
+        fn sample_rate(&self) -> f32 {
+            1000.0
+        }
+
+        fn center_frequency(&self) -> f64 {
+            if self.next > self.retuned_at { 100.0 } else { 0.0 }
+        }
+
+        fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+            if self.failures_left > 0 && self.next == 2 {
+                self.failures_left -= 1;
+                return Err(SourceError::Read("glitch".to_string()));
+            }
+            buf.clear();
+            if self.next < self.blocks {
+                buf.extend((0..4).map(|i| Complex32::new((self.next * 4 + i) as f32, 0.0)));
+                self.next += 1;
+            }
+            Ok(buf.len())
+        }
+
+        fn retune(&mut self, _frequency: f64) -> Result<(), SourceError> {
+            Err(SourceError::Unsupported("fixed".to_string()))
+        }
+
+        fn close(&mut self) -> Result<(), SourceError> {
+            Ok(())
+        }
+    }
+
+    fn run(connect_failures: usize, read_failures: u32) -> (Vec<f32>, usize, Vec<(u64, SourceEvent)>) {
+        let done = AtomicBool::new(false);
+        let (tx, rx) = buf_pair::<Vec<Complex32>>(4);
+        let mut connects = 0;
+        let mut events = Vec::new();
+        let consumer = std::thread::spawn(move || RecvBufIter::new(rx).map(|x| x.re).collect::<Vec<f32>>());
+        stream_with_reconnect(&done, "fake", || {
+            connects += 1;
+            if connects <= connect_failures {
+                return Err(SourceError::Connect("not there".to_string()));
+            }
+            // Only the first source to open misbehaves
+            let failures_left = if connects == connect_failures + 1 { read_failures } else { 0 };
+            Ok(FakeSource { next: 0, blocks: 3, failures_left, retuned_at: usize::MAX })
+        }, tx, |sample, event| events.push((sample, event)));
+        (consumer.join().unwrap(), connects, events)
+    }
+
+    #[test]
+    fn test_streams_to_end() {
+        let (samples, connects, events) = run(0, 0);
+        assert_eq!(samples, (0..12).map(|x| x as f32).collect::<Vec<f32>>());
+        assert_eq!(connects, 1);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_recovers_from_read_errors() {
+        // Transient errors are retried in place
+        let (samples, connects, events) = run(0, 2);
+        assert_eq!(samples.len(), 12);
+        assert_eq!(connects, 1);
+        let glitch = SourceEvent::DroppedBuffer("read failed: glitch".to_string());
+        assert_eq!(events, vec![(8, glitch.clone()), (8, glitch)]);
+    }
+
+    #[test]
+    fn test_reconnects() {
+        // A failed connect, then a source that keeps failing mid-stream is
+        // reopened and starts over
+        let (samples, connects, events) = run(1, MAX_RETRIES);
+        assert_eq!(connects, 3);
+        assert_eq!(events.last(), Some(&(8, SourceEvent::Reconnected)));
+        assert_eq!(samples.len(), 20);
+        assert_eq!(&samples[..8], &samples[8..16]);
+    }
+
+    #[test]
+    fn test_reports_retunes() {
+        let done = AtomicBool::new(false);
+        let (tx, rx) = buf_pair::<Vec<Complex32>>(4);
+        let consumer = std::thread::spawn(move || RecvBufIter::new(rx).count());
+        let mut source = Some(FakeSource { next: 0, blocks: 3, failures_left: 0, retuned_at: 1 });
+        let mut events = Vec::new();
+        stream_with_reconnect(&done, "fake", || source.take().ok_or(SourceError::Connect("gone".to_string())), tx,
+            |sample, event| events.push((sample, event)));
+        assert_eq!(consumer.join().unwrap(), 12);
+        assert_eq!(events, vec![(4, SourceEvent::Retuned(100.0))]);
+    }
+}
@@ -0,0 +1,32 @@
+//! Helpers shared by the tests in this crate.
+
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp dir, removed with everything in
+/// it when dropped, so a failing test leaves nothing behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// An empty directory named after `name` and this process.
+    pub fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("rradio-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Path of `name` inside the directory, for the APIs that take a `&str`.
+    pub fn file(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
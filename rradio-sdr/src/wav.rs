@@ -0,0 +1,231 @@
+//! Two-channel WAV files holding I on the left and Q on the right, as SDR#,
+//! HDSDR and many older tools record them.
+//!
+//! SDR# and HDSDR add an `auxi` chunk carrying the centre frequency and
+//! start time; without one the centre frequency can still come from their
+//! filenames, e.g. `SDRSharp_20250920_101500Z_96100000Hz_IQ.wav`.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+use num_complex::{c32, Complex32};
+
+use crate::raw::RawIqFormat;
+use crate::sigmf::{RecordingTuner, SigmfDatatype, SigmfError};
+use crate::source::{IqSource, SourceError};
+
+/// Samples per `read_into` block.
+const WAV_BLOCK: usize = 64 * 1024;
+
+/// What an `auxi` chunk records about a capture.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuxiInfo {
+    /// Centre frequency in Hz.
+    pub center_frequency: f64,
+    /// Start of the recording as `YYYY-MM-DDTHH:MM:SS.mmm`, in whatever
+    /// timezone the recorder used (UTC for SDR#, local for HDSDR).
+    pub start_time: String,
+}
+
+impl AuxiInfo {
+    /// Decode the chunk body: two Windows SYSTEMTIMEs (start, stop), then
+    /// u32 fields of which the first is the centre frequency.
+    fn parse(body: &[u8]) -> Option<AuxiInfo> {
+        let word = |i: usize| u16::from_le_bytes([body[2 * i], body[2 * i + 1]]);
+        if body.len() < 36 {
+            return None;
+        }
+        // SYSTEMTIME is year, month, day of week, day, hour, minute, second, millisecond
+        let start_time = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+            word(0), word(1), word(3), word(4), word(5), word(6), word(7));
+        let center_frequency = u32::from_le_bytes([body[32], body[33], body[34], body[35]]) as f64;
+        Some(AuxiInfo { center_frequency, start_time })
+    }
+}
+
+/// Find and decode the `auxi` chunk, wherever it sits in the RIFF file.
+fn read_auxi(file: &mut File) -> std::io::Result<Option<AuxiInfo>> {
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header)?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+    let mut chunk = [0u8; 8];
+    while file.read_exact(&mut chunk).is_ok() {
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+        if &chunk[0..4] == b"auxi" {
+            // The size comes from the file, so read no more than is there
+            let mut body = Vec::new();
+            file.by_ref().take(size).read_to_end(&mut body)?;
+            return Ok(AuxiInfo::parse(&body));
+        }
+        // Chunks are padded to an even length
+        file.seek(SeekFrom::Current((size + size % 2) as i64))?;
+    }
+    Ok(None)
+}
+
+pub struct WavIqSource {
+    reader: hound::WavReader<BufReader<File>>,
+    /// Full scale of integer samples; `None` for float.
+    int_scale: Option<f32>,
+    datatype: SigmfDatatype,
+    sample_rate: f32,
+    auxi: Option<AuxiInfo>,
+    tuner: RecordingTuner,
+    /// I and Q of the block being read, kept between reads.
+    interleaved: Vec<f32>,
+}
+
+impl WavIqSource {
+    /// Open a WAV-IQ file. An explicit `frequency` wins over the `auxi`
+    /// chunk, which wins over the filename; failing all three it is 0.
+    pub fn open(path: &str, frequency: Option<f64>) -> Result<WavIqSource, SigmfError> {
+        let bad_file = |e: &dyn std::fmt::Display| SigmfError::BadFile(format!("{}: {}", path, e));
+
+        let mut file = File::open(path).map_err(|e| bad_file(&e))?;
+        let auxi = read_auxi(&mut file).map_err(|e| bad_file(&e))?;
+
+        let reader = hound::WavReader::open(path).map_err(|e| bad_file(&e))?;
+        let spec = reader.spec();
+        if spec.channels != 2 {
+            return Err(bad_file(&format!("{} channels, IQ needs 2", spec.channels)));
+        }
+        let int_scale = match spec.sample_format {
+            hound::SampleFormat::Int => Some((1u64 << (spec.bits_per_sample - 1)) as f32),
+            hound::SampleFormat::Float if spec.bits_per_sample == 32 => None,
+            hound::SampleFormat::Float => {
+                return Err(SigmfError::UnsupportedDatatype(format!("{}-bit float WAV", spec.bits_per_sample)));
+            }
+        };
+
+        // WAV stores 8-bit samples unsigned and wider ones signed
+        let datatype = match (spec.sample_format, spec.bits_per_sample) {
+            (hound::SampleFormat::Int, 8) => SigmfDatatype::CU8,
+            (hound::SampleFormat::Int, 16) => SigmfDatatype::CI16_LE,
+            (hound::SampleFormat::Int, 32) => SigmfDatatype::CI32_LE,
+            _ => SigmfDatatype::CF32_LE,
+        };
+
+        let frequency = frequency
+            .or(auxi.as_ref().map(|a| a.center_frequency))
+            .or(RawIqFormat::from_filename(path).frequency)
+            .unwrap_or(0.0);
+        let sample_rate = spec.sample_rate as f32;
+        Ok(WavIqSource {
+            reader,
+            int_scale,
+            datatype,
+            sample_rate,
+            auxi,
+            tuner: RecordingTuner::new(sample_rate, frequency),
+            interleaved: Vec::with_capacity(2 * WAV_BLOCK),
+        })
+    }
+
+    /// The SigMF datatype that holds the file's samples without loss.
+    pub fn datatype(&self) -> SigmfDatatype {
+        self.datatype
+    }
+
+    /// Metadata from the file's `auxi` chunk, if it has one.
+    pub fn auxi(&self) -> Option<&AuxiInfo> {
+        self.auxi.as_ref()
+    }
+}
+
+impl IqSource for WavIqSource {
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn center_frequency(&self) -> f64 {
+        self.tuner.tuned()
+    }
+
+    fn read_into(&mut self, buf: &mut Vec<Complex32>) -> Result<usize, SourceError> {
+        buf.clear();
+        let read_error = |e: hound::Error| SourceError::Read(e.to_string());
+        self.interleaved.clear();
+        match self.int_scale {
+            Some(scale) => for sample in self.reader.samples::<i32>().take(2 * WAV_BLOCK) {
+                self.interleaved.push(sample.map_err(read_error)? as f32 / scale);
+            },
+            None => for sample in self.reader.samples::<f32>().take(2 * WAV_BLOCK) {
+                self.interleaved.push(sample.map_err(read_error)?);
+            },
+        }
+        buf.extend(self.interleaved.chunks_exact(2).map(|iq| c32(iq[0], iq[1])));
+        self.tuner.apply(buf);
+        Ok(buf.len())
+    }
+
+    /// Shift the recording so `frequency` sits at 0 Hz.
+    fn retune(&mut self, frequency: f64) -> Result<(), SourceError> {
+        self.tuner.retune(frequency)
+    }
+
+    fn close(&mut self) -> Result<(), SourceError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// A 16-bit WAV-IQ file laid out as SDR# writes it: fmt, auxi, then data.
+    fn sdrsharp_wav(samples: &[(i16, i16)], center: u32) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend(1u16.to_le_bytes());
+        fmt.extend(2u16.to_le_bytes());
+        fmt.extend(48000u32.to_le_bytes());
+        fmt.extend((48000u32 * 4).to_le_bytes());
+        fmt.extend(4u16.to_le_bytes());
+        fmt.extend(16u16.to_le_bytes());
+
+        let mut auxi = Vec::new();
+        for word in [2025u16, 9, 6, 20, 10, 15, 0, 250] {
+            auxi.extend(word.to_le_bytes());
+        }
+        auxi.extend([0u8; 16]);
+        auxi.extend(center.to_le_bytes());
+        auxi.extend(48000u32.to_le_bytes());
+
+        let data: Vec<u8> = samples.iter().flat_map(|&(i, q)| [i.to_le_bytes(), q.to_le_bytes()]).flatten().collect();
+
+        let mut body = b"WAVE".to_vec();
+        for (id, chunk) in [(b"fmt ", fmt), (b"auxi", auxi), (b"data", data)] {
+            body.extend(id);
+            body.extend((chunk.len() as u32).to_le_bytes());
+            body.extend(chunk);
+        }
+        let mut file = b"RIFF".to_vec();
+        file.extend((body.len() as u32).to_le_bytes());
+        file.extend(body);
+        file
+    }
+
+    #[test]
+    fn test_reads_sdrsharp_capture() {
+        let dir = TempDir::new("wav");
+        let path = dir.file("capture.wav");
+        std::fs::write(&path, sdrsharp_wav(&[(16384, -16384), (-32768, 8192)], 96_100_000)).unwrap();
+
+        let mut source = WavIqSource::open(&path, None).unwrap();
+        assert_eq!(source.sample_rate(), 48000.0);
+        assert_eq!(source.center_frequency(), 96.1e6);
+        assert_eq!(source.datatype(), SigmfDatatype::CI16_LE);
+        assert_eq!(source.auxi().unwrap().start_time, "2025-09-20T10:15:00.250");
+
+        let mut buf = Vec::new();
+        assert_eq!(source.read_into(&mut buf).unwrap(), 2);
+        assert_eq!(buf, vec![c32(0.5, -0.5), c32(-1.0, 0.25)]);
+        assert_eq!(source.read_into(&mut buf).unwrap(), 0);
+
+        // An explicit frequency overrides the chunk
+        let source = WavIqSource::open(&path, Some(100e6)).unwrap();
+        assert_eq!(source.center_frequency(), 100e6);
+    }
+}